@@ -3,17 +3,40 @@ Powerful SQL migration toolkit for Rust.
 
 `refinery` makes running migrations for different databases as easy as possible.
 It works by running your migrations on a provided database connection, either by embedding them on your Rust code, or via `refinery_cli`.\
-Currently, [`Postgres`](https://crates.io/crates/postgres), [`Rusqlite`](https://crates.io/crates/rusqlite), and [`Mysql`](https://crates.io/crates/mysql) are supported.\
+Currently, [`Postgres`](https://crates.io/crates/postgres), [`Rusqlite`](https://crates.io/crates/rusqlite), and [`Mysql`](https://crates.io/crates/mysql) are supported,
+as well as [`Diesel`](https://crates.io/crates/diesel) connections with the `diesel-postgres`, `diesel-mysql` and `diesel-sqlite` features,
+and [`Sqlx`](https://crates.io/crates/sqlx) connections and pools, migrated asynchronously, with the `sqlx-postgres`, `sqlx-mysql` and `sqlx-sqlite` features.
+[`Tiberius`](https://crates.io/crates/tiberius) clients of SQL Server are migrated asynchronously with the `tiberius` feature, splitting migrations into batches on `GO` lines.
+Connections checked out of `r2d2`, `bb8` and `deadpool-postgres` pools are supported with the features of the same name.
+With the `rusqlite` and `tokio` features, `AsyncSqliteConnection` migrates a `Rusqlite` connection asynchronously on tokio's blocking thread pool.
+[`Mysql_async`](https://crates.io/crates/mysql_async) migrations run on a single session, of an `AsyncMysqlConnection`, of an `Option<Conn>` or of a connection checked out of the `Pool` for the whole migration.
+[`CockroachDB`](https://www.cockroachlabs.com/) databases are migrated with `Postgres` and `Tokio-postgres` connections wrapped in `CockroachConnection`, locking with a row on a lock table, where grouped runs fail.
+`Postgres`, `Tokio-postgres`, `Rusqlite` and `Mysql` transactions begun by the caller can be migrated too, nesting the migrations in savepoints so that they are undone if the caller rolls the transaction back.
+Other connection types can be migrated by implementing [`Driver`] for them, or [`AsyncTransaction`], [`AsyncQuery`] and [`AsyncTransactional`] to migrate them asynchronously,
+and checked with the [`conformance`] suite.\
 
 `refinery` works best with [`Barrel`](https://crates.io/crates/barrel) but you can also have your migrations on .sql files or use any other Rust crate for schema generation.
 
 ## Usage
 
-- Migrations can be defined in .sql files or Rust modules that must have a function called `migration()` that returns a [`std::string::String`]
+- Migrations can be defined in .sql files or Rust modules that must have a function called `migration()` that returns a [`std::string::String`],
+  or a function called `migrate` that runs the migration itself with the [`Executor`] of the migration's transaction.
 - Migrations, both .sql files and Rust modules must be named in the format `V{1}__{2}.rs ` where `{1}` represents the migration version and `{2}` the name.
 - Migrations can be run either by embedding them on your Rust code with [`embed_migrations!`] and [`include_migration_mods!`] macros, or via `refinery_cli`.
+- The macros also insert a `migrations()` function, to run the migrations of a library crate along the ones of the crate using it,
+  on a namespace of their own so that their versions don't clash, see [`Migration::set_namespace`].
+- Migrations are embedded when the macros are expanded, to embed migrations added since then call [`rerun_if_migrations_changed`] from the crate's `build.rs`.
+- Hooks can be run around the migration cycle by adding a [`Callback`] to the [`Runner`], or with .sql callback files named after the event they run on, i.e. `afterMigrate.sql`, next to the migration files.
 
 [`embed_migrations!`]: macro.embed_migrations.html
+[`conformance`]: conformance/index.html
+[`AsyncTransaction`]: trait.AsyncTransaction.html
+[`AsyncQuery`]: trait.AsyncQuery.html
+[`AsyncTransactional`]: trait.AsyncTransactional.html
+[`Driver`]: trait.Driver.html
+[`Executor`]: trait.Executor.html
+[`Migration::set_namespace`]: struct.Migration.html#method.set_namespace
+[`rerun_if_migrations_changed`]: fn.rerun_if_migrations_changed.html
 [`include_migration_mods!`]: macro.include_migration_mods.html
 
 ### Example
@@ -33,5 +56,22 @@ for more examples refer to the [examples](https://github.com/rust-db/refinery/tr
 */
 
 pub use refinery_core::config;
-pub use refinery_core::{AppliedMigration, AsyncMigrate, Error, Migrate, Migration, Runner};
+pub use refinery_core::conformance;
+pub use refinery_core::dialect;
+pub use refinery_core::rerun_if_migrations_changed;
+pub use refinery_core::{
+    AppliedMigration, AsyncCallback, AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction,
+    AsyncTransactional, Callback, CallbackEvent, Dialect, Driver, Error, Executor, Migrate,
+    Migration, MigrationError, MigrationFn, MigrationFuture, Report, Runner, SqlCallback,
+    Transactional, DEFAULT_MIGRATION_TABLE_NAME,
+};
 pub use refinery_macros::{embed_migrations, include_migration_mods};
+
+#[cfg(all(feature = "rusqlite", feature = "tokio"))]
+pub use refinery_core::AsyncSqliteConnection;
+
+#[cfg(feature = "mysql_async")]
+pub use refinery_core::AsyncMysqlConnection;
+
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+pub use refinery_core::CockroachConnection;
@@ -0,0 +1,187 @@
+#[cfg(feature = "diesel-sqlite")]
+mod diesel {
+    use refinery::{conformance, Error, Executor, Migration, MigrationError, MigrationFn, Runner};
+    use refinery_core::diesel::connection::TransactionManager;
+    use refinery_core::diesel::dsl::sql;
+    use refinery_core::diesel::sql_types::BigInt;
+    use refinery_core::diesel::{self, Connection, RunQueryDsl, SqliteConnection};
+
+    mod embedded {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/sql_migrations");
+    }
+
+    mod callbacks {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/sql_migrations_callbacks");
+    }
+
+    fn connection() -> SqliteConnection {
+        SqliteConnection::establish(":memory:").unwrap()
+    }
+
+    fn count(conn: &SqliteConnection, query: &str) -> i64 {
+        diesel::select(sql::<BigInt>(query))
+            .get_result(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn embedded_applies_migrations() {
+        let mut conn = connection();
+
+        let report = embedded::migrations::runner().run(&mut conn).unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(4, count(&conn, "COUNT(*) FROM refinery_schema_history"));
+
+        let report = embedded::migrations::runner().run(&mut conn).unwrap();
+        assert!(report.applied_migrations().is_empty());
+        assert_eq!(4, report.skipped_migrations().len());
+    }
+
+    #[test]
+    fn conforms_to_driver_checks() {
+        conformance::run(connection);
+    }
+
+    #[test]
+    fn nests_migrations_on_the_caller_transaction() {
+        let mut conn = connection();
+        let manager = conn.transaction_manager();
+        manager.begin_transaction(&conn).unwrap();
+
+        let report = embedded::migrations::runner().run(&mut conn).unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(
+            1,
+            TransactionManager::<SqliteConnection>::get_transaction_depth(
+                conn.transaction_manager()
+            )
+        );
+
+        conn.transaction_manager()
+            .rollback_transaction(&conn)
+            .unwrap();
+        assert_eq!(
+            0,
+            count(
+                &conn,
+                "COUNT(*) FROM sqlite_master WHERE type='table' AND name='refinery_schema_history'"
+            )
+        );
+    }
+
+    // in-memory databases are per connection, so the pool keeps a single one
+    #[cfg(feature = "r2d2")]
+    struct MemoryConnectionManager;
+
+    #[cfg(feature = "r2d2")]
+    impl refinery_core::r2d2::ManageConnection for MemoryConnectionManager {
+        type Connection = SqliteConnection;
+        type Error = diesel::ConnectionError;
+
+        fn connect(&self) -> Result<SqliteConnection, Self::Error> {
+            SqliteConnection::establish(":memory:")
+        }
+
+        fn is_valid(&self, _conn: &mut SqliteConnection) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn has_broken(&self, _conn: &mut SqliteConnection) -> bool {
+            false
+        }
+    }
+
+    #[cfg(feature = "r2d2")]
+    #[test]
+    fn embedded_applies_migrations_on_r2d2_pooled_connection() {
+        let pool = refinery_core::r2d2::Pool::builder()
+            .max_size(1)
+            .build(MemoryConnectionManager)
+            .unwrap();
+        let mut conn = pool.get().unwrap();
+
+        let report = embedded::migrations::runner().run(&mut conn).unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(4, count(&conn, "MAX(version) FROM refinery_schema_history"));
+    }
+
+    #[test]
+    fn embedded_applies_migrations_grouped_transaction() {
+        let mut conn = connection();
+
+        let report = embedded::migrations::runner()
+            .set_grouped(true)
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(
+            1,
+            count(
+                &conn,
+                "COUNT(*) FROM sqlite_master WHERE type='table' AND name='motos'"
+            )
+        );
+    }
+
+    #[test]
+    fn embedded_runs_sql_callbacks() {
+        let mut conn = connection();
+
+        callbacks::migrations::runner().run(&mut conn).unwrap();
+        assert_eq!(3, count(&conn, "COUNT(*) FROM callbacks_log"));
+    }
+
+    fn failing_migration(executor: &mut dyn Executor) -> Result<(), MigrationError> {
+        executor.execute(&["CREATE TABLE cars (id int, name varchar(255))"])?;
+        Err("could not migrate cars".into())
+    }
+
+    #[test]
+    fn rolls_back_failed_code_migration() {
+        let mut conn = connection();
+        let migration = Migration::from_code(
+            "V1__add_cars_table",
+            MigrationFn::Sync(failing_migration),
+            "source",
+        )
+        .unwrap();
+
+        let err = Runner::new(&[migration]).run(&mut conn).unwrap_err();
+        assert!(err.to_string().contains("could not migrate cars"));
+        assert_eq!(
+            0,
+            count(
+                &conn,
+                "COUNT(*) FROM sqlite_master WHERE type='table' AND name='cars'"
+            )
+        );
+        assert_eq!(0, count(&conn, "COUNT(*) FROM refinery_schema_history"));
+    }
+
+    #[test]
+    fn aborts_on_divergent_migration() {
+        let mut conn = connection();
+
+        embedded::migrations::runner().run(&mut conn).unwrap();
+
+        let migration = Migration::from_filename(
+            "V2__add_year_field_to_cars",
+            &"ALTER TABLE cars ADD year INTEGER;",
+        )
+        .unwrap();
+        let err = Runner::new(&[migration.clone()])
+            .set_abort_missing(false)
+            .run(&mut conn)
+            .unwrap_err();
+
+        match err {
+            Error::DivergentVersion(applied, divergent) => {
+                assert_eq!(migration, divergent);
+                assert_eq!("add_cars_and_motos_table", applied.name);
+            }
+            _ => panic!("failed test"),
+        }
+    }
+}
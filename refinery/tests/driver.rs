@@ -0,0 +1,144 @@
+#[cfg(feature = "rusqlite")]
+mod driver {
+    use chrono::{DateTime, Local};
+    use refinery::{
+        conformance, dialect::Sqlite, AppliedMigration, Dialect, Driver, Migration, Runner,
+    };
+    use refinery_core::rusqlite::{params, types::Value, Connection, Error};
+
+    mod embedded {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/sql_migrations");
+    }
+
+    // a connection type of another crate, keeping the statements it was given
+    struct RecordingConnection {
+        connection: Connection,
+        statements: Vec<String>,
+    }
+
+    impl RecordingConnection {
+        fn new() -> RecordingConnection {
+            RecordingConnection {
+                connection: Connection::open_in_memory().unwrap(),
+                statements: Vec::new(),
+            }
+        }
+
+        fn run(&mut self, statement: &str) -> Result<(), Error> {
+            self.statements.push(statement.to_string());
+            self.connection.execute_batch(statement)
+        }
+    }
+
+    impl Driver for RecordingConnection {
+        type Error = Error;
+        type Dialect = Sqlite;
+
+        fn begin(&mut self) -> Result<(), Error> {
+            self.run("BEGIN")
+        }
+
+        fn commit(&mut self) -> Result<(), Error> {
+            self.run("COMMIT")
+        }
+
+        fn rollback(&mut self) -> Result<(), Error> {
+            self.run("ROLLBACK")
+        }
+
+        fn execute(&mut self, queries: &[&str]) -> Result<usize, Error> {
+            for query in queries {
+                self.run(query)?;
+            }
+            Ok(queries.len())
+        }
+
+        fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Error> {
+            self.execute(queries)
+        }
+
+        fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Error> {
+            let mut stmt = self.connection.prepare(query)?;
+            let columns = stmt.column_count();
+            let mut rows = stmt.query(params![])?;
+            let mut result = Vec::new();
+            while let Some(row) = rows.next()? {
+                let mut values = Vec::new();
+                for i in 0..columns {
+                    values.push(match row.get(i)? {
+                        Value::Null => None,
+                        Value::Integer(value) => Some(value.to_string()),
+                        Value::Text(value) => Some(value),
+                        value => panic!("unexpected value {:?}", value),
+                    });
+                }
+                result.push(values);
+            }
+            Ok(result)
+        }
+
+        fn query_history(
+            &mut self,
+            migration_table_name: &str,
+        ) -> Result<Vec<AppliedMigration>, Error> {
+            let query = Sqlite::get_applied_migrations_query(migration_table_name);
+            let mut stmt = self.connection.prepare(&query)?;
+            let mut rows = stmt.query(params![])?;
+            let mut applied = Vec::new();
+            while let Some(row) = rows.next()? {
+                let version: i64 = row.get(0)?;
+                let applied_on: String = row.get(2)?;
+                applied.push(AppliedMigration {
+                    version: version as usize,
+                    name: row.get(1)?,
+                    applied_on: DateTime::parse_from_rfc3339(&applied_on)
+                        .unwrap()
+                        .with_timezone(&Local),
+                    checksum: row.get(3)?,
+                    namespace: row.get(4)?,
+                });
+            }
+            Ok(applied)
+        }
+    }
+
+    #[test]
+    fn conforms_to_driver_checks() {
+        conformance::run(RecordingConnection::new);
+    }
+
+    #[test]
+    fn embedded_applies_migrations() {
+        let mut conn = RecordingConnection::new();
+
+        let report = embedded::migrations::runner().run(&mut conn).unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+
+        let report = embedded::migrations::runner().run(&mut conn).unwrap();
+        assert!(report.applied_migrations().is_empty());
+        assert_eq!(4, report.skipped_migrations().len());
+    }
+
+    #[test]
+    fn applies_each_migration_with_its_history_on_a_transaction() {
+        let mut conn = RecordingConnection::new();
+        let migration = Migration::from_filename(
+            "V1__add_cars_table",
+            "CREATE TABLE cars (id int, name varchar(255));",
+        )
+        .unwrap();
+
+        Runner::new(&[migration]).run(&mut conn).unwrap();
+        let statements = conn
+            .statements
+            .iter()
+            .skip_while(|statement| !statement.starts_with("CREATE TABLE cars"));
+        let statements: Vec<&str> = statements.map(AsRef::as_ref).collect();
+        assert_eq!(3, statements.len());
+        assert!(statements[1].starts_with("INSERT INTO refinery_schema_history"));
+        assert_eq!("COMMIT", statements[2]);
+        let begin = conn.statements.len() - statements.len() - 1;
+        assert_eq!("BEGIN", conn.statements[begin]);
+    }
+}
@@ -0,0 +1,8 @@
+use refinery::{AsyncExecutor, MigrationError};
+
+pub async fn migrate(executor: &mut dyn AsyncExecutor) -> Result<(), MigrationError> {
+    executor
+        .execute(&["CREATE TABLE persons (id int, name varchar(255), city varchar(255))"])
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,5 @@
+pub fn migration() -> String {
+    "CREATE TABLE persons (id int, name varchar(255), city varchar(255));
+    INSERT INTO persons (id, name, city) VALUES (1, 'john legend', 'New York'), (2, 'mary', NULL);"
+        .to_string()
+}
@@ -0,0 +1,13 @@
+use refinery::{Executor, MigrationError};
+
+pub fn migrate(executor: &mut dyn Executor) -> Result<(), MigrationError> {
+    for row in executor.query("SELECT id, name FROM persons")? {
+        let (id, name) = (row[0].clone().unwrap(), row[1].clone().unwrap_or_default());
+        executor.execute(&[&format!(
+            "UPDATE persons SET name = '{}' WHERE id = {}",
+            name.to_uppercase(),
+            id
+        )])?;
+    }
+    Ok(())
+}
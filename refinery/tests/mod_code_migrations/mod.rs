@@ -0,0 +1 @@
+refinery::include_migration_mods!("./tests/mod_code_migrations");
@@ -9,9 +9,9 @@ mod postgres {
     use predicates::str::contains;
     use refinery::{
         config::{migrate_from_config, Config, ConfigDbType},
-        Error, Migrate, Migration,
+        conformance, CockroachConnection, Error, Migrate, Migration, Runner,
     };
-    use refinery_core::postgres::{Client, NoTls};
+    use refinery_core::postgres::{Client, NoTls, Transaction};
     use std::process::Command;
 
     mod embedded {
@@ -463,6 +463,270 @@ mod postgres {
         });
     }
 
+    #[test]
+    fn conforms_to_driver_checks() {
+        run_test(|| {
+            conformance::run(|| {
+                clean_database();
+                Client::connect("postgres://postgres@localhost:5432/postgres", NoTls).unwrap()
+            });
+        })
+    }
+
+    #[test]
+    fn conforms_to_driver_checks_on_transaction() {
+        fn on_transaction(check: impl Fn(&mut Transaction<'_>)) {
+            clean_database();
+            let mut client =
+                Client::connect("postgres://postgres@localhost:5432/postgres", NoTls).unwrap();
+            let mut transaction = client.transaction().unwrap();
+            check(&mut transaction);
+        }
+
+        run_test(|| {
+            on_transaction(|transaction| conformance::commits_transactions(transaction));
+            on_transaction(|transaction| conformance::rolls_back_transactions(transaction));
+            on_transaction(|transaction| {
+                conformance::executes_outside_of_transactions(transaction)
+            });
+            on_transaction(|transaction| conformance::queries_rows_as_text(transaction));
+            on_transaction(|transaction| conformance::keeps_history(transaction));
+            on_transaction(|transaction| conformance::migrates(transaction));
+        })
+    }
+
+    #[test]
+    fn embedded_applies_migration_on_caller_transaction() {
+        run_test(|| {
+            let mut client =
+                Client::connect("postgres://postgres@localhost:5432/postgres", NoTls).unwrap();
+
+            let mut transaction = client.transaction().unwrap();
+            let report = embedded::migrations::runner()
+                .run(&mut transaction)
+                .unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+            transaction.commit().unwrap();
+
+            let rows = client
+                .query("SELECT MAX(version) FROM refinery_schema_history", &[])
+                .unwrap();
+            let current: i32 = rows[0].get(0);
+            assert_eq!(4, current);
+        });
+    }
+
+    #[test]
+    fn embedded_migrations_are_undone_with_caller_transaction() {
+        run_test(|| {
+            let mut client =
+                Client::connect("postgres://postgres@localhost:5432/postgres", NoTls).unwrap();
+
+            let mut transaction = client.transaction().unwrap();
+            let result = broken::migrations::runner().run(&mut transaction);
+            assert!(result.is_err());
+
+            // the failed migration only rolled back its savepoint, the caller's transaction goes on
+            let rows = transaction
+                .query("SELECT MAX(version) FROM refinery_schema_history", &[])
+                .unwrap();
+            let current: i32 = rows[0].get(0);
+            assert_eq!(2, current);
+            transaction.rollback().unwrap();
+
+            let rows = client
+                .query(
+                    "SELECT COUNT(*) FROM information_schema.tables WHERE table_name = 'refinery_schema_history'",
+                    &[],
+                )
+                .unwrap();
+            let count: i64 = rows[0].get(0);
+            assert_eq!(0, count);
+        });
+    }
+
+    // the local postgres stands in for CockroachDB, which speaks its protocol,
+    // wrapped in CockroachConnection the client is migrated the way CockroachDB is
+    fn cockroach_client() -> CockroachConnection<Client> {
+        CockroachConnection::new(
+            Client::connect("postgres://postgres@localhost:5432/postgres", NoTls).unwrap(),
+        )
+    }
+
+    #[test]
+    fn cockroach_conforms_to_driver_checks() {
+        run_test(|| {
+            conformance::run(|| {
+                clean_database();
+                cockroach_client()
+            });
+        })
+    }
+
+    #[test]
+    fn cockroach_fails_on_grouped_migrations() {
+        run_test(|| {
+            let mut client = cockroach_client();
+
+            let err = embedded::migrations::runner()
+                .set_grouped(true)
+                .run(&mut client)
+                .unwrap_err();
+            match err {
+                Error::GroupedMigrationsNotSupported => {}
+                err => panic!("unexpected error {}", err),
+            }
+
+            // nothing was migrated, not even the migrations table was created
+            let mut client = client.into_inner();
+            let rows = client
+                .query(
+                    "SELECT COUNT(*) FROM information_schema.tables WHERE table_name = 'refinery_schema_history'",
+                    &[],
+                )
+                .unwrap();
+            let count: i64 = rows[0].get(0);
+            assert_eq!(0, count);
+        });
+    }
+
+    #[test]
+    fn cockroach_upgrades_migrations_table_without_namespace_column() {
+        run_test(|| {
+            let mut client =
+                Client::connect("postgres://postgres@localhost:5432/postgres", NoTls).unwrap();
+            let mut migrations = embedded::migrations::migrations();
+            migrations.sort();
+            // a migrations table as created before namespaces were kept on it, with the first migration applied
+            client
+                .batch_execute(
+                    "CREATE TABLE refinery_schema_history( \
+                     version INT4 PRIMARY KEY,\
+                     name VARCHAR(255),\
+                     applied_on VARCHAR(255),
+                     checksum VARCHAR(255));",
+                )
+                .unwrap();
+            client.batch_execute(&migrations[0].sql).unwrap();
+            client
+                .execute(
+                    "INSERT INTO refinery_schema_history (version, name, applied_on, checksum) VALUES ($1, $2, $3, $4)",
+                    &[
+                        &(migrations[0].version as i32),
+                        &migrations[0].name,
+                        &Local::now().to_rfc3339(),
+                        &migrations[0].checksum().to_string(),
+                    ],
+                )
+                .unwrap();
+            let mut client = CockroachConnection::new(client);
+
+            let report = Runner::new(&migrations).run(&mut client).unwrap();
+            assert_eq!(3, report.applied_migrations().len());
+            assert_eq!(1, report.skipped_migrations().len());
+
+            let mut client = client.into_inner();
+            let rows = client
+                .query(
+                    "SELECT COUNT(*) FROM refinery_schema_history WHERE namespace = ''",
+                    &[],
+                )
+                .unwrap();
+            let count: i64 = rows[0].get(0);
+            assert_eq!(4, count);
+        });
+    }
+
+    #[test]
+    fn cockroach_holds_a_lock_row_while_migrating() {
+        run_test(|| {
+            let mut client = cockroach_client();
+            let migration = Migration::from_filename(
+                "V1__copy_lock_rows",
+                "CREATE TABLE lock_rows AS SELECT id FROM refinery_schema_history_lock;",
+            )
+            .unwrap();
+
+            Runner::new(&[migration]).run(&mut client).unwrap();
+
+            let mut client = client.into_inner();
+            let rows = client.query("SELECT id FROM lock_rows", &[]).unwrap();
+            assert_eq!(1, rows.len());
+            let rows = client
+                .query("SELECT id FROM refinery_schema_history_lock", &[])
+                .unwrap();
+            assert!(rows.is_empty());
+        });
+    }
+
+    #[test]
+    fn cockroach_fails_while_another_runner_holds_the_lock() {
+        run_test(|| {
+            let mut client =
+                Client::connect("postgres://postgres@localhost:5432/postgres", NoTls).unwrap();
+            client
+                .batch_execute(
+                    "CREATE TABLE refinery_schema_history_lock (id INT4 PRIMARY KEY, owner VARCHAR(255) NOT NULL, locked_at TIMESTAMPTZ NOT NULL); \
+                     INSERT INTO refinery_schema_history_lock (id, owner, locked_at) VALUES (1, 'other', now());",
+                )
+                .unwrap();
+            let mut client = CockroachConnection::new(client);
+
+            let err = embedded::migrations::runner().run(&mut client).unwrap_err();
+            match err {
+                Error::MigrationsTableLocked(table) => {
+                    assert_eq!("refinery_schema_history", table)
+                }
+                err => panic!("unexpected error {}", err),
+            }
+
+            // the row of the runner holding the lock is left alone, and nothing was migrated
+            let mut client = client.into_inner();
+            let rows = client
+                .query("SELECT owner FROM refinery_schema_history_lock", &[])
+                .unwrap();
+            assert_eq!(1, rows.len());
+            let owner: String = rows[0].get(0);
+            assert_eq!("other", owner);
+            let rows = client
+                .query(
+                    "SELECT COUNT(*) FROM information_schema.tables WHERE table_name = 'refinery_schema_history'",
+                    &[],
+                )
+                .unwrap();
+            let count: i64 = rows[0].get(0);
+            assert_eq!(0, count);
+        });
+    }
+
+    #[test]
+    fn cockroach_takes_over_a_lock_left_behind_an_hour_ago() {
+        run_test(|| {
+            let mut client =
+                Client::connect("postgres://postgres@localhost:5432/postgres", NoTls).unwrap();
+            client
+                .batch_execute(
+                    "CREATE TABLE refinery_schema_history_lock (id INT4 PRIMARY KEY, owner VARCHAR(255) NOT NULL, locked_at TIMESTAMPTZ NOT NULL); \
+                     INSERT INTO refinery_schema_history_lock (id, owner, locked_at) VALUES (1, 'stopped', now() - INTERVAL '2 hours');",
+                )
+                .unwrap();
+            let mut client = CockroachConnection::new(client);
+
+            embedded::migrations::runner().run(&mut client).unwrap();
+
+            let mut client = client.into_inner();
+            let rows = client
+                .query("SELECT MAX(version) FROM refinery_schema_history", &[])
+                .unwrap();
+            let current: i32 = rows[0].get(0);
+            assert_eq!(4, current);
+            let rows = client
+                .query("SELECT id FROM refinery_schema_history_lock", &[])
+                .unwrap();
+            assert!(rows.is_empty());
+        });
+    }
+
     #[test]
     fn migrates_from_config() {
         run_test(|| {
@@ -477,6 +741,32 @@ mod postgres {
         })
     }
 
+    #[test]
+    fn migrates_cockroach_from_config() {
+        run_test(|| {
+            let config = Config::new(ConfigDbType::Cockroach)
+                .set_db_name("postgres")
+                .set_db_user("postgres")
+                .set_db_host("localhost")
+                .set_db_port("5432");
+
+            let migrations = get_migrations();
+            migrate_from_config(&config, false, true, true, &migrations).unwrap();
+
+            let mut client =
+                Client::connect("postgres://postgres@localhost:5432/postgres", NoTls).unwrap();
+            let rows = client
+                .query("SELECT MAX(version) FROM refinery_schema_history", &[])
+                .unwrap();
+            let current: i32 = rows[0].get(0);
+            assert_eq!(5, current);
+            let rows = client
+                .query("SELECT id FROM refinery_schema_history_lock", &[])
+                .unwrap();
+            assert!(rows.is_empty());
+        })
+    }
+
     #[test]
     fn migrates_from_cli() {
         run_test(|| {
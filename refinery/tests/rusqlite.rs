@@ -1,19 +1,25 @@
 use barrel::backend::Sqlite as Sql;
+mod mod_async_migrations;
+mod mod_code_migrations;
 mod mod_migrations;
 
 #[cfg(feature = "rusqlite")]
 mod rusqlite {
-    use super::mod_migrations;
+    use super::{mod_async_migrations, mod_code_migrations, mod_migrations};
     use assert_cmd::prelude::*;
     use chrono::{DateTime, Local};
     use predicates::str::contains;
     use refinery::{
         config::{migrate_from_config, Config, ConfigDbType},
-        Error, Migrate, Migration,
+        conformance, Callback, Error, Executor, Migrate, Migration, MigrationError, MigrationFn,
+        Runner, DEFAULT_MIGRATION_TABLE_NAME,
     };
-    use refinery_core::rusqlite::{Connection, OptionalExtension, NO_PARAMS};
+    use refinery_core::rusqlite::{params, Connection, OptionalExtension, Transaction};
+    use std::collections::HashMap;
     use std::fs::{self, File};
     use std::process::Command;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
     mod embedded {
         use refinery::embed_migrations;
@@ -30,6 +36,63 @@ mod rusqlite {
         embed_migrations!("./tests/sql_migrations_missing");
     }
 
+    mod callbacks {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/sql_migrations_callbacks");
+    }
+
+    mod namespaced {
+        use refinery::embed_migrations;
+        embed_migrations!(namespace = "plugin", "./tests/sql_migrations_namespaced");
+    }
+
+    mod merged {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/sql_migrations/V1-2", "./tests/sql_migrations/V3");
+    }
+
+    // counts the invocations of each hook
+    #[derive(Default)]
+    struct CountingCallback {
+        before_each: Arc<AtomicUsize>,
+        after_each: Arc<AtomicUsize>,
+        after_each_error: Arc<AtomicUsize>,
+    }
+
+    impl Callback for CountingCallback {
+        fn before_each(
+            &self,
+            _migration: &Migration,
+            _executor: &mut dyn Executor,
+        ) -> Result<(), Error> {
+            self.before_each.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn after_each(
+            &self,
+            _migration: &Migration,
+            _executor: &mut dyn Executor,
+        ) -> Result<(), Error> {
+            self.after_each.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn after_each_error(
+            &self,
+            migration: &Migration,
+            _error: &Error,
+            executor: &mut dyn Executor,
+        ) -> Result<(), Error> {
+            self.after_each_error.fetch_add(1, Ordering::SeqCst);
+            executor.execute(&[&format!(
+                "CREATE TABLE failed_migration AS SELECT {} AS version",
+                migration.version
+            )])?;
+            Ok(())
+        }
+    }
+
     fn run_test<T>(test: T)
     where
         T: FnOnce() + std::panic::UnwindSafe,
@@ -85,7 +148,7 @@ mod rusqlite {
         let table_name: String = conn
             .query_row(
                 "SELECT name FROM sqlite_master WHERE type='table' AND name='refinery_schema_history'",
-                NO_PARAMS,
+                params![],
                 |row| row.get(0),
             )
             .unwrap();
@@ -102,7 +165,7 @@ mod rusqlite {
         let table_name: String = conn
             .query_row(
                 "SELECT name FROM sqlite_master WHERE type='table' AND name='refinery_schema_history'",
-                NO_PARAMS,
+                params![],
                 |row| row.get(0),
             )
             .unwrap();
@@ -121,7 +184,7 @@ mod rusqlite {
         )
         .unwrap();
         let (name, city): (String, String) = conn
-            .query_row("SELECT name, city FROM persons", NO_PARAMS, |row| {
+            .query_row("SELECT name, city FROM persons", params![], |row| {
                 Ok((row.get(0).unwrap(), row.get(1).unwrap()))
             })
             .unwrap();
@@ -144,7 +207,7 @@ mod rusqlite {
         )
         .unwrap();
         let (name, city): (String, String) = conn
-            .query_row("SELECT name, city FROM persons", NO_PARAMS, |row| {
+            .query_row("SELECT name, city FROM persons", params![], |row| {
                 Ok((row.get(0).unwrap(), row.get(1).unwrap()))
             })
             .unwrap();
@@ -161,7 +224,7 @@ mod rusqlite {
         let current: u32 = conn
             .query_row(
                 "SELECT MAX(version) FROM refinery_schema_history",
-                NO_PARAMS,
+                params![],
                 |row| row.get(0),
             )
             .unwrap();
@@ -170,7 +233,7 @@ mod rusqlite {
         let applied_on: DateTime<Local> = conn
             .query_row(
                 "SELECT applied_on FROM refinery_schema_history where version=(SELECT MAX(version) from refinery_schema_history)",
-                NO_PARAMS,
+                params![],
                 |row| {
                     let applied_on: String = row.get(0).unwrap();
                     Ok(DateTime::parse_from_rfc3339(&applied_on).unwrap().with_timezone(&Local))
@@ -180,6 +243,169 @@ mod rusqlite {
         assert_eq!(Local::today(), applied_on.date());
     }
 
+    #[test]
+    fn embedded_merges_several_locations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let report = merged::migrations::runner().run(&mut conn).unwrap();
+        let applied: Vec<usize> = report
+            .applied_migrations()
+            .iter()
+            .map(|migration| migration.version)
+            .collect();
+        assert_eq!(vec![1, 2, 3], applied);
+
+        let current: u32 = conn
+            .query_row(
+                "SELECT MAX(version) FROM refinery_schema_history",
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(3, current);
+    }
+
+    #[test]
+    fn embedded_runs_migrations_of_several_namespaces() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [
+            embedded::migrations::migrations(),
+            namespaced::migrations::migrations(),
+        ]
+        .concat();
+
+        let report = Runner::new(&migrations).run(&mut conn).unwrap();
+        assert_eq!(6, report.applied_migrations().len());
+        assert_eq!("plugin", report.applied_migrations()[4].namespace());
+
+        for (namespace, version) in &[("", 4), ("plugin", 2)] {
+            let current: u32 = conn
+                .query_row(
+                    "SELECT MAX(version) FROM refinery_schema_history WHERE namespace = ?",
+                    &[namespace],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(*version, current);
+        }
+
+        let report = Runner::new(&migrations).run(&mut conn).unwrap();
+        assert!(report.applied_migrations().is_empty());
+        assert_eq!(6, report.skipped_migrations().len());
+    }
+
+    #[test]
+    fn runs_migrations_of_the_namespace_set() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [
+            embedded::migrations::migrations(),
+            namespaced::migrations::migrations(),
+        ]
+        .concat();
+
+        let report = Runner::new(&migrations)
+            .set_namespace("plugin")
+            .run(&mut conn)
+            .unwrap();
+        let applied: Vec<String> = report
+            .applied_migrations()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            vec![
+                "plugin/V1__add_plugins_table",
+                "plugin/V2__add_enabled_to_plugins"
+            ],
+            applied
+        );
+
+        // the migrations applied on the plugin namespace are not missing from the default one
+        let report = Runner::new(&embedded::migrations::migrations())
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+    }
+
+    #[test]
+    fn rejects_invalid_migration_table_name() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let err = embedded::migrations::runner()
+            .set_migration_table_name("history; DROP TABLE cars")
+            .run(&mut conn)
+            .unwrap_err();
+        match err {
+            Error::InvalidTableName(name) => assert_eq!("history; DROP TABLE cars", name),
+            _ => panic!("failed test"),
+        }
+    }
+
+    #[test]
+    fn upgrades_migrations_table_without_namespace_column() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let mut migrations = embedded::migrations::migrations();
+        migrations.sort();
+        // a migrations table as created before namespaces were kept on it, with the first migration applied
+        conn.execute_batch(
+            "CREATE TABLE refinery_schema_history( \
+             version INT4 PRIMARY KEY,\
+             name VARCHAR(255),\
+             applied_on VARCHAR(255),
+             checksum VARCHAR(255));",
+        )
+        .unwrap();
+        conn.execute_batch(&migrations[0].sql).unwrap();
+        conn.execute(
+            "INSERT INTO refinery_schema_history (version, name, applied_on, checksum) VALUES (?, ?, ?, ?)",
+            &[
+                &migrations[0].version.to_string(),
+                &migrations[0].name,
+                &Local::now().to_rfc3339(),
+                &migrations[0].checksum().to_string(),
+            ],
+        )
+        .unwrap();
+
+        let report = Runner::new(&[migrations, namespaced::migrations::migrations()].concat())
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(5, report.applied_migrations().len());
+        assert_eq!(1, report.skipped_migrations().len());
+
+        let namespaces: i64 = conn
+            .query_row(
+                "SELECT COUNT(DISTINCT namespace) FROM refinery_schema_history",
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(2, namespaces);
+    }
+
+    #[test]
+    fn fails_on_migrations_table_backup_left_behind() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        // a backup left behind by an upgrade of the migrations table that stopped halfway
+        conn.execute_batch(
+            "CREATE TABLE refinery_schema_history_backup( \
+             version INT4 PRIMARY KEY,\
+             name VARCHAR(255),\
+             applied_on VARCHAR(255),
+             checksum VARCHAR(255));",
+        )
+        .unwrap();
+
+        let err = embedded::migrations::runner().run(&mut conn).unwrap_err();
+
+        match err {
+            Error::MigrationsTableBackup(table) => {
+                assert_eq!(DEFAULT_MIGRATION_TABLE_NAME, table);
+            }
+            _ => panic!("failed test"),
+        }
+    }
+
     #[test]
     fn embedded_updates_schema_history_grouped_transaction() {
         let mut conn = Connection::open_in_memory().unwrap();
@@ -192,7 +418,7 @@ mod rusqlite {
         let current: u32 = conn
             .query_row(
                 "SELECT MAX(version) FROM refinery_schema_history",
-                NO_PARAMS,
+                params![],
                 |row| row.get(0),
             )
             .unwrap();
@@ -201,7 +427,7 @@ mod rusqlite {
         let applied_on: DateTime<Local> = conn
             .query_row(
                 "SELECT applied_on FROM refinery_schema_history where version=(SELECT MAX(version) from refinery_schema_history)",
-                NO_PARAMS,
+                params![],
                 |row| {
                     let applied_on: String = row.get(0).unwrap();
                     Ok(DateTime::parse_from_rfc3339(&applied_on).unwrap().with_timezone(&Local))
@@ -221,7 +447,7 @@ mod rusqlite {
         let current: u32 = conn
             .query_row(
                 "SELECT MAX(version) FROM refinery_schema_history",
-                NO_PARAMS,
+                params![],
                 |row| row.get(0),
             )
             .unwrap();
@@ -240,7 +466,7 @@ mod rusqlite {
         let query: Option<u32> = conn
             .query_row(
                 "SELECT version FROM refinery_schema_history",
-                NO_PARAMS,
+                params![],
                 |row| row.get(0),
             )
             .optional()
@@ -255,7 +481,7 @@ mod rusqlite {
         let table_name: String = conn
             .query_row(
                 "SELECT name FROM sqlite_master WHERE type='table' AND name='refinery_schema_history'",
-                NO_PARAMS,
+                params![],
                 |row| row.get(0),
             )
             .unwrap();
@@ -274,7 +500,7 @@ mod rusqlite {
         )
         .unwrap();
         let (name, city): (String, String) = conn
-            .query_row("SELECT name, city FROM persons", NO_PARAMS, |row| {
+            .query_row("SELECT name, city FROM persons", params![], |row| {
                 Ok((row.get(0).unwrap(), row.get(1).unwrap()))
             })
             .unwrap();
@@ -291,7 +517,7 @@ mod rusqlite {
         let current: u32 = conn
             .query_row(
                 "SELECT MAX(version) FROM refinery_schema_history",
-                NO_PARAMS,
+                params![],
                 |row| row.get(0),
             )
             .unwrap();
@@ -300,7 +526,7 @@ mod rusqlite {
         let applied_on: DateTime<Local> = conn
             .query_row(
                 "SELECT applied_on FROM refinery_schema_history where version=(SELECT MAX(version) from refinery_schema_history)",
-                NO_PARAMS,
+                params![],
                 |row| {
                     let applied_on: String = row.get(0).unwrap();
                     Ok(DateTime::parse_from_rfc3339(&applied_on).unwrap().with_timezone(&Local))
@@ -310,6 +536,230 @@ mod rusqlite {
         assert_eq!(Local::today(), applied_on.date());
     }
 
+    #[test]
+    fn mod_runs_code_migration_on_migration_transaction() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let report = mod_code_migrations::runner().run(&mut conn).unwrap();
+        assert_eq!(2, report.applied_migrations().len());
+
+        let names: Vec<String> = conn
+            .prepare("SELECT name FROM persons ORDER BY id")
+            .unwrap()
+            .query_map(params![], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(vec!["JOHN LEGEND", "MARY"], names);
+
+        let checksum: String = conn
+            .query_row(
+                "SELECT checksum FROM refinery_schema_history where version = 2",
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let source = Migration::from_filename(
+            "V2__capitalize_persons_names",
+            include_str!("./mod_code_migrations/V2__capitalize_persons_names.rs"),
+        )
+        .unwrap();
+        assert_eq!(source.checksum().to_string(), checksum);
+    }
+
+    fn failing_migration(executor: &mut dyn Executor) -> Result<(), MigrationError> {
+        executor.execute(&["CREATE TABLE cars (id int, name varchar(255))"])?;
+        Err("could not migrate cars".into())
+    }
+
+    #[test]
+    fn rolls_back_failed_code_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migration = Migration::from_code(
+            "V1__add_cars_table",
+            MigrationFn::Sync(failing_migration),
+            "source",
+        )
+        .unwrap();
+
+        let err = Runner::new(&[migration]).run(&mut conn).unwrap_err();
+        assert!(err.to_string().contains("could not migrate cars"));
+
+        let cars: Option<String> = conn
+            .query_row(
+                "SELECT name FROM sqlite_master WHERE type='table' AND name='cars'",
+                params![],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap();
+        assert!(cars.is_none());
+        let applied: u32 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM refinery_schema_history",
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(0, applied);
+    }
+
+    #[test]
+    fn fails_running_async_code_migration_on_sync_runner() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let err = mod_async_migrations::runner().run(&mut conn).unwrap_err();
+        match err {
+            Error::IncompatibleMigration(migration, _) => assert_eq!(1, migration.version),
+            _ => panic!("test failed"),
+        }
+    }
+
+    #[test]
+    fn embedded_runs_sql_callbacks() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        callbacks::migrations::runner().run(&mut conn).unwrap();
+
+        let mut stmt = conn.prepare("SELECT event FROM callbacks_log").unwrap();
+        let events: Vec<String> = stmt
+            .query_map(params![], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(vec!["afterEach", "afterEach", "afterMigrate"], events);
+    }
+
+    #[test]
+    fn runs_callbacks_around_each_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let callback = CountingCallback::default();
+        let before_each = callback.before_each.clone();
+        let after_each = callback.after_each.clone();
+        let after_each_error = callback.after_each_error.clone();
+
+        let result = broken::migrations::runner()
+            .add_callback(callback)
+            .run(&mut conn);
+
+        assert!(result.is_err());
+        assert_eq!(3, before_each.load(Ordering::SeqCst));
+        assert_eq!(2, after_each.load(Ordering::SeqCst));
+        assert_eq!(1, after_each_error.load(Ordering::SeqCst));
+        let failed: u32 = conn
+            .query_row("SELECT version FROM failed_migration", params![], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(3, failed);
+    }
+
+    #[test]
+    fn replaces_placeholders_keeping_checksum() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migration = Migration::from_filename(
+            "V1__initial",
+            "CREATE TABLE ${table} (id int, name varchar(255));",
+        )
+        .unwrap();
+        let checksum = migration.checksum();
+
+        let mut placeholders = HashMap::new();
+        placeholders.insert("table".to_string(), "persons".to_string());
+        Runner::new(&[migration.clone()])
+            .set_placeholders(placeholders)
+            .run(&mut conn)
+            .unwrap();
+
+        conn.execute(
+            "INSERT INTO persons (id, name) VALUES (1, 'John')",
+            params![],
+        )
+        .unwrap();
+        let applied_checksum: String = conn
+            .query_row(
+                "SELECT checksum FROM refinery_schema_history",
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(checksum.to_string(), applied_checksum);
+
+        // other placeholder values don't make the applied migration divergent
+        let mut placeholders = HashMap::new();
+        placeholders.insert("table".to_string(), "people".to_string());
+        let report = Runner::new(&[migration])
+            .set_placeholders(placeholders)
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(1, report.skipped_migrations().len());
+    }
+
+    #[test]
+    fn fails_on_missing_placeholder() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migration =
+            Migration::from_filename("V1__initial", "CREATE TABLE ${schema}.${table} (id int);")
+                .unwrap();
+
+        let mut placeholders = HashMap::new();
+        placeholders.insert("table".to_string(), "persons".to_string());
+        let err = Runner::new(&[migration])
+            .set_placeholders(placeholders)
+            .run(&mut conn)
+            .unwrap_err();
+
+        match err {
+            Error::MissingPlaceholder(placeholder, migration) => {
+                assert_eq!("schema", placeholder);
+                assert_eq!("initial", migration.name);
+            }
+            _ => panic!("failed test"),
+        }
+    }
+
+    #[test]
+    fn replaces_placeholders_on_sql_callbacks() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let mut placeholders = HashMap::new();
+        placeholders.insert("table".to_string(), "persons".to_string());
+        placeholders.insert("name".to_string(), "John".to_string());
+        Runner::from_directory("tests/sql_migrations_placeholders")
+            .unwrap()
+            .set_placeholders(placeholders)
+            .run(&mut conn)
+            .unwrap();
+
+        let name: String = conn
+            .query_row("SELECT name FROM persons WHERE id = 1", params![], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!("John", name);
+    }
+
+    #[test]
+    fn fails_on_missing_sql_callback_placeholder() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let mut placeholders = HashMap::new();
+        placeholders.insert("table".to_string(), "persons".to_string());
+        let err = Runner::from_directory("tests/sql_migrations_placeholders")
+            .unwrap()
+            .set_placeholders(placeholders)
+            .run(&mut conn)
+            .unwrap_err();
+
+        match err {
+            Error::MissingCallbackPlaceholder(placeholder, callback) => {
+                assert_eq!("name", placeholder);
+                assert_eq!("afterMigrate", callback);
+            }
+            _ => panic!("failed test"),
+        }
+    }
+
     #[test]
     fn applies_new_migration() {
         let mut conn = Connection::open_in_memory().unwrap();
@@ -324,7 +774,7 @@ mod rusqlite {
         let (current, checksum): (u32, String) = conn
             .query_row(
                 "SELECT version, checksum FROM refinery_schema_history where version = (SELECT MAX(version) from refinery_schema_history)",
-                NO_PARAMS,
+                params![],
                 |row| Ok((row.get(0).unwrap(), row.get(1).unwrap())),
             )
             .unwrap();
@@ -332,6 +782,20 @@ mod rusqlite {
         assert_eq!(mchecksum.to_string(), checksum);
     }
 
+    #[test]
+    fn reports_applied_and_skipped_migrations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let report = embedded::migrations::runner().run(&mut conn).unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert!(report.skipped_migrations().is_empty());
+
+        let migrations = get_migrations();
+        let report = Runner::new(&migrations).run(&mut conn).unwrap();
+        assert_eq!(&migrations[4..], report.applied_migrations());
+        assert_eq!(4, report.skipped_migrations().len());
+    }
+
     #[test]
     fn aborts_on_missing_migration_on_filesystem() {
         let mut conn = Connection::open_in_memory().unwrap();
@@ -423,6 +887,90 @@ mod rusqlite {
         migrate_from_config(&config, false, true, true, &migrations).unwrap();
     }
 
+    #[test]
+    fn migrate_from_config_honors_config_settings() {
+        let db = tempfile::NamedTempFile::new_in(".").unwrap();
+        let config = Config::new(ConfigDbType::Sqlite)
+            .set_db_path(db.path().to_str().unwrap())
+            .set_table_name("custom_schema_history")
+            .set_abort_divergent(false)
+            .set_abort_missing(false);
+
+        let migrations = get_migrations();
+        migrate_from_config(&config, false, true, true, &migrations).unwrap();
+
+        let migration = Migration::from_filename(
+            "V2__add_year_field_to_cars",
+            &"ALTER TABLE cars ADD year INTEGER;",
+        )
+        .unwrap();
+        let report = migrate_from_config(&config, false, true, true, &[migration]).unwrap();
+        assert!(report.applied_migrations().is_empty());
+
+        let conn = Connection::open(db.path()).unwrap();
+        let current: u32 = conn
+            .query_row(
+                "SELECT MAX(version) FROM custom_schema_history",
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(5, current);
+    }
+
+    #[test]
+    fn migrate_from_config_fails_on_mssql_config() {
+        let config = Config::new(ConfigDbType::Mssql);
+
+        let err = migrate_from_config(&config, false, true, true, &get_migrations()).unwrap_err();
+        match err {
+            Error::ConfigError(message) => {
+                assert!(message.contains("migrate_from_config_async"));
+            }
+            _ => panic!("failed test"),
+        }
+    }
+
+    #[test]
+    fn runner_from_config_applies_migrations_on_config_locations() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let config = Config::new(ConfigDbType::Sqlite)
+            .set_locations(&["tests/sql_migrations"])
+            .set_table_name("custom_schema_history");
+
+        Runner::from_config(&config)
+            .unwrap()
+            .run(&mut conn)
+            .unwrap();
+
+        let current: u32 = conn
+            .query_row(
+                "SELECT MAX(version) FROM custom_schema_history",
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(4, current);
+    }
+
+    #[test]
+    fn runner_from_directory_applies_migrations_and_callbacks() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let report = Runner::from_directory("tests/sql_migrations_callbacks")
+            .unwrap()
+            .run(&mut conn)
+            .unwrap();
+        assert_eq!(2, report.applied_migrations().len());
+
+        let events: u32 = conn
+            .query_row("SELECT COUNT(*) FROM callbacks_log", params![], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(3, events);
+    }
+
     #[test]
     fn migrates_from_cli() {
         run_test(|| {
@@ -440,4 +988,120 @@ mod rusqlite {
                 .stdout(contains("applying migration: V4__add_year_to_motos_table"));
         })
     }
+
+    // in-memory databases are per connection, so the pool keeps a single one
+    #[cfg(feature = "r2d2")]
+    struct MemoryConnectionManager;
+
+    #[cfg(feature = "r2d2")]
+    impl refinery_core::r2d2::ManageConnection for MemoryConnectionManager {
+        type Connection = Connection;
+        type Error = refinery_core::rusqlite::Error;
+
+        fn connect(&self) -> Result<Connection, Self::Error> {
+            Connection::open_in_memory()
+        }
+
+        fn is_valid(&self, conn: &mut Connection) -> Result<(), Self::Error> {
+            conn.execute_batch("")
+        }
+
+        fn has_broken(&self, _conn: &mut Connection) -> bool {
+            false
+        }
+    }
+
+    #[cfg(feature = "r2d2")]
+    #[test]
+    fn embedded_applies_migrations_on_r2d2_pooled_connection() {
+        let pool = refinery_core::r2d2::Pool::builder()
+            .max_size(1)
+            .build(MemoryConnectionManager)
+            .unwrap();
+        let mut conn = pool.get().unwrap();
+
+        let report = embedded::migrations::runner().run(&mut conn).unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+
+        let current: u32 = conn
+            .query_row(
+                &format!("SELECT MAX(version) FROM {}", DEFAULT_MIGRATION_TABLE_NAME),
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(4, current);
+    }
+
+    #[test]
+    fn conforms_to_driver_checks() {
+        conformance::run(|| Connection::open_in_memory().unwrap());
+    }
+
+    #[test]
+    fn conforms_to_driver_checks_on_transaction() {
+        fn on_transaction(check: impl Fn(&mut Transaction<'_>)) {
+            let mut conn = Connection::open_in_memory().unwrap();
+            let mut transaction = conn.transaction().unwrap();
+            check(&mut transaction);
+        }
+
+        on_transaction(|transaction| conformance::commits_transactions(transaction));
+        on_transaction(|transaction| conformance::rolls_back_transactions(transaction));
+        on_transaction(|transaction| conformance::executes_outside_of_transactions(transaction));
+        on_transaction(|transaction| conformance::queries_rows_as_text(transaction));
+        on_transaction(|transaction| conformance::keeps_history(transaction));
+        on_transaction(|transaction| conformance::migrates(transaction));
+    }
+
+    #[test]
+    fn embedded_applies_migration_on_caller_transaction() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let mut transaction = conn.transaction().unwrap();
+        let report = embedded::migrations::runner()
+            .run(&mut transaction)
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        transaction.commit().unwrap();
+
+        let current: u32 = conn
+            .query_row(
+                &format!("SELECT MAX(version) FROM {}", DEFAULT_MIGRATION_TABLE_NAME),
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(4, current);
+    }
+
+    #[test]
+    fn embedded_migrations_are_undone_with_caller_transaction() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let mut transaction = conn.transaction().unwrap();
+        let result = broken::migrations::runner().run(&mut transaction);
+        assert!(result.is_err());
+
+        // the failed migration only rolled back its savepoint, the caller's transaction goes on
+        let current: u32 = transaction
+            .query_row(
+                &format!("SELECT MAX(version) FROM {}", DEFAULT_MIGRATION_TABLE_NAME),
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(2, current);
+        transaction.rollback().unwrap();
+
+        let table_name: Option<String> = conn
+            .query_row(
+                "SELECT name FROM sqlite_master WHERE type='table' AND name='refinery_schema_history'",
+                params![],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap();
+        assert!(table_name.is_none());
+    }
 }
@@ -0,0 +1 @@
+INSERT INTO callbacks_log (event) VALUES ('afterMigrate');
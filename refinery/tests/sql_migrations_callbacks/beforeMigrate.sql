@@ -0,0 +1,3 @@
+CREATE TABLE IF NOT EXISTS callbacks_log (
+    event varchar(255)
+);
@@ -0,0 +1,4 @@
+CREATE TABLE plugins (
+    id int,
+    name varchar(255)
+);
@@ -0,0 +1 @@
+ALTER TABLE plugins ADD enabled BOOLEAN;
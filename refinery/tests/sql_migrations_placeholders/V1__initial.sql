@@ -0,0 +1,4 @@
+CREATE TABLE ${table} (
+    id int,
+    name varchar(255)
+);
@@ -0,0 +1 @@
+INSERT INTO ${table} (id, name) VALUES (1, '${name}');
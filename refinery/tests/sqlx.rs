@@ -0,0 +1,183 @@
+mod mod_async_migrations;
+
+#[cfg(feature = "sqlx-sqlite")]
+mod sqlx {
+    use super::mod_async_migrations;
+    use refinery::{
+        conformance, AsyncExecutor, Error, Migration, MigrationError, MigrationFn, MigrationFuture,
+        Runner,
+    };
+    use refinery_core::sqlx::sqlite::SqlitePoolOptions;
+    use refinery_core::sqlx::{self, Connection, SqliteConnection};
+    use tokio1 as tokio;
+
+    mod embedded {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/sql_migrations");
+    }
+
+    async fn connection() -> SqliteConnection {
+        SqliteConnection::connect("sqlite::memory:").await.unwrap()
+    }
+
+    async fn count(conn: &mut SqliteConnection, query: &str) -> i64 {
+        let (count,): (i64,) = sqlx::query_as(query).fetch_one(conn).await.unwrap();
+        count
+    }
+
+    #[tokio::test]
+    async fn conforms_to_async_driver_checks() {
+        conformance::run_async(connection).await;
+    }
+
+    #[tokio::test]
+    async fn embedded_applies_migrations() {
+        let mut conn = connection().await;
+
+        let report = embedded::migrations::runner()
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(
+            4,
+            count(&mut conn, "SELECT COUNT(*) FROM refinery_schema_history").await
+        );
+
+        let report = embedded::migrations::runner()
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+        assert!(report.applied_migrations().is_empty());
+        assert_eq!(4, report.skipped_migrations().len());
+    }
+
+    #[tokio::test]
+    async fn embedded_applies_migrations_grouped_transaction() {
+        let mut conn = connection().await;
+
+        let report = embedded::migrations::runner()
+            .set_grouped(true)
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(
+            1,
+            count(
+                &mut conn,
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='motos'"
+            )
+            .await
+        );
+    }
+
+    #[tokio::test]
+    async fn embedded_applies_migrations_with_pool() {
+        // every connection to an in-memory database opens a new one, so the pool keeps a single connection
+        let mut pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        let report = embedded::migrations::runner()
+            .run_async(&mut pool)
+            .await
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM refinery_schema_history")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(4, count);
+    }
+
+    #[tokio::test]
+    async fn mod_runs_async_code_migration() {
+        let mut conn = connection().await;
+
+        mod_async_migrations::runner()
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(
+            1,
+            count(
+                &mut conn,
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='persons'"
+            )
+            .await
+        );
+    }
+
+    async fn failing_migration(executor: &mut dyn AsyncExecutor) -> Result<(), MigrationError> {
+        executor
+            .execute(&["CREATE TABLE cars (id int, name varchar(255))"])
+            .await?;
+        Err("could not migrate cars".into())
+    }
+
+    fn failing_migration_fn(executor: &mut dyn AsyncExecutor) -> MigrationFuture<'_> {
+        Box::pin(failing_migration(executor))
+    }
+
+    #[tokio::test]
+    async fn rolls_back_failed_code_migration() {
+        let mut conn = connection().await;
+        let migration = Migration::from_code(
+            "V1__add_cars_table",
+            MigrationFn::Async(failing_migration_fn),
+            "source",
+        )
+        .unwrap();
+
+        let err = Runner::new(&[migration])
+            .run_async(&mut conn)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("could not migrate cars"));
+        assert_eq!(
+            0,
+            count(
+                &mut conn,
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='cars'"
+            )
+            .await
+        );
+        assert_eq!(
+            0,
+            count(&mut conn, "SELECT COUNT(*) FROM refinery_schema_history").await
+        );
+    }
+
+    #[tokio::test]
+    async fn aborts_on_divergent_migration() {
+        let mut conn = connection().await;
+
+        embedded::migrations::runner()
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+
+        let migration = Migration::from_filename(
+            "V2__add_year_field_to_cars",
+            &"ALTER TABLE cars ADD year INTEGER;",
+        )
+        .unwrap();
+        let err = Runner::new(&[migration.clone()])
+            .set_abort_missing(false)
+            .run_async(&mut conn)
+            .await
+            .unwrap_err();
+
+        match err {
+            Error::DivergentVersion(applied, divergent) => {
+                assert_eq!(migration, divergent);
+                assert_eq!("add_cars_and_motos_table", applied.name);
+            }
+            _ => panic!("failed test"),
+        }
+    }
+}
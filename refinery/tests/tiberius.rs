@@ -0,0 +1,234 @@
+#[cfg(all(feature = "tokio", feature = "tiberius"))]
+mod tiberius {
+    use refinery::{
+        conformance, AsyncExecutor, AsyncTransactional, Error, Migration, MigrationError,
+        MigrationFn, MigrationFuture, Runner,
+    };
+    use refinery_core::tiberius::{Client, Config};
+    use refinery_core::tokio::{self, net::TcpStream};
+    use tokio_util::compat::{Compat, Tokio02AsyncWriteCompatExt};
+
+    mod embedded {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/sql_migrations");
+    }
+
+    // the tests need a SQL Server database, given as an ADO.NET connection string, and are skipped without one
+    async fn connect() -> Option<Client<Compat<TcpStream>>> {
+        let ado_string = std::env::var("MSSQL_ADO_STRING").ok()?;
+        let config = Config::from_ado_string(&ado_string).unwrap();
+        let tcp = TcpStream::connect(config.get_addr()).await.unwrap();
+        tcp.set_nodelay(true).unwrap();
+        let mut client = Client::connect(config, tcp.compat_write()).await.unwrap();
+
+        client
+            .simple_query(
+                "DROP VIEW IF EXISTS cars_view; \
+                 DROP TABLE IF EXISTS persons, cars, motos, refinery_schema_history, \
+                 refinery_conformance, refinery_conformance_history;",
+            )
+            .await
+            .unwrap()
+            .into_results()
+            .await
+            .unwrap();
+        Some(client)
+    }
+
+    async fn count(client: &mut Client<Compat<TcpStream>>, query: &str) -> i32 {
+        let rows = client
+            .simple_query(query)
+            .await
+            .unwrap()
+            .into_first_result()
+            .await
+            .unwrap();
+        rows[0].get(0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn conforms_to_async_driver_checks() {
+        if connect().await.is_none() {
+            return;
+        }
+        conformance::run_async(|| async { connect().await.unwrap() }).await;
+    }
+
+    #[tokio::test]
+    async fn rolls_back_transaction_dropped_without_being_ended() {
+        let mut client = match connect().await {
+            Some(client) => client,
+            None => return,
+        };
+        client
+            .simple_query("CREATE TABLE cars (id int)")
+            .await
+            .unwrap()
+            .into_results()
+            .await
+            .unwrap();
+
+        let mut transaction = client.begin().await.unwrap();
+        transaction
+            .execute(&["INSERT INTO cars (id) VALUES (1)"])
+            .await
+            .unwrap();
+        drop(transaction);
+
+        let transaction = client.begin().await.unwrap();
+        transaction.commit().await.unwrap();
+        assert_eq!(0, count(&mut client, "SELECT COUNT(*) FROM cars").await);
+    }
+
+    #[tokio::test]
+    async fn embedded_applies_migrations() {
+        let mut client = match connect().await {
+            Some(client) => client,
+            None => return,
+        };
+
+        let report = embedded::migrations::runner()
+            .run_async(&mut client)
+            .await
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(
+            4,
+            count(
+                &mut client,
+                "SELECT MAX(version) FROM refinery_schema_history"
+            )
+            .await
+        );
+
+        let report = embedded::migrations::runner()
+            .run_async(&mut client)
+            .await
+            .unwrap();
+        assert!(report.applied_migrations().is_empty());
+        assert_eq!(4, report.skipped_migrations().len());
+    }
+
+    #[tokio::test]
+    async fn embedded_applies_migrations_grouped_transaction() {
+        let mut client = match connect().await {
+            Some(client) => client,
+            None => return,
+        };
+
+        let report = embedded::migrations::runner()
+            .set_grouped(true)
+            .run_async(&mut client)
+            .await
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(
+            1,
+            count(
+                &mut client,
+                "SELECT COUNT(*) FROM sys.tables WHERE name = 'motos'"
+            )
+            .await
+        );
+    }
+
+    #[tokio::test]
+    async fn splits_migrations_on_go_lines() {
+        let mut client = match connect().await {
+            Some(client) => client,
+            None => return,
+        };
+        // CREATE VIEW has to be the first statement of its batch
+        let migration = Migration::from_filename(
+            "V1__add_cars_view",
+            "CREATE TABLE cars (id int, name varchar(255));\nGO\n\
+             CREATE VIEW cars_view AS SELECT name FROM cars;\nGO\n",
+        )
+        .unwrap();
+
+        Runner::new(&[migration])
+            .run_async(&mut client)
+            .await
+            .unwrap();
+        assert_eq!(
+            1,
+            count(
+                &mut client,
+                "SELECT COUNT(*) FROM sys.views WHERE name = 'cars_view'"
+            )
+            .await
+        );
+    }
+
+    async fn failing_migration(executor: &mut dyn AsyncExecutor) -> Result<(), MigrationError> {
+        executor
+            .execute(&["CREATE TABLE cars (id int, name varchar(255))"])
+            .await?;
+        Err("could not migrate cars".into())
+    }
+
+    fn failing_migration_fn(executor: &mut dyn AsyncExecutor) -> MigrationFuture<'_> {
+        Box::pin(failing_migration(executor))
+    }
+
+    #[tokio::test]
+    async fn rolls_back_failed_code_migration() {
+        let mut client = match connect().await {
+            Some(client) => client,
+            None => return,
+        };
+        let migration = Migration::from_code(
+            "V1__add_cars_table",
+            MigrationFn::Async(failing_migration_fn),
+            "source",
+        )
+        .unwrap();
+
+        let err = Runner::new(&[migration])
+            .run_async(&mut client)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("could not migrate cars"));
+        assert_eq!(
+            0,
+            count(
+                &mut client,
+                "SELECT COUNT(*) FROM sys.tables WHERE name = 'cars'"
+            )
+            .await
+        );
+        assert_eq!(0, count(&mut client, "SELECT @@TRANCOUNT").await);
+    }
+
+    #[tokio::test]
+    async fn aborts_on_divergent_migration() {
+        let mut client = match connect().await {
+            Some(client) => client,
+            None => return,
+        };
+
+        embedded::migrations::runner()
+            .run_async(&mut client)
+            .await
+            .unwrap();
+
+        let migration = Migration::from_filename(
+            "V2__add_year_field_to_cars",
+            &"ALTER TABLE cars ADD year INTEGER;",
+        )
+        .unwrap();
+        let err = Runner::new(&[migration.clone()])
+            .set_abort_missing(false)
+            .run_async(&mut client)
+            .await
+            .unwrap_err();
+
+        match err {
+            Error::DivergentVersion(applied, divergent) => {
+                assert_eq!(migration, divergent);
+                assert_eq!("add_cars_and_motos_table", applied.name);
+            }
+            _ => panic!("failed test"),
+        }
+    }
+}
@@ -1,14 +1,15 @@
 use barrel::backend::Pg as Sql;
+mod mod_async_migrations;
 mod mod_migrations;
 
 #[cfg(all(feature = "tokio", feature = "tokio-postgres"))]
 mod tokio_postgres {
-    use super::mod_migrations;
+    use super::{mod_async_migrations, mod_migrations};
     use chrono::{DateTime, Local};
     use futures::FutureExt;
     use refinery::{
         config::{migrate_from_config_async, Config, ConfigDbType},
-        AsyncMigrate, Error, Migration,
+        conformance, AsyncMigrate, CockroachConnection, Error, Migration, Runner,
     };
     use refinery_core::tokio_postgres::NoTls;
     use refinery_core::{tokio, tokio_postgres};
@@ -94,6 +95,26 @@ mod tokio_postgres {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn conforms_to_async_driver_checks() {
+        run_test(async {
+            conformance::run_async(|| async {
+                clean_database().await;
+                let (client, connection) =
+                    tokio_postgres::connect("postgres://postgres@localhost:5432/postgres", NoTls)
+                        .await
+                        .unwrap();
+
+                tokio::spawn(async move {
+                    connection.await.unwrap();
+                });
+                client
+            })
+            .await;
+        })
+        .await
+    }
+
     #[tokio::test]
     async fn embedded_creates_migration_table() {
         run_test(async {
@@ -125,6 +146,42 @@ mod tokio_postgres {
         }).await;
     }
 
+    #[tokio::test]
+    async fn mod_runs_async_code_migration() {
+        run_test(async {
+            let (mut client, connection) =
+                tokio_postgres::connect("postgres://postgres@localhost:5432/postgres", NoTls)
+                    .await
+                    .unwrap();
+
+            tokio::spawn(async move {
+                connection.await.unwrap();
+            });
+
+            mod_async_migrations::runner()
+                .run_async(&mut client)
+                .await
+                .unwrap();
+
+            let rows = client
+                .query(
+                    "SELECT table_name FROM information_schema.tables WHERE table_name='persons'",
+                    &[],
+                )
+                .await
+                .unwrap();
+            assert_eq!(1, rows.len());
+
+            let rows = client
+                .query("SELECT version FROM refinery_schema_history", &[])
+                .await
+                .unwrap();
+            let version: i32 = rows[0].get(0);
+            assert_eq!(1, version);
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn embedded_creates_migration_table_grouped_migrations() {
         run_test(async {
@@ -644,6 +701,283 @@ mod tokio_postgres {
         .await;
     }
 
+    #[tokio::test]
+    async fn embedded_applies_migration_on_caller_transaction() {
+        run_test(async {
+            let (mut client, connection) =
+                tokio_postgres::connect("postgres://postgres@localhost:5432/postgres", NoTls)
+                    .await
+                    .unwrap();
+
+            tokio::spawn(async move {
+                connection.await.unwrap();
+            });
+
+            let mut transaction = client.transaction().await.unwrap();
+            let report = embedded::migrations::runner()
+                .run_async(&mut transaction)
+                .await
+                .unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+            transaction.commit().await.unwrap();
+
+            let rows = client
+                .query("SELECT MAX(version) FROM refinery_schema_history", &[])
+                .await
+                .unwrap();
+            let current: i32 = rows[0].get(0);
+            assert_eq!(4, current);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn embedded_migrations_are_undone_with_caller_transaction() {
+        run_test(async {
+            let (mut client, connection) =
+                tokio_postgres::connect("postgres://postgres@localhost:5432/postgres", NoTls)
+                    .await
+                    .unwrap();
+
+            tokio::spawn(async move {
+                connection.await.unwrap();
+            });
+
+            let mut transaction = client.transaction().await.unwrap();
+            let result = broken::migrations::runner()
+                .run_async(&mut transaction)
+                .await;
+            assert!(result.is_err());
+
+            // the failed migration only rolled back its savepoint, the caller's transaction goes on
+            let rows = transaction
+                .query("SELECT MAX(version) FROM refinery_schema_history", &[])
+                .await
+                .unwrap();
+            let current: i32 = rows[0].get(0);
+            assert_eq!(2, current);
+            transaction.rollback().await.unwrap();
+
+            let rows = client
+                .query(
+                    "SELECT COUNT(*) FROM information_schema.tables WHERE table_name = 'refinery_schema_history'",
+                    &[],
+                )
+                .await
+                .unwrap();
+            let count: i64 = rows[0].get(0);
+            assert_eq!(0, count);
+        })
+        .await
+    }
+
+    // the local postgres stands in for CockroachDB, which speaks its protocol,
+    // wrapped in CockroachConnection the client is migrated the way CockroachDB is
+    async fn cockroach_client() -> CockroachConnection<tokio_postgres::Client> {
+        let (client, connection) =
+            tokio_postgres::connect("postgres://postgres@localhost:5432/postgres", NoTls)
+                .await
+                .unwrap();
+
+        tokio::spawn(async move {
+            connection.await.unwrap();
+        });
+
+        CockroachConnection::new(client)
+    }
+
+    #[tokio::test]
+    async fn cockroach_fails_on_grouped_migrations() {
+        run_test(async {
+            let mut client = cockroach_client().await;
+
+            let err = embedded::migrations::runner()
+                .set_grouped(true)
+                .run_async(&mut client)
+                .await
+                .unwrap_err();
+            match err {
+                Error::GroupedMigrationsNotSupported => {}
+                err => panic!("unexpected error {}", err),
+            }
+
+            // nothing was migrated, not even the migrations table was created
+            let rows = client
+                .into_inner()
+                .query(
+                    "SELECT COUNT(*) FROM information_schema.tables WHERE table_name = 'refinery_schema_history'",
+                    &[],
+                )
+                .await
+                .unwrap();
+            let count: i64 = rows[0].get(0);
+            assert_eq!(0, count);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn cockroach_upgrades_migrations_table_without_namespace_column() {
+        run_test(async {
+            let mut client = cockroach_client().await;
+            let mut migrations = embedded::migrations::migrations();
+            migrations.sort();
+            // a migrations table as created before namespaces were kept on it, with the first migration applied
+            client
+                .batch_execute(
+                    "CREATE TABLE refinery_schema_history( \
+                     version INT4 PRIMARY KEY,\
+                     name VARCHAR(255),\
+                     applied_on VARCHAR(255),
+                     checksum VARCHAR(255));",
+                )
+                .await
+                .unwrap();
+            client.batch_execute(&migrations[0].sql).await.unwrap();
+            client
+                .execute(
+                    "INSERT INTO refinery_schema_history (version, name, applied_on, checksum) VALUES ($1, $2, $3, $4)",
+                    &[
+                        &(migrations[0].version as i32),
+                        &migrations[0].name,
+                        &Local::now().to_rfc3339(),
+                        &migrations[0].checksum().to_string(),
+                    ],
+                )
+                .await
+                .unwrap();
+
+            let report = Runner::new(&migrations).run_async(&mut client).await.unwrap();
+            assert_eq!(3, report.applied_migrations().len());
+            assert_eq!(1, report.skipped_migrations().len());
+
+            let rows = client
+                .into_inner()
+                .query(
+                    "SELECT COUNT(*) FROM refinery_schema_history WHERE namespace = ''",
+                    &[],
+                )
+                .await
+                .unwrap();
+            let count: i64 = rows[0].get(0);
+            assert_eq!(4, count);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn cockroach_holds_a_lock_row_while_migrating() {
+        run_test(async {
+            let mut client = cockroach_client().await;
+            let migration = Migration::from_filename(
+                "V1__copy_lock_rows",
+                "CREATE TABLE lock_rows AS SELECT id FROM refinery_schema_history_lock;",
+            )
+            .unwrap();
+
+            Runner::new(&[migration])
+                .run_async(&mut client)
+                .await
+                .unwrap();
+
+            let client = client.into_inner();
+            let rows = client.query("SELECT id FROM lock_rows", &[]).await.unwrap();
+            assert_eq!(1, rows.len());
+            let rows = client
+                .query("SELECT id FROM refinery_schema_history_lock", &[])
+                .await
+                .unwrap();
+            assert!(rows.is_empty());
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn cockroach_fails_while_another_runner_holds_the_lock() {
+        run_test(async {
+            let mut client = cockroach_client().await;
+            client
+                .batch_execute(
+                    "CREATE TABLE refinery_schema_history_lock (id INT4 PRIMARY KEY, owner VARCHAR(255) NOT NULL, locked_at TIMESTAMPTZ NOT NULL); \
+                     INSERT INTO refinery_schema_history_lock (id, owner, locked_at) VALUES (1, 'other', now());",
+                )
+                .await
+                .unwrap();
+
+            let err = embedded::migrations::runner()
+                .run_async(&mut client)
+                .await
+                .unwrap_err();
+            match err {
+                Error::MigrationsTableLocked(table) => {
+                    assert_eq!("refinery_schema_history", table)
+                }
+                err => panic!("unexpected error {}", err),
+            }
+
+            // the row of the runner holding the lock is left alone
+            let rows = client
+                .into_inner()
+                .query("SELECT owner FROM refinery_schema_history_lock", &[])
+                .await
+                .unwrap();
+            assert_eq!(1, rows.len());
+            let owner: String = rows[0].get(0);
+            assert_eq!("other", owner);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn cockroach_takes_over_a_lock_left_behind_an_hour_ago() {
+        run_test(async {
+            let mut client = cockroach_client().await;
+            client
+                .batch_execute(
+                    "CREATE TABLE refinery_schema_history_lock (id INT4 PRIMARY KEY, owner VARCHAR(255) NOT NULL, locked_at TIMESTAMPTZ NOT NULL); \
+                     INSERT INTO refinery_schema_history_lock (id, owner, locked_at) VALUES (1, 'stopped', now() - INTERVAL '2 hours');",
+                )
+                .await
+                .unwrap();
+
+            embedded::migrations::runner()
+                .run_async(&mut client)
+                .await
+                .unwrap();
+
+            let client = client.into_inner();
+            let rows = client
+                .query("SELECT MAX(version) FROM refinery_schema_history", &[])
+                .await
+                .unwrap();
+            let current: i32 = rows[0].get(0);
+            assert_eq!(4, current);
+            let rows = client
+                .query("SELECT id FROM refinery_schema_history_lock", &[])
+                .await
+                .unwrap();
+            assert!(rows.is_empty());
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn migrates_cockroach_from_config() {
+        run_test(async {
+            let config = Config::new(ConfigDbType::Cockroach)
+                .set_db_name("postgres")
+                .set_db_user("postgres")
+                .set_db_host("localhost")
+                .set_db_port("5432");
+
+            let migrations = get_migrations();
+            migrate_from_config_async(&config, false, true, true, &migrations)
+                .await
+                .unwrap();
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn migrates_from_config() {
         run_test(async {
@@ -660,4 +994,62 @@ mod tokio_postgres {
         })
         .await;
     }
+
+    #[cfg(feature = "bb8")]
+    #[tokio::test]
+    async fn embedded_applies_migration_on_bb8_pooled_connection() {
+        run_test(async {
+            let manager = bb8_postgres::PostgresConnectionManager::new_from_stringlike(
+                "postgres://postgres@localhost:5432/postgres",
+                NoTls,
+            )
+            .unwrap();
+            let pool = refinery_core::bb8::Pool::builder()
+                .build(manager)
+                .await
+                .unwrap();
+            let mut client = pool.get().await.unwrap();
+
+            let report = embedded::migrations::runner()
+                .run_async(&mut client)
+                .await
+                .unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+
+            let rows = client
+                .query("SELECT MAX(version) FROM refinery_schema_history", &[])
+                .await
+                .unwrap();
+            let current: i32 = rows[0].get(0);
+            assert_eq!(4, current);
+        })
+        .await;
+    }
+
+    #[cfg(feature = "deadpool-postgres")]
+    #[tokio::test]
+    async fn embedded_applies_migration_on_deadpool_client() {
+        run_test(async {
+            let config = "postgres://postgres@localhost:5432/postgres"
+                .parse::<tokio_postgres::Config>()
+                .unwrap();
+            let manager = refinery_core::deadpool_postgres::Manager::new(config, NoTls);
+            let pool = refinery_core::deadpool_postgres::Pool::new(manager, 1);
+            let mut client = pool.get().await.unwrap();
+
+            let report = embedded::migrations::runner()
+                .run_async(&mut client)
+                .await
+                .unwrap();
+            assert_eq!(4, report.applied_migrations().len());
+
+            let rows = client
+                .query("SELECT MAX(version) FROM refinery_schema_history", &[])
+                .await
+                .unwrap();
+            let current: i32 = rows[0].get(0);
+            assert_eq!(4, current);
+        })
+        .await;
+    }
 }
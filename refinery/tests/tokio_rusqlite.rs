@@ -0,0 +1,189 @@
+mod mod_async_migrations;
+
+#[cfg(all(feature = "tokio", feature = "rusqlite"))]
+mod tokio_rusqlite {
+    use super::mod_async_migrations;
+    use refinery::{
+        config::{migrate_from_config_async, Config, ConfigDbType},
+        conformance, AsyncExecutor, AsyncSqliteConnection, Error, Migration, MigrationError,
+        MigrationFn, MigrationFuture, Runner,
+    };
+    use refinery_core::rusqlite::{params, Connection};
+    use refinery_core::tokio;
+
+    mod embedded {
+        use refinery::embed_migrations;
+        embed_migrations!("./tests/sql_migrations");
+    }
+
+    fn connection() -> AsyncSqliteConnection {
+        AsyncSqliteConnection::new(Connection::open_in_memory().unwrap())
+    }
+
+    async fn count(conn: &AsyncSqliteConnection, query: &'static str) -> u32 {
+        conn.call(move |conn| conn.query_row(query, params![], |row| row.get(0)))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn conforms_to_async_driver_checks() {
+        conformance::run_async(|| async { connection() }).await;
+    }
+
+    #[tokio::test]
+    async fn embedded_applies_migrations() {
+        let mut conn = connection();
+
+        let report = embedded::migrations::runner()
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(
+            4,
+            count(&conn, "SELECT COUNT(*) FROM refinery_schema_history").await
+        );
+
+        let report = embedded::migrations::runner()
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+        assert!(report.applied_migrations().is_empty());
+        assert_eq!(4, report.skipped_migrations().len());
+    }
+
+    #[tokio::test]
+    async fn embedded_applies_migrations_grouped_transaction() {
+        let mut conn = connection();
+
+        let report = embedded::migrations::runner()
+            .set_grouped(true)
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+        assert_eq!(
+            1,
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='motos'"
+            )
+            .await
+        );
+    }
+
+    #[tokio::test]
+    async fn mod_runs_async_code_migration() {
+        let mut conn = connection();
+
+        mod_async_migrations::runner()
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(
+            1,
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='persons'"
+            )
+            .await
+        );
+    }
+
+    async fn failing_migration(executor: &mut dyn AsyncExecutor) -> Result<(), MigrationError> {
+        executor
+            .execute(&["CREATE TABLE cars (id int, name varchar(255))"])
+            .await?;
+        Err("could not migrate cars".into())
+    }
+
+    fn failing_migration_fn(executor: &mut dyn AsyncExecutor) -> MigrationFuture<'_> {
+        Box::pin(failing_migration(executor))
+    }
+
+    #[tokio::test]
+    async fn rolls_back_failed_code_migration() {
+        let mut conn = connection();
+        let migration = Migration::from_code(
+            "V1__add_cars_table",
+            MigrationFn::Async(failing_migration_fn),
+            "source",
+        )
+        .unwrap();
+
+        let err = Runner::new(&[migration])
+            .run_async(&mut conn)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("could not migrate cars"));
+        assert_eq!(
+            0,
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='cars'"
+            )
+            .await
+        );
+        assert_eq!(
+            0,
+            count(&conn, "SELECT COUNT(*) FROM refinery_schema_history").await
+        );
+    }
+
+    #[tokio::test]
+    async fn aborts_on_divergent_migration() {
+        let mut conn = connection();
+
+        embedded::migrations::runner()
+            .run_async(&mut conn)
+            .await
+            .unwrap();
+
+        let migration = Migration::from_filename(
+            "V2__add_year_field_to_cars",
+            &"ALTER TABLE cars ADD year INTEGER;",
+        )
+        .unwrap();
+        let err = Runner::new(&[migration.clone()])
+            .set_abort_missing(false)
+            .run_async(&mut conn)
+            .await
+            .unwrap_err();
+
+        match err {
+            Error::DivergentVersion(applied, divergent) => {
+                assert_eq!(migration, divergent);
+                assert_eq!("add_cars_and_motos_table", applied.name);
+            }
+            _ => panic!("failed test"),
+        }
+    }
+
+    #[tokio::test]
+    async fn migrates_from_config() {
+        let db = tempfile::NamedTempFile::new_in(".").unwrap();
+        let config = Config::new(ConfigDbType::Sqlite).set_db_path(db.path().to_str().unwrap());
+
+        let report = migrate_from_config_async(
+            &config,
+            false,
+            true,
+            true,
+            &embedded::migrations::migrations(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(4, report.applied_migrations().len());
+
+        let conn = Connection::open(db.path()).unwrap();
+        let current: u32 = conn
+            .query_row(
+                "SELECT MAX(version) FROM refinery_schema_history",
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(4, current);
+    }
+}
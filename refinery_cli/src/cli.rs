@@ -5,9 +5,76 @@ use clap::{App, AppSettings, Arg, SubCommand};
 
 /// Initialise the CLI parser for our app
 pub fn create_cli() -> App<'static, 'static> {
+    /* --output of the commands with machine readable outcome */
+    let output_format = Arg::with_name("output")
+        .long("output")
+        .help("print either log lines or a json document with the outcome of the command")
+        .possible_values(&["text", "json"])
+        .default_value("text");
+
     /* The setup cmd handles initialisation */
     let setup = SubCommand::with_name("setup")
-        .about("Run the refinery setup hooks to generate the config file");
+        .about("Run the refinery setup hooks to generate the config file")
+        .arg(
+            Arg::with_name("db-type")
+                .long("db-type")
+                .help("database type")
+                .takes_value(true)
+                .possible_values(&["mysql", "postgres", "sqlite", "mssql", "cockroach"])
+                .case_insensitive(true),
+        )
+        .arg(
+            Arg::with_name("host")
+                .long("host")
+                .help("database host")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("port")
+                .long("port")
+                .help("database port")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("user")
+                .long("user")
+                .help("database username")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("password")
+                .long("password")
+                .help("database password, prefer giving it through the environment so it isn't kept on the shell history")
+                .env("REFINERY_DB_PASSWORD")
+                .hide_env_values(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("name")
+                .long("name")
+                .help("database name")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("path")
+                .long("path")
+                .help("database path, for Sqlite databases")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .help("config file location")
+                .default_value("./refinery.toml"),
+        )
+        .arg(
+            Arg::with_name("force")
+                .short("f")
+                .long("force")
+                .help("overwrite the config file if it already exists, using its values for the ones not given")
+                .takes_value(false),
+        );
 
     let migrate = SubCommand::with_name("migrate")
         .about("Refinery's main migrate operation")
@@ -35,12 +102,23 @@ pub fn create_cli() -> App<'static, 'static> {
                 .help("if set, migrates even if missing migrations are found")
                 .takes_value(false),
         )
-        // .subcommand(
-        //     SubCommand::with_name("mod")
-        //         .display_order(1)
-        //         .about("Run migrations in rust modules")
-        //         .arg_from_usage("<location> 'migrations module path i.e. crate::migrations'"),
-        // )
+        .arg(output_format.clone())
+        .subcommand(
+            SubCommand::with_name("mod")
+                .display_order(1)
+                .about("Run migrations in Rust modules")
+                .arg(
+                    Arg::with_name("location")
+                        .help("migration modules dir path i.e. ./src/migrations")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("manifest-path")
+                        .long("manifest-path")
+                        .help("Cargo.toml of the crate the migration modules belong to")
+                        .default_value("./Cargo.toml"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("files")
                 .display_order(2)
@@ -48,17 +126,55 @@ pub fn create_cli() -> App<'static, 'static> {
                 .arg(
                     Arg::with_name("path")
                         .short("p")
-                        .help("migrations dir path")
+                        .help("migrations dir path, can be given several times to merge the migrations on each, if not given the locations on the config file are used")
                         .default_value("./migrations")
+                        .multiple(true)
+                        .number_of_values(1)
                         .empty_values(false),
                 ),
         )
         .setting(AppSettings::SubcommandRequired);
 
+    let new = SubCommand::with_name("new")
+        .about("Create the file of the next migration")
+        .arg(
+            Arg::with_name("name")
+                .help("migration name, alphanumeric characters plus \"_\"")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .help("give a config file location, used to find the migrations dir if it is not given")
+                .default_value("./refinery.toml"),
+        )
+        .arg(
+            Arg::with_name("path")
+                .short("p")
+                .help("migrations dir path")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("type")
+                .short("t")
+                .help("create either a .sql file or a Rust module")
+                .possible_values(&["sql", "rs"])
+                .default_value("sql"),
+        )
+        .arg(
+            Arg::with_name("versioning")
+                .long("versioning")
+                .help("use the last version plus one, or the current unix timestamp to avoid collisions between branches")
+                .possible_values(&["sequential", "timestamp"])
+                .default_value("sequential"),
+        )
+        .arg(output_format);
+
     /* Create an app and return it */
     App::new(APP_NAME)
         .version(VERSION)
         .subcommand(setup)
         .subcommand(migrate)
+        .subcommand(new)
         .setting(AppSettings::SubcommandRequiredElseHelp)
 }
@@ -2,17 +2,22 @@
 
 mod cli;
 mod migrate;
+mod mod_runner;
+mod new;
+mod output;
 mod setup;
 
 use anyhow::Error;
+use clap::ArgMatches;
 use env_logger::{Builder, Target};
 use log::LevelFilter;
+use output::OutputFormat;
 use std::io::Write;
 
 const APP_NAME: &str = "refinery";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-fn main() -> Result<(), Error> {
+fn main() {
     human_panic::setup_panic!(Metadata {
         name: APP_NAME.into(),
         version: VERSION.into(),
@@ -20,18 +25,32 @@ fn main() -> Result<(), Error> {
         homepage: "https://github.com/rust-db/refinery/".into(),
     });
 
+    let matches = cli::create_cli().get_matches();
+    let format = OutputFormat::from_args(&matches);
+
+    // with json output stdout is kept for the json document
+    let target = match format {
+        OutputFormat::Text => Target::Stdout,
+        OutputFormat::Json => Target::Stderr,
+    };
     let mut builder = Builder::new();
     builder
         .format(|buf, record| writeln!(buf, "{}", record.args()))
         .filter(Some("refinery_core::traits"), LevelFilter::Info)
-        .target(Target::Stdout)
+        .target(target)
         .init();
 
-    let matches = cli::create_cli().get_matches();
+    if let Err(err) = run(&matches, format) {
+        let code = output::print_error(format, &err);
+        std::process::exit(code);
+    }
+}
 
+fn run(matches: &ArgMatches, format: OutputFormat) -> Result<(), Error> {
     match matches.subcommand() {
-        ("migrate", Some(matches)) => migrate::handle_migration_command(matches)?,
+        ("migrate", Some(matches)) => migrate::handle_migration_command(matches, format)?,
         ("setup", Some(matches)) => setup::handle_setup(matches)?,
+        ("new", Some(matches)) => new::handle_new(matches, format)?,
         _ => unreachable!("Can't touch this..."),
     };
     Ok(())
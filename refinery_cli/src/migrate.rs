@@ -1,56 +1,71 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use clap::ArgMatches;
 use refinery_core::{
-    config::{migrate_from_config, Config},
-    find_migration_files, Migration, MigrationType,
+    config::{migrate_from_config, migrate_from_config_with_runner, Config},
+    Report, Runner,
 };
 
-pub fn handle_migration_command(args: &ArgMatches) -> Result<()> {
+use crate::mod_runner::load_mod_migrations;
+use crate::output::{print_report, OutputFormat};
+
+pub fn handle_migration_command(args: &ArgMatches, format: OutputFormat) -> Result<()> {
     //safe to call unwrap as we specified default values
     let config_location = args.value_of("config").unwrap();
-    let grouped = args.is_present("grouped");
-    let divergent = !args.is_present("divergent");
-    let missing = !args.is_present("missing");
-
-    match args.subcommand() {
-        ("files", Some(args)) => {
-            run_files_migrations(config_location, grouped, divergent, missing, args)?
-        }
+    let config =
+        Config::from_file_location(config_location).context("could not parse the config file")?;
+
+    // flags override the settings on the config file
+    let grouped = args.is_present("grouped") || config.get_grouped();
+    let divergent = !args.is_present("divergent") && config.get_abort_divergent();
+    let missing = !args.is_present("missing") && config.get_abort_missing();
+
+    let report = match args.subcommand() {
+        ("files", Some(args)) => run_files_migrations(&config, grouped, divergent, missing, args)?,
+        ("mod", Some(args)) => run_mod_migrations(&config, grouped, divergent, missing, args)?,
         _ => unreachable!("Can't touch this..."),
-    }
+    };
+    print_report(format, &report);
     Ok(())
 }
 
 fn run_files_migrations(
-    config_location: &str,
+    config: &Config,
     grouped: bool,
     divergent: bool,
     missing: bool,
     arg: &ArgMatches,
-) -> Result<()> {
-    //safe to call unwrap as we specified default value
-    let path = arg.value_of("path").unwrap();
-    let path = Path::new(path);
-    let migration_files_path = find_migration_files(path, MigrationType::Sql)?;
-    let mut migrations = Vec::new();
-    for path in migration_files_path {
-        let sql = std::fs::read_to_string(path.as_path())
-            .with_context(|| format!("could not read migration file name {}", path.display()))?;
-
-        //safe to call unwrap as find_migration_filenames returns canonical paths
-        let filename = path
-            .file_stem()
-            .and_then(|file| file.to_os_string().into_string().ok())
-            .unwrap();
-
-        let migration = Migration::from_filename(&filename, &sql)
-            .with_context(|| format!("could not read migration file name {}", path.display()))?;
-        migrations.push(migration);
-    }
-    let config =
-        Config::from_file_location(config_location).context("could not parse the config file")?;
-    migrate_from_config(&config, grouped, divergent, missing, &migrations)?;
-    Ok(())
+) -> Result<Report> {
+    // use the migrations dir paths if given, otherwise the locations on the config file
+    let runner = if arg.occurrences_of("path") > 0 {
+        //safe to call unwrap as we checked the argument is present
+        let locations: Vec<PathBuf> = arg.values_of("path").unwrap().map(PathBuf::from).collect();
+        Runner::from_config(&config.clone().set_locations(&locations))?
+    } else {
+        Runner::from_config(config)?
+    };
+
+    let runner = runner
+        .set_grouped(grouped)
+        .set_abort_divergent(divergent)
+        .set_abort_missing(missing);
+    let report = migrate_from_config_with_runner(config, &runner)?;
+    Ok(report)
+}
+
+fn run_mod_migrations(
+    config: &Config,
+    grouped: bool,
+    divergent: bool,
+    missing: bool,
+    arg: &ArgMatches,
+) -> Result<Report> {
+    //safe to call unwrap as location is required and manifest-path has a default value
+    let location = Path::new(arg.value_of("location").unwrap());
+    let manifest_path = Path::new(arg.value_of("manifest-path").unwrap());
+
+    let migrations = load_mod_migrations(location, manifest_path)?;
+    let report = migrate_from_config(config, grouped, divergent, missing, &migrations)?;
+    Ok(report)
 }
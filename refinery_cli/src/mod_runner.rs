@@ -0,0 +1,184 @@
+//! Submodule for running Rust module migrations
+//!
+//! Rust module migrations can't be evaluated by the cli itself,
+//! so we generate a small binary crate on the `target` dir of the crate
+//! they belong to. It includes every migration module with the same
+//! dependencies as that crate, and prints what each module's `migration()`
+//! function returns, which is then read back to be applied as any other migration.
+//!
+//! As the migration modules are built outside of their crate, they can only
+//! refer to the crate's dependencies, and not to other modules of the crate.
+//! Modules with a `migrate` function running on the migration's transaction are rejected,
+//! they can only be run through `include_migration_mods!`.
+
+use anyhow::{anyhow, Context, Result};
+use refinery_core::{find_migration_files, Migration, MigrationType};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use toml::value::{Table, Value};
+
+const RUNNER_CRATE_NAME: &str = "refinery_mod_migrations";
+
+/// Builds and runs the binary evaluating the Rust module migrations found on `location`,
+/// `manifest_path` being the Cargo.toml of the crate they belong to
+pub fn load_mod_migrations(location: &Path, manifest_path: &Path) -> Result<Vec<Migration>> {
+    let manifest_path = manifest_path
+        .canonicalize()
+        .with_context(|| format!("could not find manifest {}", manifest_path.display()))?;
+    //safe to call unwrap as a canonical file path always has a parent
+    let crate_dir = manifest_path.parent().unwrap();
+
+    let mut modules: Vec<(String, PathBuf)> = Vec::new();
+    for path in find_migration_files(location, MigrationType::Mod)? {
+        //safe to call unwrap as find_migration_filenames returns canonical paths
+        let name = path
+            .file_stem()
+            .and_then(|file| file.to_os_string().into_string().ok())
+            .unwrap();
+        // the module names are the file stems, so two modules with the same file name
+        // on different subdirectories would be declared twice on the runner crate
+        if let Some((_, other)) = modules.iter().find(|(other, _)| *other == name) {
+            return Err(anyhow!(
+                "migration module {} found on both {} and {}",
+                name,
+                other.display(),
+                path.display()
+            ));
+        }
+        if defines_migrate_fn(&path)? {
+            return Err(anyhow!(
+                "migration module {} runs on the migration's transaction with a `migrate` function, \
+                 which can only be run with include_migration_mods!",
+                path.display()
+            ));
+        }
+        modules.push((name, path));
+    }
+
+    let runner_dir = crate_dir.join("target").join(RUNNER_CRATE_NAME);
+    fs::create_dir_all(runner_dir.join("src"))
+        .with_context(|| format!("could not create directory {}", runner_dir.display()))?;
+    fs::write(
+        runner_dir.join("Cargo.toml"),
+        runner_manifest(&manifest_path, crate_dir)?,
+    )
+    .context("could not write migrations runner manifest")?;
+    fs::write(
+        runner_dir.join("src").join("main.rs"),
+        runner_main(&modules),
+    )
+    .context("could not write migrations runner source")?;
+
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+    let output = Command::new(cargo)
+        .arg("run")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(runner_dir.join("Cargo.toml"))
+        .stderr(Stdio::inherit())
+        .output()
+        .context("could not run cargo")?;
+
+    if !output.status.success() {
+        return Err(anyhow!("could not build and run the migration modules"));
+    }
+
+    parse_runner_output(&String::from_utf8(output.stdout)?)
+}
+
+// modules with a `migrate` function are looked up the same way include_migration_mods! does,
+// they need a connection of the application to run on and so can't be evaluated by the runner crate
+fn defines_migrate_fn(path: &Path) -> Result<bool> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("could not read migration module {}", path.display()))?;
+    let file = syn::parse_file(&source)
+        .with_context(|| format!("could not parse migration module {}", path.display()))?;
+    Ok(file.items.iter().any(|item| match item {
+        syn::Item::Fn(function) => function.sig.ident == "migrate",
+        _ => false,
+    }))
+}
+
+// the runner crate has the same dependencies as the crate the migrations belong to,
+// with relative path dependencies made absolute as it lives in another directory
+fn runner_manifest(manifest_path: &Path, crate_dir: &Path) -> Result<String> {
+    let manifest: Value = toml::from_str(&fs::read_to_string(manifest_path)?)
+        .with_context(|| format!("could not parse manifest {}", manifest_path.display()))?;
+
+    let mut dependencies = manifest
+        .get("dependencies")
+        .and_then(Value::as_table)
+        .cloned()
+        .unwrap_or_default();
+
+    for (_, dependency) in dependencies.iter_mut() {
+        if let Some(path) = dependency.get_mut("path") {
+            if let Some(relative) = path.as_str().map(PathBuf::from) {
+                if relative.is_relative() {
+                    *path = Value::String(crate_dir.join(relative).display().to_string());
+                }
+            }
+        }
+    }
+
+    let mut package = Table::new();
+    package.insert("name".into(), Value::String(RUNNER_CRATE_NAME.into()));
+    package.insert("version".into(), Value::String("0.0.0".into()));
+    package.insert("edition".into(), Value::String("2018".into()));
+    package.insert("publish".into(), Value::Boolean(false));
+
+    let mut runner_manifest = Table::new();
+    runner_manifest.insert("package".into(), Value::Table(package));
+    // an empty workspace so that the runner crate is not taken as part of the crate's workspace
+    runner_manifest.insert("workspace".into(), Value::Table(Table::new()));
+    runner_manifest.insert("dependencies".into(), Value::Table(dependencies));
+
+    Ok(toml::to_string(&Value::Table(runner_manifest))?)
+}
+
+// prints for each migration its name, the length of the returned sql and the sql itself,
+// so that sql with new lines can be read back
+fn runner_main(modules: &[(String, PathBuf)]) -> String {
+    let mut main = String::new();
+    for (name, path) in modules {
+        main.push_str(&format!(
+            "#[allow(non_snake_case)]\n#[path = {:?}]\nmod {};\n",
+            path.display().to_string(),
+            name
+        ));
+    }
+    main.push_str("\nfn main() {\n");
+    for (name, _) in modules {
+        main.push_str(&format!(
+            "    let sql: String = {}::migration();\n    print!(\"{}\\n{{}}\\n{{}}\", sql.len(), sql);\n",
+            name, name
+        ));
+    }
+    main.push_str("}\n");
+    main
+}
+
+fn parse_runner_output(mut output: &str) -> Result<Vec<Migration>> {
+    let mut migrations = Vec::new();
+    while !output.is_empty() {
+        let mut header = output.splitn(3, '\n');
+        let (name, len, rest) = match (header.next(), header.next(), header.next()) {
+            (Some(name), Some(len), Some(rest)) => (name, len, rest),
+            _ => return Err(anyhow!("invalid output from the migrations runner")),
+        };
+        let len: usize = len
+            .parse()
+            .context("invalid output from the migrations runner")?;
+        let sql = rest
+            .get(..len)
+            .ok_or_else(|| anyhow!("invalid output from the migrations runner"))?;
+
+        let migration = Migration::from_filename(name, sql)
+            .with_context(|| format!("could not read migration module {}", name))?;
+        migrations.push(migration);
+        output = &rest[len..];
+    }
+    Ok(migrations)
+}
@@ -0,0 +1,106 @@
+//! Submodule for handling new commands
+//!
+//! `new` creates the file of the next migration on the migrations
+//! directory, named `V{version}__{name}` where version is either the
+//! last version found on the directory plus one, or the current unix
+//! timestamp to avoid collisions between migrations created on different branches.
+
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+use refinery_core::{config::Config, find_migration_files, Migration, MigrationType};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::output::{print_created, OutputFormat};
+
+const SQL_TEMPLATE: &str = "-- write the statements of this migration here\n";
+
+const MOD_TEMPLATE: &str = "\
+pub fn migration() -> String {
+    // return the statements of this migration, either by hand or generated with a crate like barrel
+    String::new()
+}
+";
+
+pub fn handle_new(args: &ArgMatches, format: OutputFormat) -> Result<()> {
+    //safe to call unwrap as name is required
+    let name = args.value_of("name").unwrap();
+    if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(anyhow!(
+            "invalid migration name {}, only alphanumeric characters plus \"_\" are supported",
+            name
+        ));
+    }
+
+    let location = get_location(args)?;
+    fs::create_dir_all(&location).with_context(|| {
+        format!(
+            "could not create migrations directory {}",
+            location.display()
+        )
+    })?;
+
+    let last_version = get_last_version(&location)?;
+    //safe to call unwrap as we specified default value
+    let version = match args.value_of("versioning").unwrap() {
+        "timestamp" => {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .context("system clock is set before the unix epoch")?
+                .as_secs() as usize;
+            timestamp.max(last_version + 1)
+        }
+        _ => last_version + 1,
+    };
+
+    let (extension, template) = match args.value_of("type").unwrap() {
+        "rs" => ("rs", MOD_TEMPLATE),
+        _ => ("sql", SQL_TEMPLATE),
+    };
+
+    let path = location.join(format!("V{}__{}.{}", version, name, extension));
+    fs::write(&path, template)
+        .with_context(|| format!("could not write migration file {}", path.display()))?;
+    print_created(format, &path);
+    Ok(())
+}
+
+// the migrations dir path if given, otherwise the first location on the config file if there's one
+fn get_location(args: &ArgMatches) -> Result<PathBuf> {
+    if let Some(path) = args.value_of("path") {
+        return Ok(PathBuf::from(path));
+    }
+
+    //safe to call unwrap as we specified default value
+    let config_location = Path::new(args.value_of("config").unwrap());
+    if !config_location.exists() {
+        return Ok(PathBuf::from("./migrations"));
+    }
+
+    let config =
+        Config::from_file_location(config_location).context("could not parse the config file")?;
+    config
+        .get_locations()
+        .first()
+        .cloned()
+        .ok_or_else(|| anyhow!("config file has no migrations locations"))
+}
+
+// the greatest version of both .sql and Rust module migrations found on the location, or 0
+fn get_last_version(location: &Path) -> Result<usize> {
+    let mut last_version = 0;
+    for migration_type in [MigrationType::Sql, MigrationType::Mod].iter() {
+        for path in find_migration_files(location, *migration_type)? {
+            //safe to call unwrap as find_migration_filenames returns canonical paths
+            let filename = path
+                .file_stem()
+                .and_then(|file| file.to_os_string().into_string().ok())
+                .unwrap();
+            let migration = Migration::from_filename(&filename, "")
+                .with_context(|| format!("invalid migration file name {}", path.display()))?;
+            last_version = last_version.max(migration.version);
+        }
+    }
+    Ok(last_version)
+}
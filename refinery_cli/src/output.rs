@@ -0,0 +1,166 @@
+//! Submodule for printing the outcome of commands
+//!
+//! By default commands print human readable log lines. With `--output json`
+//! a single json document is printed to stdout instead, logs go to stderr,
+//! so that the outcome can be read by other tools. Errors are also printed as json,
+//! and in both formats the process exits with a code given by the error kind,
+//! see [`exit_code`].
+
+use anyhow::Error;
+use clap::ArgMatches;
+use refinery_core::{AppliedMigration, Migration, Report};
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Exit code for errors that are not from refinery_core, or that have no code of their own
+pub const EXIT_FAILURE: i32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    /// The format chosen by the `--output` flag of the command, text if the command has no such flag
+    pub fn from_args(args: &ArgMatches) -> OutputFormat {
+        match args.subcommand() {
+            // setup's --output is the config file location
+            ("setup", _) => OutputFormat::Text,
+            (_, Some(args)) if args.value_of("output") == Some("json") => OutputFormat::Json,
+            _ => OutputFormat::Text,
+        }
+    }
+}
+
+/// Exit code for each refinery_core error kind, which are kept stable so that scripts can rely on them
+pub fn exit_code(err: &Error) -> i32 {
+    match core_error(err) {
+        Some(refinery_core::Error::InvalidName)
+        | Some(refinery_core::Error::InvalidVersion)
+        | Some(refinery_core::Error::InvalidCallbackName(..))
+        | Some(refinery_core::Error::InvalidNamespace(..))
+        | Some(refinery_core::Error::IncompatibleMigration(..))
+        | Some(refinery_core::Error::InvalidMigrationFile(..))
+        | Some(refinery_core::Error::DuplicateVersion(..)) => 3,
+        Some(refinery_core::Error::DivergentVersion(..)) => 4,
+        Some(refinery_core::Error::MissingVersion(..)) => 5,
+        Some(refinery_core::Error::InvalidMigrationPath(..)) => 6,
+        Some(refinery_core::Error::ConfigError(..))
+        | Some(refinery_core::Error::InvalidTableName(..))
+        | Some(refinery_core::Error::GroupedMigrationsNotSupported) => 7,
+        Some(refinery_core::Error::Connection(..))
+        | Some(refinery_core::Error::MigrationsTableBackup(..))
+        | Some(refinery_core::Error::MigrationsTableLocked(..)) => 8,
+        Some(refinery_core::Error::MissingPlaceholder(..))
+        | Some(refinery_core::Error::MissingCallbackPlaceholder(..)) => 9,
+        None => EXIT_FAILURE,
+    }
+}
+
+pub fn print_report(format: OutputFormat, report: &Report) {
+    if format == OutputFormat::Text {
+        // the migrations are already logged while they are applied
+        return;
+    }
+
+    let document = json!({
+        "status": "success",
+        "applied": report.applied_migrations().iter().map(migration_json).collect::<Vec<_>>(),
+        "skipped": report.skipped_migrations().iter().map(migration_json).collect::<Vec<_>>(),
+        "divergent": report
+            .divergent_migrations()
+            .iter()
+            .map(|(applied, migration)| json!({
+                "applied": applied_migration_json(applied),
+                "filesystem": migration_json(migration),
+            }))
+            .collect::<Vec<_>>(),
+        "missing": report.missing_migrations().iter().map(applied_migration_json).collect::<Vec<_>>(),
+    });
+    println!("{}", document);
+}
+
+pub fn print_created(format: OutputFormat, path: &Path) {
+    match format {
+        OutputFormat::Text => println!("created migration {}", path.display()),
+        OutputFormat::Json => println!(
+            "{}",
+            json!({
+                "status": "success",
+                "created": path.display().to_string(),
+            })
+        ),
+    }
+}
+
+/// Prints the error and returns the code the process should exit with
+pub fn print_error(format: OutputFormat, err: &Error) -> i32 {
+    let code = exit_code(err);
+    match format {
+        OutputFormat::Text => eprintln!("Error: {:?}", err),
+        OutputFormat::Json => println!(
+            "{}",
+            json!({
+                "status": "error",
+                "kind": error_kind(err),
+                "message": format!("{:#}", err),
+                "exit_code": code,
+            })
+        ),
+    }
+    code
+}
+
+fn error_kind(err: &Error) -> &'static str {
+    match core_error(err) {
+        Some(refinery_core::Error::InvalidName) => "invalid_name",
+        Some(refinery_core::Error::InvalidVersion) => "invalid_version",
+        Some(refinery_core::Error::InvalidCallbackName(..)) => "invalid_callback_name",
+        Some(refinery_core::Error::InvalidNamespace(..)) => "invalid_namespace",
+        Some(refinery_core::Error::IncompatibleMigration(..)) => "incompatible_migration",
+        Some(refinery_core::Error::InvalidMigrationFile(..)) => "invalid_migration_file",
+        Some(refinery_core::Error::DuplicateVersion(..)) => "duplicate_version",
+        Some(refinery_core::Error::DivergentVersion(..)) => "divergent_version",
+        Some(refinery_core::Error::MissingVersion(..)) => "missing_version",
+        Some(refinery_core::Error::InvalidMigrationPath(..)) => "invalid_migration_path",
+        Some(refinery_core::Error::ConfigError(..)) => "config_error",
+        Some(refinery_core::Error::InvalidTableName(..)) => "invalid_table_name",
+        Some(refinery_core::Error::Connection(..)) => "connection",
+        Some(refinery_core::Error::MigrationsTableBackup(..)) => "migrations_table_backup",
+        Some(refinery_core::Error::MigrationsTableLocked(..)) => "migrations_table_locked",
+        Some(refinery_core::Error::GroupedMigrationsNotSupported) => {
+            "grouped_migrations_not_supported"
+        }
+        Some(refinery_core::Error::MissingPlaceholder(..))
+        | Some(refinery_core::Error::MissingCallbackPlaceholder(..)) => "missing_placeholder",
+        None => "other",
+    }
+}
+
+// the innermost refinery_core error that caused err, if any, as errors may have been given context
+// and refinery_core errors may wrap others, i.e. the naming error of an invalid migration file
+fn core_error(err: &Error) -> Option<&refinery_core::Error> {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<refinery_core::Error>())
+        .last()
+}
+
+fn migration_json(migration: &Migration) -> Value {
+    json!({
+        "version": migration.version,
+        "name": migration.name,
+        "namespace": migration.namespace(),
+        "checksum": migration.checksum().to_string(),
+    })
+}
+
+fn applied_migration_json(migration: &AppliedMigration) -> Value {
+    json!({
+        "version": migration.version,
+        "name": migration.name,
+        "namespace": migration.namespace,
+        "checksum": migration.checksum,
+        "applied_on": migration.applied_on.to_rfc3339(),
+    })
+}
@@ -10,85 +10,208 @@
 //! is mirrored in the refinery configuration file stored
 //! in `refinery.toml` in the crate root.
 //!
+//! Values not given by flags are asked interactively.
 //! When running the setup *again* when a configuration file
-//! is already present, the fields from it can be used to
-//! override otherwise default values
+//! is already present, it is only overwritten with `--force`,
+//! and the fields from it are used to override otherwise
+//! prompted values
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::ArgMatches;
 use refinery_core::config::{Config, ConfigDbType};
-use std::fs::File;
+use std::fs;
 use std::io::{self, Write};
+use std::path::Path;
 
 /// Do everything that the module docs promise. And more ✨
-pub fn handle_setup(_: &ArgMatches) -> Result<()> {
-    let cfg = get_config_from_input()?;
+pub fn handle_setup(args: &ArgMatches) -> Result<()> {
+    //safe to call unwrap as we specified default value
+    let output = Path::new(args.value_of("output").unwrap());
+
+    let existing = if output.exists() {
+        if !args.is_present("force") {
+            return Err(anyhow!(
+                "config file {} already exists, use --force to overwrite it",
+                output.display()
+            ));
+        }
+        Some(read_existing_config(output)?)
+    } else {
+        None
+    };
+
+    let cfg = get_config(args, existing)?;
     let s = toml::to_string(&cfg)?;
-    let mut file = File::create("./refinery.toml").unwrap();
-    file.write_all(s.as_bytes()).ok();
+    fs::write(output, s)
+        .with_context(|| format!("could not write config file {}", output.display()))?;
+
+    create_migrations_dir(&cfg, output)?;
     Ok(())
 }
 
-fn get_config_from_input() -> Result<Config> {
-    println!("Select database 1) Mysql 2) Postgresql 3) Sqlite: ");
-    print!("Enter a number: ");
-    io::stdout().flush()?;
+// reads the config as is, without resolving the relative paths like Config::from_file_location does,
+// so they are kept when it is written back
+fn read_existing_config(location: &Path) -> Result<Config> {
+    let file = fs::read_to_string(location)
+        .with_context(|| format!("could not read config file {}", location.display()))?;
+    toml::from_str(&file)
+        .with_context(|| format!("could not parse config file {}", location.display()))
+}
+
+fn get_config(args: &ArgMatches, existing: Option<Config>) -> Result<Config> {
+    let db_type = match args.value_of("db-type") {
+        Some(db_type) => parse_db_type(db_type)?,
+        None => match &existing {
+            Some(existing) => existing.get_db_type(),
+            None => get_db_type_from_input()?,
+        },
+    };
 
-    let mut db_type = String::new();
-    io::stdin().read_line(&mut db_type)?;
-    let db_type = match db_type.as_str().trim() {
-        "1" => ConfigDbType::Mysql,
-        "2" => ConfigDbType::Postgres,
-        "3" => ConfigDbType::Sqlite,
-        _ => return Err(anyhow!("invalid option")),
+    let existing = existing.as_ref();
+    // an existing config is used as base so its migrations settings are kept
+    let mut config = match existing {
+        Some(existing) => existing.clone().set_db_type(db_type),
+        None => Config::new(db_type),
     };
-    let mut config = Config::new(db_type);
-
-    if config.get_db_type() == ConfigDbType::Sqlite {
-        print!("Enter database path: ");
-        io::stdout().flush()?;
-        let mut db_path = String::new();
-        io::stdin().read_line(&mut db_path)?;
-        //remove \n
-        db_path.pop();
-        config = config.set_db_path(db_path.trim());
-        return Ok(config);
+
+    if db_type == ConfigDbType::Sqlite {
+        let db_path = match args.value_of("path") {
+            Some(db_path) => db_path.to_string(),
+            None => match existing.and_then(Config::get_db_path) {
+                Some(db_path) => db_path.display().to_string(),
+                None => prompt("Enter database path")?,
+            },
+        };
+        return Ok(config.set_db_path(&db_path));
     }
 
-    print!("Enter database host: ");
-    io::stdout().flush()?;
-    let mut db_host = String::new();
-    io::stdin().read_line(&mut db_host)?;
-    db_host.pop();
+    let db_host = value_or_prompt(
+        args,
+        "host",
+        existing.and_then(Config::get_db_host),
+        "Enter database host",
+    )?;
     config = config.set_db_host(&db_host);
 
-    print!("Enter database port: ");
-    io::stdout().flush()?;
-    let mut db_port = String::new();
-    io::stdin().read_line(&mut db_port)?;
-    db_port.pop();
+    let db_port = value_or_prompt(
+        args,
+        "port",
+        existing.and_then(Config::get_db_port),
+        "Enter database port",
+    )?;
     config = config.set_db_port(&db_port);
 
-    print!("Enter database username: ");
-    io::stdout().flush()?;
-    let mut db_user = String::new();
-    io::stdin().read_line(&mut db_user)?;
-    db_user.pop();
+    let db_user = value_or_prompt(
+        args,
+        "user",
+        existing.and_then(Config::get_db_user),
+        "Enter database username",
+    )?;
     config = config.set_db_user(&db_user);
 
-    print!("Enter database password: ");
-    io::stdout().flush()?;
-    let mut db_pass = String::new();
-    io::stdin().read_line(&mut db_pass)?;
-    db_pass.pop();
+    let db_pass = match args
+        .value_of("password")
+        .or_else(|| existing.and_then(Config::get_db_pass))
+    {
+        Some(db_pass) => db_pass.to_string(),
+        None => rpassword::prompt_password_stdout("Enter database password: ")?,
+    };
     config = config.set_db_pass(&db_pass);
 
-    print!("Enter database name: ");
-    io::stdout().flush()?;
-    let mut db_name = String::new();
-    io::stdin().read_line(&mut db_name)?;
-    db_name.pop();
+    let db_name = value_or_prompt(
+        args,
+        "name",
+        existing.and_then(Config::get_db_name),
+        "Enter database name",
+    )?;
     config = config.set_db_name(&db_name);
 
     Ok(config)
 }
+
+fn value_or_prompt(
+    args: &ArgMatches,
+    name: &str,
+    existing: Option<&str>,
+    message: &str,
+) -> Result<String> {
+    match args.value_of(name).or(existing) {
+        Some(value) => Ok(value.to_string()),
+        None => prompt(message),
+    }
+}
+
+fn prompt(message: &str) -> Result<String> {
+    print!("{}: ", message);
+    io::stdout().flush()?;
+    let mut value = String::new();
+    io::stdin().read_line(&mut value)?;
+    Ok(value.trim().to_string())
+}
+
+fn parse_db_type(db_type: &str) -> Result<ConfigDbType> {
+    match db_type.to_lowercase().as_str() {
+        "mysql" => Ok(ConfigDbType::Mysql),
+        "postgres" | "postgresql" => Ok(ConfigDbType::Postgres),
+        "sqlite" => Ok(ConfigDbType::Sqlite),
+        "mssql" | "sqlserver" => Ok(ConfigDbType::Mssql),
+        "cockroach" | "cockroachdb" => Ok(ConfigDbType::Cockroach),
+        _ => Err(anyhow!("invalid database type {}", db_type)),
+    }
+}
+
+fn get_db_type_from_input() -> Result<ConfigDbType> {
+    println!("Select database 1) Mysql 2) Postgresql 3) Sqlite 4) Mssql 5) Cockroach: ");
+    let db_type = prompt("Enter a number")?;
+    match db_type.as_str() {
+        "1" => Ok(ConfigDbType::Mysql),
+        "2" => Ok(ConfigDbType::Postgres),
+        "3" => Ok(ConfigDbType::Sqlite),
+        "4" => Ok(ConfigDbType::Mssql),
+        "5" => Ok(ConfigDbType::Cockroach),
+        _ => Err(anyhow!("invalid option")),
+    }
+}
+
+// creates the first migrations location with an initial migration, if it doesn't exist yet
+fn create_migrations_dir(config: &Config, config_location: &Path) -> Result<()> {
+    let location = match config.get_locations().first() {
+        Some(location) => location,
+        None => return Ok(()),
+    };
+    // relative locations are relative to the config file, see Config::from_file_location
+    let location = match config_location.parent() {
+        Some(config_dir) if location.is_relative() => config_dir.join(location),
+        _ => location.to_path_buf(),
+    };
+    if location.exists() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(&location).with_context(|| {
+        format!(
+            "could not create migrations directory {}",
+            location.display()
+        )
+    })?;
+    let initial = location.join("V1__initial.sql");
+    fs::write(&initial, initial_migration(config.get_db_type()))
+        .with_context(|| format!("could not write migration file {}", initial.display()))?;
+    Ok(())
+}
+
+fn initial_migration(db_type: ConfigDbType) -> String {
+    let db_name = match db_type {
+        ConfigDbType::Mysql => "Mysql",
+        ConfigDbType::Postgres => "Postgresql",
+        ConfigDbType::Sqlite => "Sqlite",
+        ConfigDbType::Mssql => "Mssql",
+        ConfigDbType::Cockroach => "Cockroach",
+    };
+    format!(
+        "-- initial migration generated by refinery setup for a {} database\n\
+         -- replace the statement below with your schema\n\
+         SELECT 1;\n",
+        db_name
+    )
+}
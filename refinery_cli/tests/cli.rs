@@ -1,6 +1,8 @@
 mod cli {
     use assert_cmd::prelude::*;
+    use predicates::prelude::*;
     use predicates::str::contains;
+    use std::fs;
     use std::process::Command;
 
     // `refinery` with no args should exit with a non-zero code.
@@ -27,4 +29,506 @@ mod cli {
             .assert()
             .failure();
     }
+
+    #[test]
+    fn setup_from_flags() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("refinery.toml");
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["setup", "--db-type", "sqlite", "--path", "./db.sql", "-o"])
+            .arg(&config_path)
+            .assert()
+            .success();
+
+        let config = fs::read_to_string(&config_path).unwrap();
+        assert!(config.contains("db_type = \"Sqlite\""));
+        assert!(config.contains("db_path = \"./db.sql\""));
+        assert!(tmp_dir
+            .path()
+            .join("migrations")
+            .join("V1__initial.sql")
+            .is_file());
+    }
+
+    #[test]
+    fn setup_prompts_missing_values() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("refinery.toml");
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&[
+                "setup",
+                "--db-type",
+                "postgres",
+                "--host",
+                "localhost",
+                "-o",
+            ])
+            .arg(&config_path)
+            .with_stdin()
+            .buffer("5432\npostgres\nsecret\nrefinery\n")
+            .assert()
+            .success()
+            .stdout(contains("Enter database password"));
+
+        let config = fs::read_to_string(&config_path).unwrap();
+        assert!(config.contains("db_host = \"localhost\""));
+        assert!(config.contains("db_port = \"5432\""));
+        assert!(config.contains("db_pass = \"secret\""));
+        assert!(config.contains("db_name = \"refinery\""));
+    }
+
+    #[test]
+    fn setup_reads_password_from_env() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("refinery.toml");
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&[
+                "setup",
+                "--db-type",
+                "postgres",
+                "--host",
+                "localhost",
+                "--port",
+                "5432",
+                "--user",
+                "postgres",
+                "--name",
+                "refinery",
+                "-o",
+            ])
+            .arg(&config_path)
+            .env("REFINERY_DB_PASSWORD", "secret")
+            .assert()
+            .success()
+            .stdout(contains("Enter database password").not());
+
+        let config = fs::read_to_string(&config_path).unwrap();
+        assert!(config.contains("db_pass = \"secret\""));
+    }
+
+    #[test]
+    fn setup_doesnt_overwrite_config_without_force() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("refinery.toml");
+        fs::write(
+            &config_path,
+            "[main]\ndb_type = \"Sqlite\"\ndb_path = \"./db.sql\"\n",
+        )
+        .unwrap();
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&[
+                "setup",
+                "--db-type",
+                "sqlite",
+                "--path",
+                "./other.sql",
+                "-o",
+            ])
+            .arg(&config_path)
+            .assert()
+            .failure()
+            .stderr(contains("use --force to overwrite it"));
+    }
+
+    #[test]
+    fn setup_uses_existing_config_values_with_force() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = tmp_dir.path().join("refinery.toml");
+        fs::write(
+            &config_path,
+            "[main]\ndb_type = \"Sqlite\"\ndb_path = \"./db.sql\"\n\n[migrations]\ntable_name = \"history\"\n",
+        )
+        .unwrap();
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["setup", "--force", "-o"])
+            .arg(&config_path)
+            .assert()
+            .success();
+
+        let config = fs::read_to_string(&config_path).unwrap();
+        assert!(config.contains("db_path = \"./db.sql\""));
+        assert!(config.contains("table_name = \"history\""));
+    }
+
+    #[test]
+    fn new_creates_next_migration() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir_all(migrations_dir.join("V1-2")).unwrap();
+        fs::write(migrations_dir.join("V1-2").join("V1__initial.sql"), "").unwrap();
+        fs::write(migrations_dir.join("V2__add_cars.rs"), "").unwrap();
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["new", "add_brand_to_cars", "-p"])
+            .arg(&migrations_dir)
+            .assert()
+            .success()
+            .stdout(contains("V3__add_brand_to_cars.sql"));
+
+        assert!(migrations_dir.join("V3__add_brand_to_cars.sql").is_file());
+    }
+
+    #[test]
+    fn new_creates_mod_migration() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["new", "initial", "-t", "rs", "-p"])
+            .arg(&migrations_dir)
+            .assert()
+            .success();
+
+        let module = fs::read_to_string(migrations_dir.join("V1__initial.rs")).unwrap();
+        assert!(module.contains("pub fn migration() -> String"));
+    }
+
+    #[test]
+    fn new_fails_on_invalid_name() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["new", "add-cars", "-p"])
+            .arg(tmp_dir.path())
+            .assert()
+            .failure()
+            .stderr(contains("invalid migration name"));
+    }
+
+    #[test]
+    fn migrates_mod_migrations() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let crate_dir = tmp_dir.path().join("app");
+        let migrations_dir = crate_dir.join("src").join("migrations");
+        fs::create_dir_all(&migrations_dir).unwrap();
+        fs::write(
+            crate_dir.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::write(
+            migrations_dir.join("V1__initial.rs"),
+            "pub fn migration() -> String {\n    \"CREATE TABLE persons (id int);\".to_string()\n}\n",
+        )
+        .unwrap();
+
+        let db_path = tmp_dir.path().join("db.sql");
+        fs::File::create(&db_path).unwrap();
+        let config_path = tmp_dir.path().join("refinery.toml");
+        fs::write(
+            &config_path,
+            format!(
+                "[main]\ndb_type = \"Sqlite\"\ndb_path = {:?}\n",
+                db_path.display().to_string()
+            ),
+        )
+        .unwrap();
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "-c"])
+            .arg(&config_path)
+            .arg("mod")
+            .arg(&migrations_dir)
+            .arg("--manifest-path")
+            .arg(crate_dir.join("Cargo.toml"))
+            .assert()
+            .success()
+            .stdout(contains("applying migration: V1__initial"));
+    }
+
+    #[test]
+    fn migrate_mod_rejects_modules_with_the_same_name() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        for dir in &["a", "b"] {
+            fs::create_dir_all(migrations_dir.join(dir)).unwrap();
+            fs::write(migrations_dir.join(dir).join("V1__initial.rs"), "").unwrap();
+        }
+        fs::write(
+            tmp_dir.path().join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        let config_path = sqlite_config(tmp_dir.path(), &migrations_dir);
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "-c"])
+            .arg(&config_path)
+            .arg("mod")
+            .arg(&migrations_dir)
+            .arg("--manifest-path")
+            .arg(tmp_dir.path().join("Cargo.toml"))
+            .assert()
+            .failure()
+            .stderr(contains("migration module V1__initial found on both"));
+    }
+
+    #[test]
+    fn migrate_mod_rejects_modules_running_on_the_transaction() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir_all(&migrations_dir).unwrap();
+        fs::write(
+            migrations_dir.join("V1__initial.rs"),
+            "pub fn migrate(executor: &mut dyn Executor) -> Result<(), MigrationError> {\n    Ok(())\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            tmp_dir.path().join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        let config_path = sqlite_config(tmp_dir.path(), &migrations_dir);
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "-c"])
+            .arg(&config_path)
+            .arg("mod")
+            .arg(&migrations_dir)
+            .arg("--manifest-path")
+            .arg(tmp_dir.path().join("Cargo.toml"))
+            .assert()
+            .failure()
+            .stderr(contains("can only be run with include_migration_mods!"));
+    }
+
+    // writes a config for a new sqlite database migrating the given location
+    fn sqlite_config(dir: &std::path::Path, location: &std::path::Path) -> std::path::PathBuf {
+        let db_path = dir.join("db.sql");
+        fs::File::create(&db_path).unwrap();
+        let config_path = dir.join("refinery.toml");
+        fs::write(
+            &config_path,
+            format!(
+                "[main]\ndb_type = \"Sqlite\"\ndb_path = {:?}\n\n[migrations]\nlocations = [{:?}]\n",
+                db_path.display().to_string(),
+                location.display().to_string()
+            ),
+        )
+        .unwrap();
+        config_path
+    }
+
+    #[test]
+    fn migrate_prints_json_report() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir_all(&migrations_dir).unwrap();
+        fs::write(
+            migrations_dir.join("V1__initial.sql"),
+            "CREATE TABLE persons (id int);",
+        )
+        .unwrap();
+        let config_path = sqlite_config(tmp_dir.path(), &migrations_dir);
+
+        let output = Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "--output", "json", "-c"])
+            .arg(&config_path)
+            .arg("files")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!("success", report["status"]);
+        assert_eq!(1, report["applied"][0]["version"]);
+        assert_eq!("initial", report["applied"][0]["name"]);
+        assert_eq!(0, report["skipped"].as_array().unwrap().len());
+
+        fs::write(
+            migrations_dir.join("V2__add_cars.sql"),
+            "CREATE TABLE cars (id int);",
+        )
+        .unwrap();
+        let output = Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "--output", "json", "-c"])
+            .arg(&config_path)
+            .arg("files")
+            .output()
+            .unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(2, report["applied"][0]["version"]);
+        assert_eq!(1, report["skipped"][0]["version"]);
+    }
+
+    #[test]
+    fn migrate_prints_json_error_with_exit_code() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir_all(&migrations_dir).unwrap();
+        fs::write(
+            migrations_dir.join("V1__initial.sql"),
+            "CREATE TABLE persons (id int);",
+        )
+        .unwrap();
+        let config_path = sqlite_config(tmp_dir.path(), &migrations_dir);
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "-c"])
+            .arg(&config_path)
+            .arg("files")
+            .assert()
+            .success();
+
+        fs::write(
+            migrations_dir.join("V1__initial.sql"),
+            "CREATE TABLE persons (id int, name text);",
+        )
+        .unwrap();
+        let output = Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "--output", "json", "-c"])
+            .arg(&config_path)
+            .arg("files")
+            .output()
+            .unwrap();
+        assert_eq!(Some(4), output.status.code());
+        let error: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!("error", error["status"]);
+        assert_eq!("divergent_version", error["kind"]);
+        assert_eq!(4, error["exit_code"]);
+    }
+
+    #[test]
+    fn migrate_merges_several_locations() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        let plugin_dir = tmp_dir.path().join("plugin_migrations");
+        fs::create_dir_all(&migrations_dir).unwrap();
+        fs::create_dir_all(&plugin_dir).unwrap();
+        fs::write(
+            migrations_dir.join("V1__initial.sql"),
+            "CREATE TABLE persons (id int);",
+        )
+        .unwrap();
+        fs::write(
+            plugin_dir.join("V2__add_cars.sql"),
+            "CREATE TABLE cars (id int);",
+        )
+        .unwrap();
+        let config_path = sqlite_config(tmp_dir.path(), &migrations_dir);
+
+        let output = Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "--output", "json", "-c"])
+            .arg(&config_path)
+            .args(&["files", "-p"])
+            .arg(&migrations_dir)
+            .arg("-p")
+            .arg(&plugin_dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(1, report["applied"][0]["version"]);
+        assert_eq!(2, report["applied"][1]["version"]);
+
+        fs::write(
+            plugin_dir.join("V1__add_motos.sql"),
+            "CREATE TABLE motos (id int);",
+        )
+        .unwrap();
+        let output = Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "--output", "json", "-c"])
+            .arg(&config_path)
+            .args(&["files", "-p"])
+            .arg(&migrations_dir)
+            .arg("-p")
+            .arg(&plugin_dir)
+            .output()
+            .unwrap();
+        assert_eq!(Some(3), output.status.code());
+        let error: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!("duplicate_version", error["kind"]);
+    }
+
+    #[test]
+    fn migrate_runs_sql_callbacks() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir_all(&migrations_dir).unwrap();
+        fs::write(
+            migrations_dir.join("V1__initial.sql"),
+            "CREATE TABLE persons (id int);",
+        )
+        .unwrap();
+        fs::write(
+            migrations_dir.join("afterMigrate.sql"),
+            "CREATE TABLE IF NOT EXISTS callbacks_log (event text);",
+        )
+        .unwrap();
+        let config_path = sqlite_config(tmp_dir.path(), &migrations_dir);
+
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "-c"])
+            .arg(&config_path)
+            .arg("files")
+            .assert()
+            .success();
+
+        // only applies if the table was created by the callback of the previous run
+        fs::write(
+            migrations_dir.join("V2__log_event.sql"),
+            "INSERT INTO callbacks_log (event) VALUES ('V2');",
+        )
+        .unwrap();
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "-c"])
+            .arg(&config_path)
+            .arg("files")
+            .assert()
+            .success()
+            .stdout(contains("applying migration: V2__log_event"));
+    }
+
+    #[test]
+    fn migrate_exits_with_config_error_code() {
+        Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["migrate", "-c", "missing.toml", "files"])
+            .assert()
+            .code(7)
+            .stderr(contains("could not parse the config file"));
+    }
+
+    #[test]
+    fn new_prints_json() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+
+        let output = Command::cargo_bin("refinery")
+            .unwrap()
+            .args(&["new", "initial", "--output", "json", "-p"])
+            .arg(&migrations_dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let created: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!("success", created["status"]);
+        assert!(created["created"]
+            .as_str()
+            .unwrap()
+            .ends_with("V1__initial.sql"));
+    }
 }
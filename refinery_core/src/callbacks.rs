@@ -0,0 +1,327 @@
+use async_trait::async_trait;
+use regex::Regex;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::runner::replace_placeholders;
+use crate::traits::r#async::AsyncExecutor;
+use crate::traits::sync::Executor;
+use crate::{Error, Migration, Report};
+
+// regex used to match callback file names without extension, i.e. afterMigrate or afterMigrate__grants
+pub(crate) const CALLBACK_NAME_RE: &str =
+    r"(beforeMigrate|beforeEach|afterEachError|afterEach|afterMigrate)(?:__(\w+))?";
+
+lazy_static::lazy_static! {
+    static ref RE: regex::Regex = Regex::new(&format!("^{}$", CALLBACK_NAME_RE)).unwrap();
+}
+
+/// An enum set that represents the events of the migration cycle on which callbacks are invoked
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CallbackEvent {
+    BeforeMigrate,
+    BeforeEach,
+    AfterEach,
+    AfterEachError,
+    AfterMigrate,
+}
+
+impl CallbackEvent {
+    fn from_name(name: &str) -> Option<CallbackEvent> {
+        match name {
+            "beforeMigrate" => Some(CallbackEvent::BeforeMigrate),
+            "beforeEach" => Some(CallbackEvent::BeforeEach),
+            "afterEach" => Some(CallbackEvent::AfterEach),
+            "afterEachError" => Some(CallbackEvent::AfterEachError),
+            "afterMigrate" => Some(CallbackEvent::AfterMigrate),
+            _ => None,
+        }
+    }
+
+    /// The name of the .sql callback files invoked on this event
+    pub fn name(&self) -> &'static str {
+        match self {
+            CallbackEvent::BeforeMigrate => "beforeMigrate",
+            CallbackEvent::BeforeEach => "beforeEach",
+            CallbackEvent::AfterEach => "afterEach",
+            CallbackEvent::AfterEachError => "afterEachError",
+            CallbackEvent::AfterMigrate => "afterMigrate",
+        }
+    }
+}
+
+impl fmt::Display for CallbackEvent {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}", self.name())
+    }
+}
+
+/// Hooks invoked by [`Migrate::migrate`] around the migration cycle.
+/// Every hook does nothing by default, so implementors only need to implement the ones they use.
+/// The queries given to the [`Executor`] run on the same connection as the migrations, but on their own transaction.
+/// Returning an `Err` from a hook aborts the migration.
+///
+/// When migrations are grouped, `before_each` is invoked for every migration before the single transaction is run,
+/// and `after_each` or `after_each_error` for every migration after it.
+///
+/// [`Migrate::migrate`]: trait.Migrate.html#method.migrate
+/// [`Executor`]: trait.Executor.html
+pub trait Callback: Send + Sync {
+    /// Invoked before the migration cycle starts
+    fn before_migrate(&self, _executor: &mut dyn Executor) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Invoked before each migration is applied
+    fn before_each(
+        &self,
+        _migration: &Migration,
+        _executor: &mut dyn Executor,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Invoked after each migration is successfully applied
+    fn after_each(
+        &self,
+        _migration: &Migration,
+        _executor: &mut dyn Executor,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Invoked after a migration fails to apply, before the error is returned
+    fn after_each_error(
+        &self,
+        _migration: &Migration,
+        _error: &Error,
+        _executor: &mut dyn Executor,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Invoked after the migration cycle finishes successfully, with its report
+    fn after_migrate(&self, _report: &Report, _executor: &mut dyn Executor) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Async counterpart of [`Callback`], invoked by [`AsyncMigrate::migrate`]
+///
+/// [`Callback`]: trait.Callback.html
+/// [`AsyncMigrate::migrate`]: trait.AsyncMigrate.html#method.migrate
+#[async_trait]
+pub trait AsyncCallback: Send + Sync {
+    /// Invoked before the migration cycle starts
+    async fn before_migrate(&self, _executor: &mut dyn AsyncExecutor) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Invoked before each migration is applied
+    async fn before_each(
+        &self,
+        _migration: &Migration,
+        _executor: &mut dyn AsyncExecutor,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Invoked after each migration is successfully applied
+    async fn after_each(
+        &self,
+        _migration: &Migration,
+        _executor: &mut dyn AsyncExecutor,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Invoked after a migration fails to apply, before the error is returned
+    async fn after_each_error(
+        &self,
+        _migration: &Migration,
+        _error: &Error,
+        _executor: &mut dyn AsyncExecutor,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Invoked after the migration cycle finishes successfully, with its report
+    async fn after_migrate(
+        &self,
+        _report: &Report,
+        _executor: &mut dyn AsyncExecutor,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Represents a .sql callback file, named after the event it is invoked on, i.e. `beforeMigrate.sql`,
+/// optionally followed by a description, i.e. `afterMigrate__grants.sql`.
+/// Callback files are gathered by the [`embed_migrations!`] macro along with the migration files
+/// and run in every migration cycle, so their statements should be idempotent.
+///
+/// [`embed_migrations!`]: macro.embed_migrations.html
+#[derive(Clone, Debug)]
+pub struct SqlCallback {
+    pub name: String,
+    pub event: CallbackEvent,
+    pub sql: String,
+}
+
+impl SqlCallback {
+    pub fn from_filename(name: &str, sql: &str) -> Result<SqlCallback, Error> {
+        let captures = RE
+            .captures(name)
+            .ok_or_else(|| Error::InvalidCallbackName(name.into()))?;
+        //safe to call unwrap as the regex only matches valid event names
+        let event = CallbackEvent::from_name(&captures[1]).unwrap();
+
+        Ok(SqlCallback {
+            name: name.into(),
+            event,
+            sql: sql.into(),
+        })
+    }
+
+    /// Returns a copy of this callback with every `${name}` placeholder on its sql replaced by the value given for `name`.
+    /// Returns `Err` if there's no value for a placeholder found on the sql
+    pub fn replace_placeholders(
+        &self,
+        placeholders: &HashMap<String, String>,
+    ) -> Result<SqlCallback, Error> {
+        let sql = replace_placeholders(&self.sql, placeholders).map_err(|placeholder| {
+            Error::MissingCallbackPlaceholder(placeholder, self.name.clone())
+        })?;
+
+        Ok(SqlCallback {
+            sql,
+            ..self.clone()
+        })
+    }
+
+    fn execute(&self, event: CallbackEvent, executor: &mut dyn Executor) -> Result<(), Error> {
+        if self.event == event {
+            log::info!("running callback: {}", self);
+            executor.execute(&[&self.sql])?;
+        }
+        Ok(())
+    }
+
+    async fn execute_async(
+        &self,
+        event: CallbackEvent,
+        executor: &mut dyn AsyncExecutor,
+    ) -> Result<(), Error> {
+        if self.event == event {
+            log::info!("running callback: {}", self);
+            executor.execute(&[&self.sql]).await?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SqlCallback {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}", self.name)
+    }
+}
+
+impl Callback for SqlCallback {
+    fn before_migrate(&self, executor: &mut dyn Executor) -> Result<(), Error> {
+        self.execute(CallbackEvent::BeforeMigrate, executor)
+    }
+
+    fn before_each(
+        &self,
+        _migration: &Migration,
+        executor: &mut dyn Executor,
+    ) -> Result<(), Error> {
+        self.execute(CallbackEvent::BeforeEach, executor)
+    }
+
+    fn after_each(&self, _migration: &Migration, executor: &mut dyn Executor) -> Result<(), Error> {
+        self.execute(CallbackEvent::AfterEach, executor)
+    }
+
+    fn after_each_error(
+        &self,
+        _migration: &Migration,
+        _error: &Error,
+        executor: &mut dyn Executor,
+    ) -> Result<(), Error> {
+        self.execute(CallbackEvent::AfterEachError, executor)
+    }
+
+    fn after_migrate(&self, _report: &Report, executor: &mut dyn Executor) -> Result<(), Error> {
+        self.execute(CallbackEvent::AfterMigrate, executor)
+    }
+}
+
+#[async_trait]
+impl AsyncCallback for SqlCallback {
+    async fn before_migrate(&self, executor: &mut dyn AsyncExecutor) -> Result<(), Error> {
+        self.execute_async(CallbackEvent::BeforeMigrate, executor)
+            .await
+    }
+
+    async fn before_each(
+        &self,
+        _migration: &Migration,
+        executor: &mut dyn AsyncExecutor,
+    ) -> Result<(), Error> {
+        self.execute_async(CallbackEvent::BeforeEach, executor)
+            .await
+    }
+
+    async fn after_each(
+        &self,
+        _migration: &Migration,
+        executor: &mut dyn AsyncExecutor,
+    ) -> Result<(), Error> {
+        self.execute_async(CallbackEvent::AfterEach, executor).await
+    }
+
+    async fn after_each_error(
+        &self,
+        _migration: &Migration,
+        _error: &Error,
+        executor: &mut dyn AsyncExecutor,
+    ) -> Result<(), Error> {
+        self.execute_async(CallbackEvent::AfterEachError, executor)
+            .await
+    }
+
+    async fn after_migrate(
+        &self,
+        _report: &Report,
+        executor: &mut dyn AsyncExecutor,
+    ) -> Result<(), Error> {
+        self.execute_async(CallbackEvent::AfterMigrate, executor)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CallbackEvent, SqlCallback};
+    use crate::Error;
+
+    #[test]
+    fn parses_callback_file_names() {
+        let callback = SqlCallback::from_filename("afterEach", "SELECT 1;").unwrap();
+        assert_eq!(CallbackEvent::AfterEach, callback.event);
+
+        let callback = SqlCallback::from_filename("afterEachError__alert", "SELECT 1;").unwrap();
+        assert_eq!(CallbackEvent::AfterEachError, callback.event);
+        assert_eq!("afterEachError__alert", callback.name);
+    }
+
+    #[test]
+    fn fails_on_invalid_callback_file_names() {
+        match SqlCallback::from_filename("afterAll", "SELECT 1;").unwrap_err() {
+            Error::InvalidCallbackName(name) => assert_eq!("afterAll", name),
+            _ => panic!("test failed"),
+        }
+    }
+}
@@ -1,15 +1,22 @@
 use crate::error::WrapMigrationError;
-use crate::{Error, Migration, Runner};
+use crate::runner::TABLE_NAME_RE;
+use crate::traits::DEFAULT_MIGRATION_TABLE_NAME;
+use crate::{Error, FindOptions, Migration, Report, Runner};
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 // refinery config file used by migrate_from_config if migration from a Config struct is prefered instead of using the macros
 // Config can either be instanced with [`Config::new`] or retrieved from a config file with [`Config::from_file_location`]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Config {
     main: Main,
+    #[serde(default)]
+    migrations: Migrations,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    placeholders: HashMap<String, String>,
 }
 
 #[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
@@ -17,6 +24,8 @@ pub enum ConfigDbType {
     Mysql,
     Postgres,
     Sqlite,
+    Mssql,
+    Cockroach,
 }
 
 impl Config {
@@ -32,6 +41,8 @@ impl Config {
                 db_pass: None,
                 db_name: None,
             },
+            migrations: Migrations::default(),
+            placeholders: HashMap::new(),
         }
     }
 
@@ -43,6 +54,10 @@ impl Config {
         let mut config: Config = toml::from_str(&file)
             .map_err(|err| Error::ConfigError(format!("could not parse config file, {}", err)))?;
 
+        if !TABLE_NAME_RE.is_match(config.get_table_name()) {
+            return Err(Error::InvalidTableName(config.get_table_name().into()));
+        }
+
         //replace relative path with canonical path in case of Sqlite db
         if config.main.db_type == ConfigDbType::Sqlite {
             let mut config_db_path = config.main.db_path.ok_or_else(|| {
@@ -68,6 +83,15 @@ impl Config {
             config.main.db_path = Some(config_db_path);
         }
 
+        //replace relative migration locations with paths relative to the config file
+        if let Some(config_dir) = location.as_ref().parent() {
+            for migrations_location in config.migrations.locations.iter_mut() {
+                if migrations_location.is_relative() {
+                    *migrations_location = config_dir.join(&migrations_location);
+                }
+            }
+        }
+
         Ok(config)
     }
 
@@ -75,12 +99,86 @@ impl Config {
         self.main.db_type
     }
 
+    pub fn get_db_user(&self) -> Option<&str> {
+        self.main.db_user.as_deref()
+    }
+
+    pub fn get_db_pass(&self) -> Option<&str> {
+        self.main.db_pass.as_deref()
+    }
+
+    pub fn get_db_path(&self) -> Option<&Path> {
+        self.main.db_path.as_deref()
+    }
+
+    pub fn get_db_host(&self) -> Option<&str> {
+        self.main.db_host.as_deref()
+    }
+
+    pub fn get_db_port(&self) -> Option<&str> {
+        self.main.db_port.as_deref()
+    }
+
+    pub fn get_db_name(&self) -> Option<&str> {
+        self.main.db_name.as_deref()
+    }
+
+    pub fn get_locations(&self) -> &[PathBuf] {
+        &self.migrations.locations
+    }
+
+    pub fn get_table_name(&self) -> &str {
+        &self.migrations.table_name
+    }
+
+    pub fn get_grouped(&self) -> bool {
+        self.migrations.grouped
+    }
+
+    pub fn get_abort_divergent(&self) -> bool {
+        self.migrations.abort_divergent
+    }
+
+    pub fn get_abort_missing(&self) -> bool {
+        self.migrations.abort_missing
+    }
+
+    pub fn get_follow_links(&self) -> bool {
+        self.migrations.follow_links
+    }
+
+    pub fn get_max_depth(&self) -> Option<usize> {
+        self.migrations.max_depth
+    }
+
+    /// The options to search for migration files on the config locations
+    pub fn get_find_options(&self) -> FindOptions {
+        FindOptions::default()
+            .set_follow_links(self.migrations.follow_links)
+            .set_max_depth(self.migrations.max_depth)
+    }
+
+    pub fn get_placeholders(&self) -> &HashMap<String, String> {
+        &self.placeholders
+    }
+
+    pub fn set_db_type(self, db_type: ConfigDbType) -> Config {
+        Config {
+            main: Main {
+                db_type,
+                ..self.main
+            },
+            ..self
+        }
+    }
+
     pub fn set_db_user(self, db_user: &str) -> Config {
         Config {
             main: Main {
                 db_user: Some(db_user.into()),
                 ..self.main
             },
+            ..self
         }
     }
 
@@ -90,6 +188,7 @@ impl Config {
                 db_pass: Some(db_pass.into()),
                 ..self.main
             },
+            ..self
         }
     }
 
@@ -99,6 +198,7 @@ impl Config {
                 db_path: Some(db_path.into()),
                 ..self.main
             },
+            ..self
         }
     }
 
@@ -108,6 +208,7 @@ impl Config {
                 db_host: Some(db_host.into()),
                 ..self.main
             },
+            ..self
         }
     }
 
@@ -117,6 +218,7 @@ impl Config {
                 db_port: Some(db_port.into()),
                 ..self.main
             },
+            ..self
         }
     }
 
@@ -126,11 +228,93 @@ impl Config {
                 db_name: Some(db_name.into()),
                 ..self.main
             },
+            ..self
+        }
+    }
+
+    pub fn set_locations<T: AsRef<Path>>(self, locations: &[T]) -> Config {
+        Config {
+            migrations: Migrations {
+                locations: locations
+                    .iter()
+                    .map(|location| location.as_ref().to_path_buf())
+                    .collect(),
+                ..self.migrations
+            },
+            ..self
+        }
+    }
+
+    /// Set the migrations table name, migrating returns `Err` if it has characters other than alphanumeric ones and "_"
+    pub fn set_table_name(self, table_name: &str) -> Config {
+        Config {
+            migrations: Migrations {
+                table_name: table_name.into(),
+                ..self.migrations
+            },
+            ..self
+        }
+    }
+
+    pub fn set_grouped(self, grouped: bool) -> Config {
+        Config {
+            migrations: Migrations {
+                grouped,
+                ..self.migrations
+            },
+            ..self
+        }
+    }
+
+    pub fn set_abort_divergent(self, abort_divergent: bool) -> Config {
+        Config {
+            migrations: Migrations {
+                abort_divergent,
+                ..self.migrations
+            },
+            ..self
+        }
+    }
+
+    pub fn set_abort_missing(self, abort_missing: bool) -> Config {
+        Config {
+            migrations: Migrations {
+                abort_missing,
+                ..self.migrations
+            },
+            ..self
+        }
+    }
+
+    pub fn set_follow_links(self, follow_links: bool) -> Config {
+        Config {
+            migrations: Migrations {
+                follow_links,
+                ..self.migrations
+            },
+            ..self
+        }
+    }
+
+    pub fn set_max_depth(self, max_depth: Option<usize>) -> Config {
+        Config {
+            migrations: Migrations {
+                max_depth,
+                ..self.migrations
+            },
+            ..self
+        }
+    }
+
+    pub fn set_placeholders(self, placeholders: HashMap<String, String>) -> Config {
+        Config {
+            placeholders,
+            ..self
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 struct Main {
     db_type: ConfigDbType,
     db_path: Option<PathBuf>,
@@ -141,6 +325,35 @@ struct Main {
     db_name: Option<String>,
 }
 
+// migration locations and runner settings, stored under the [migrations] section of the config file.
+// every field is optional and defaults to the same values as a Runner instanced with Runner::new
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
+struct Migrations {
+    locations: Vec<PathBuf>,
+    table_name: String,
+    grouped: bool,
+    abort_divergent: bool,
+    abort_missing: bool,
+    follow_links: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_depth: Option<usize>,
+}
+
+impl Default for Migrations {
+    fn default() -> Migrations {
+        Migrations {
+            locations: vec![PathBuf::from("./migrations")],
+            table_name: DEFAULT_MIGRATION_TABLE_NAME.into(),
+            grouped: false,
+            abort_divergent: true,
+            abort_missing: true,
+            follow_links: false,
+            max_depth: None,
+        }
+    }
+}
+
 fn build_db_url(name: &str, config: &Config) -> String {
     let mut url: String = name.to_string() + "://";
 
@@ -166,13 +379,42 @@ fn build_db_url(name: &str, config: &Config) -> String {
     url
 }
 
+// the runner of migrate_from_config and migrate_from_config_async, the settings of `config` are honored
+// on top of the given ones, i.e. migrations are grouped if either `grouped` or the config's `grouped` are set
+#[cfg(any(
+    feature = "mysql",
+    feature = "rusqlite",
+    feature = "postgres",
+    feature = "mysql_async",
+    feature = "tokio-postgres",
+    all(feature = "tiberius", feature = "tokio")
+))]
+fn config_runner(
+    config: &Config,
+    grouped: bool,
+    divergent: bool,
+    missing: bool,
+    migrations: &[Migration],
+) -> Runner {
+    Runner::new(migrations)
+        .set_grouped(grouped || config.get_grouped())
+        .set_abort_divergent(divergent && config.get_abort_divergent())
+        .set_abort_missing(missing && config.get_abort_missing())
+        .set_migration_table_name(config.get_table_name())
+        .set_placeholders(config.get_placeholders().clone())
+}
+
 /// migrates from a given config file location
 /// use this function if you prefer to generate a config file either from refinery_cli or by hand,
-/// and migrate without having to pass a database Connection
+/// and migrate without having to pass a database Connection.
+/// The `grouped`, `abort_divergent`, `abort_missing`, `table_name` and `placeholders` settings of `config` are honored,
+/// migrations are grouped if either `grouped` or the config's `grouped` are true, and divergent and missing migrations
+/// abort the migration only if both the argument and the config's setting are true.
 /// # Panics
 ///
 /// This function panics if refinery was not built with database driver support for the target database,
 /// eg trying to migrate a PostgresSQL without feature postgres enabled.
+/// Mssql configs return `Err`, as they can only be migrated by `migrate_from_config_async`.
 #[cfg(any(feature = "mysql", feature = "rusqlite", feature = "postgres",))]
 pub fn migrate_from_config(
     config: &Config,
@@ -180,14 +422,31 @@ pub fn migrate_from_config(
     divergent: bool,
     missing: bool,
     migrations: &[Migration],
-) -> Result<(), Error> {
+) -> Result<Report, Error> {
+    let runner = config_runner(config, grouped, divergent, missing, migrations);
+    migrate_from_config_with_runner(config, &runner)
+}
+
+/// migrates the database of `config` with `runner`, i.e. one built with [`Runner::from_config`],
+/// connecting to it the way `migrate_from_config` does
+/// # Panics
+///
+/// This function panics if refinery was not built with database driver support for the target database,
+/// eg trying to migrate a PostgresSQL without feature postgres enabled.
+/// Mssql configs return `Err`, as they can only be migrated by `migrate_from_config_with_runner_async`.
+///
+/// [`Runner::from_config`]: ../struct.Runner.html#method.from_config
+#[cfg(any(feature = "mysql", feature = "rusqlite", feature = "postgres",))]
+pub fn migrate_from_config_with_runner(config: &Config, runner: &Runner) -> Result<Report, Error> {
+    // assigned by the arm of the database type, the others panic when the driver is not enabled
+    let report;
     match config.main.db_type {
         ConfigDbType::Mysql => {
             cfg_if::cfg_if! {
                 if #[cfg(feature = "mysql")] {
                     let url = build_db_url("mysql", &config);
                     let mut connection = mysql::Conn::new(&url).migration_err("could not connect to database")?;
-                    Runner::new(migrations).set_grouped(grouped).set_abort_divergent(divergent).set_abort_missing(missing).run(&mut connection)?;
+                    report = runner.run(&mut connection)?;
                 } else {
                     panic!("tried to migrate from config for a mysql database, but feature mysql not enabled!");
                 }
@@ -199,7 +458,7 @@ pub fn migrate_from_config(
                     //may have been checked earlier on config parsing, even if not let it fail with a Rusqlite db file not found error
                     let path = config.main.db_path.clone().unwrap_or_default();
                     let mut connection = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE).migration_err("could not open database")?;
-                    Runner::new(migrations).set_grouped(grouped).set_abort_divergent(divergent).set_abort_missing(missing).run(&mut connection)?;
+                    report = runner.run(&mut connection)?;
                 } else {
                     panic!("tried to migrate from config for a sqlite database, but feature rusqlite not enabled!");
                 }
@@ -210,45 +469,102 @@ pub fn migrate_from_config(
                 if #[cfg(feature = "postgres")] {
                     let path = build_db_url("postgresql", &config);
                     let mut connection = postgres::Client::connect(path.as_str(), postgres::NoTls).migration_err("could not connect to database")?;
-                    Runner::new(migrations).set_grouped(grouped).set_abort_divergent(divergent).set_abort_missing(missing).run(&mut connection)?;
+                    report = runner.run(&mut connection)?;
                 } else {
                     panic!("tried to migrate from config for a postgresql database, but feature postgres not enabled!");
                 }
             }
         }
+        ConfigDbType::Cockroach => {
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "postgres")] {
+                    let path = build_db_url("postgresql", &config);
+                    let connection = postgres::Client::connect(path.as_str(), postgres::NoTls).migration_err("could not connect to database")?;
+                    let mut connection = crate::CockroachConnection::new(connection);
+                    report = runner.run(&mut connection)?;
+                } else {
+                    panic!("tried to migrate from config for a cockroach database, but feature postgres not enabled!");
+                }
+            }
+        }
+        ConfigDbType::Mssql => {
+            return Err(Error::ConfigError(
+                "mssql databases can only be migrated with migrate_from_config_async and the tiberius feature".into(),
+            ));
+        }
     }
-    Ok(())
+    Ok(report)
 }
 
 /// migrates from a given config file location
 /// use this function if you prefer to generate a config file either from refinery_cli or by hand,
-/// and migrate without having to pass a database Connection
+/// and migrate without having to pass a database Connection.
+/// The settings of `config` are honored the way `migrate_from_config` does.
 /// # Panics
 ///
 /// This function panics if refinery was not built with database driver support for the target database,
 /// eg trying to migrate a PostgresSQL without feature postgres enabled.
-#[cfg(any(feature = "mysql_async", feature = "tokio-postgres"))]
+#[cfg(any(
+    feature = "mysql_async",
+    feature = "tokio-postgres",
+    all(feature = "rusqlite", feature = "tokio"),
+    all(feature = "tiberius", feature = "tokio")
+))]
 pub async fn migrate_from_config_async(
     config: &Config,
     grouped: bool,
     divergent: bool,
     missing: bool,
     migrations: &[Migration],
-) -> Result<(), Error> {
+) -> Result<Report, Error> {
+    let runner = config_runner(config, grouped, divergent, missing, migrations);
+    migrate_from_config_with_runner_async(config, &runner).await
+}
+
+/// migrates the database of `config` asynchronously with `runner`, i.e. one built with [`Runner::from_config`],
+/// connecting to it the way `migrate_from_config_async` does
+/// # Panics
+///
+/// This function panics if refinery was not built with database driver support for the target database,
+/// eg trying to migrate a PostgresSQL without feature postgres enabled.
+///
+/// [`Runner::from_config`]: ../struct.Runner.html#method.from_config
+#[cfg(any(
+    feature = "mysql_async",
+    feature = "tokio-postgres",
+    all(feature = "rusqlite", feature = "tokio"),
+    all(feature = "tiberius", feature = "tokio")
+))]
+pub async fn migrate_from_config_with_runner_async(
+    config: &Config,
+    runner: &Runner,
+) -> Result<Report, Error> {
+    // assigned by the arm of the database type, the others panic when the driver is not enabled
+    let report;
     match config.main.db_type {
         ConfigDbType::Mysql => {
             cfg_if::cfg_if! {
                 if #[cfg(feature = "mysql_async")] {
                     let url = build_db_url("mysql", &config);
                     let mut pool = mysql_async::Pool::from_url(&url).migration_err("could not connect to the database")?;
-                    Runner::new(migrations).set_grouped(grouped).set_abort_divergent(divergent).set_abort_missing(missing).run_async(&mut pool).await?;
+                    report = runner.run_async(&mut pool).await?;
                 } else {
                     panic!("tried to migrate async from config for a mysql database, but feature mysql_async not enabled!");
                 }
             }
         }
         ConfigDbType::Sqlite => {
-            panic!("tried to migrate async from config for a sqlite database, but this feature is not implemented yet");
+            cfg_if::cfg_if! {
+                if #[cfg(all(feature = "rusqlite", feature = "tokio"))] {
+                    //may have been checked earlier on config parsing, even if not let it fail with a Rusqlite db file not found error
+                    let path = config.main.db_path.clone().unwrap_or_default();
+                    let connection = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE).migration_err("could not open database")?;
+                    let mut connection = crate::AsyncSqliteConnection::new(connection);
+                    report = runner.run_async(&mut connection).await?;
+                } else {
+                    panic!("tried to migrate async from config for a sqlite database, but either tokio or rusqlite was not enabled!");
+                }
+            }
         }
         ConfigDbType::Postgres => {
             cfg_if::cfg_if! {
@@ -261,21 +577,69 @@ pub async fn migrate_from_config_async(
                         }
                     });
 
-                    Runner::new(migrations).set_grouped(grouped).set_abort_divergent(divergent).set_abort_missing(missing).run_async(&mut client).await?;
+                    report = runner.run_async(&mut client).await?;
                 } else {
                     panic!("tried to migrate async from config for a postgresql database, but either tokio or tokio-postgres was not enabled!");
                 }
             }
         }
+        ConfigDbType::Cockroach => {
+            cfg_if::cfg_if! {
+                if #[cfg(all(feature = "tokio-postgres", feature = "tokio"))] {
+                    let path = build_db_url("postgresql", &config);
+                    let (client, connection ) = tokio_postgres::connect(path.as_str(), tokio_postgres::NoTls).await.migration_err("could not connect to database")?;
+                    tokio::spawn(async move {
+                        if let Err(e) = connection.await {
+                            eprintln!("connection error: {}", e);
+                        }
+                    });
+
+                    let mut client = crate::CockroachConnection::new(client);
+                    report = runner.run_async(&mut client).await?;
+                } else {
+                    panic!("tried to migrate async from config for a cockroach database, but either tokio or tokio-postgres was not enabled!");
+                }
+            }
+        }
+        ConfigDbType::Mssql => {
+            cfg_if::cfg_if! {
+                if #[cfg(all(feature = "tiberius", feature = "futures", feature = "tokio-util", feature = "tokio"))] {
+                    use tokio_util::compat::Tokio02AsyncWriteCompatExt;
+
+                    let mut tiberius_config = tiberius::Config::new();
+                    if let Some(host) = &config.main.db_host {
+                        tiberius_config.host(host);
+                    }
+                    if let Some(port) = &config.main.db_port {
+                        let port = port.parse().map_err(|_| Error::ConfigError(format!("invalid port {}", port)))?;
+                        tiberius_config.port(port);
+                    }
+                    if let Some(name) = &config.main.db_name {
+                        tiberius_config.database(name);
+                    }
+                    let user = config.main.db_user.clone().unwrap_or_default();
+                    let pass = config.main.db_pass.clone().unwrap_or_default();
+                    tiberius_config.authentication(tiberius::AuthMethod::sql_server(user, pass));
+
+                    let tcp = tokio::net::TcpStream::connect(tiberius_config.get_addr()).await.migration_err("could not connect to database")?;
+                    tcp.set_nodelay(true).migration_err("could not connect to database")?;
+                    let mut client = tiberius::Client::connect(tiberius_config, tcp.compat_write()).await.migration_err("could not connect to database")?;
+
+                    report = runner.run_async(&mut client).await?;
+                } else {
+                    panic!("tried to migrate async from config for a mssql database, but either tokio or tiberius was not enabled!");
+                }
+            }
+        }
     }
-    Ok(())
+    Ok(report)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{build_db_url, Config, Error};
+    use super::{build_db_url, Config, ConfigDbType, Error};
     use std::io::Write;
-    use std::path::Path;
+    use std::path::PathBuf;
 
     #[test]
     fn returns_config_error_from_invalid_config_location() {
@@ -356,4 +720,125 @@ mod tests {
             build_db_url("postgres", &config)
         );
     }
+
+    #[test]
+    fn reads_cockroach_db_type() {
+        let config = "[main] \n
+                     db_type = \"Cockroach\" \n
+                     db_host = \"localhost\" \n
+                     db_port = \"26257\" \n
+                     db_user = \"root\" \n
+                     db_name = \"refinery\"";
+
+        let config: Config = toml::from_str(&config).unwrap();
+
+        assert_eq!(ConfigDbType::Cockroach, config.get_db_type());
+        assert_eq!(
+            "postgresql://root@localhost:26257/refinery",
+            build_db_url("postgresql", &config)
+        );
+    }
+
+    #[test]
+    fn uses_default_migrations_settings_when_section_is_missing() {
+        let config = "[main] \n
+                     db_type = \"Postgres\" \n";
+
+        let config: Config = toml::from_str(&config).unwrap();
+
+        assert_eq!(&[PathBuf::from("./migrations")], config.get_locations());
+        assert_eq!("refinery_schema_history", config.get_table_name());
+        assert!(!config.get_grouped());
+        assert!(config.get_abort_divergent());
+        assert!(config.get_abort_missing());
+    }
+
+    #[test]
+    fn reads_migrations_settings() {
+        let config = "[main] \n
+                     db_type = \"Postgres\" \n
+                     [migrations] \n
+                     locations = [\"/migrations/core\", \"/migrations/service\"] \n
+                     table_name = \"service_schema_history\" \n
+                     grouped = true \n
+                     abort_missing = false";
+
+        let config: Config = toml::from_str(&config).unwrap();
+
+        assert_eq!(
+            &[
+                PathBuf::from("/migrations/core"),
+                PathBuf::from("/migrations/service")
+            ],
+            config.get_locations()
+        );
+        assert_eq!("service_schema_history", config.get_table_name());
+        assert!(config.get_grouped());
+        assert!(config.get_abort_divergent());
+        assert!(!config.get_abort_missing());
+    }
+
+    #[test]
+    fn returns_error_from_invalid_table_name() {
+        let config = "[main] \n
+                     db_type = \"Postgres\" \n
+                     [migrations] \n
+                     table_name = \"history'; DROP TABLE users; --\"";
+
+        let config_dir = tempfile::TempDir::new().unwrap();
+        let config_path = config_dir.path().join("refinery.toml");
+        std::fs::write(&config_path, config).unwrap();
+        match Config::from_file_location(&config_path).unwrap_err() {
+            Error::InvalidTableName(name) => assert_eq!("history'; DROP TABLE users; --", name),
+            _ => panic!("test failed"),
+        }
+    }
+
+    #[test]
+    fn builds_migrations_locations_relative_to_config_file() {
+        let config = "[main] \n
+                     db_type = \"Postgres\" \n
+                     [migrations] \n
+                     locations = [\"sql_migrations\"]";
+
+        let config_dir = tempfile::TempDir::new().unwrap();
+        let config_path = config_dir.path().join("refinery.toml");
+        std::fs::write(&config_path, config).unwrap();
+        let config = Config::from_file_location(&config_path).unwrap();
+
+        assert_eq!(
+            &[config_dir.path().join("sql_migrations")],
+            config.get_locations()
+        );
+    }
+
+    #[test]
+    fn reads_placeholders() {
+        let config = "[main] \n
+                     db_type = \"Postgres\" \n
+                     [placeholders] \n
+                     schema = \"service\" \n
+                     role = \"service_rw\"";
+
+        let config: Config = toml::from_str(&config).unwrap();
+
+        assert_eq!(2, config.get_placeholders().len());
+        assert_eq!("service", config.get_placeholders()["schema"]);
+        assert_eq!("service_rw", config.get_placeholders()["role"]);
+    }
+
+    #[test]
+    fn reads_find_options() {
+        let config = "[main] \n
+                     db_type = \"Postgres\" \n
+                     [migrations] \n
+                     follow_links = true \n
+                     max_depth = 2";
+
+        let config: Config = toml::from_str(&config).unwrap();
+        let options = config.get_find_options();
+
+        assert!(options.get_follow_links());
+        assert_eq!(Some(2), options.get_max_depth());
+    }
 }
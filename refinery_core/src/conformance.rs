@@ -0,0 +1,423 @@
+/*!
+Checks a [`Driver`] implementation behaves the way refinery expects of it.
+
+Every check takes a connection to an empty database and panics if the driver misbehaves,
+[`run`] runs all of them, each on a new connection returned by the given closure:
+
+```rust,ignore
+#[test]
+fn driver_conforms() {
+    refinery::conformance::run(|| MyConnection::open("my_database"));
+}
+```
+
+Async connections, implementing [`AsyncTransaction`], [`AsyncQuery`] and [`AsyncTransactional`],
+are checked by the checks suffixed with `_async`, which [`run_async`] runs on the connections returned by the given future:
+
+```rust,ignore
+#[tokio::test]
+async fn async_driver_conforms() {
+    refinery::conformance::run_async(|| MyAsyncConnection::open("my_database")).await;
+}
+```
+
+[`Driver`]: ../trait.Driver.html
+[`run`]: fn.run.html
+[`AsyncTransaction`]: ../trait.AsyncTransaction.html
+[`AsyncQuery`]: ../trait.AsyncQuery.html
+[`AsyncTransactional`]: ../trait.AsyncTransactional.html
+[`run_async`]: fn.run_async.html
+*/
+use crate::dialect::Dialect;
+use crate::{
+    AppliedMigration, AsyncExecutor, AsyncQuery, AsyncTransactional, Driver, Executor, Migration,
+    MigrationError, MigrationFn, MigrationFuture, Runner,
+};
+use std::fmt::Debug;
+use std::future::Future;
+
+const TABLE_NAME: &str = "refinery_conformance";
+const MIGRATION_TABLE_NAME: &str = "refinery_conformance_history";
+
+// unwraps the result of a driver method, panicking with its error
+fn succeed<T, E: Debug>(result: Result<T, E>, method: &str) -> T {
+    result.unwrap_or_else(|err| panic!("{} failed: {:?}", method, err))
+}
+
+fn create_table<D: Driver>(driver: &mut D) {
+    let query = format!("CREATE TABLE {} (id INT, name VARCHAR(255))", TABLE_NAME);
+    succeed(driver.execute_no_tx(&[&query]), "execute_no_tx");
+}
+
+fn names<D: Driver>(driver: &mut D) -> Vec<Vec<Option<String>>> {
+    let query = format!("SELECT name FROM {} ORDER BY id", TABLE_NAME);
+    succeed(driver.query(&query), "query")
+}
+
+// the migrations run by the checks, with insert_moto as the code of the third one
+fn migrations(insert_moto: MigrationFn) -> Vec<Migration> {
+    vec![
+        Migration::from_filename(
+            "V1__create_table",
+            &format!("CREATE TABLE {} (id INT, name VARCHAR(255));", TABLE_NAME),
+        )
+        .unwrap(),
+        Migration::from_filename(
+            "V2__insert_car",
+            &format!("INSERT INTO {} (id, name) VALUES (1, 'car');", TABLE_NAME),
+        )
+        .unwrap(),
+        Migration::from_code("V3__insert_moto", insert_moto, "").unwrap(),
+    ]
+}
+
+fn insert_moto(executor: &mut dyn Executor) -> Result<(), MigrationError> {
+    let query = format!("INSERT INTO {} (id, name) VALUES (2, 'moto')", TABLE_NAME);
+    executor.execute(&[&query])?;
+    Ok(())
+}
+
+fn insert_bike_and_fail(executor: &mut dyn Executor) -> Result<(), MigrationError> {
+    let query = format!("INSERT INTO {} (id, name) VALUES (3, 'bike')", TABLE_NAME);
+    executor.execute(&[&query])?;
+    Err("could not migrate bikes".into())
+}
+
+fn insert_moto_async(executor: &mut dyn AsyncExecutor) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let query = format!("INSERT INTO {} (id, name) VALUES (2, 'moto')", TABLE_NAME);
+        executor.execute(&[&query]).await?;
+        Ok(())
+    })
+}
+
+fn insert_bike_and_fail_async(executor: &mut dyn AsyncExecutor) -> MigrationFuture<'_> {
+    Box::pin(async move {
+        let query = format!("INSERT INTO {} (id, name) VALUES (3, 'bike')", TABLE_NAME);
+        executor.execute(&[&query]).await?;
+        Err("could not migrate bikes".into())
+    })
+}
+
+/// Checks queries executed on a transaction are kept when it is committed
+pub fn commits_transactions<D: Driver>(driver: &mut D) {
+    create_table(driver);
+    succeed(driver.begin(), "begin");
+    let query = format!("INSERT INTO {} (id, name) VALUES (1, 'car')", TABLE_NAME);
+    assert_eq!(1, succeed(driver.execute(&[&query]), "execute"));
+    succeed(driver.commit(), "commit");
+
+    assert_eq!(vec![vec![Some("car".to_string())]], names(driver));
+}
+
+/// Checks queries executed on a transaction are undone when it is rolled back
+pub fn rolls_back_transactions<D: Driver>(driver: &mut D) {
+    create_table(driver);
+    succeed(driver.begin(), "begin");
+    let query = format!("INSERT INTO {} (id, name) VALUES (1, 'car')", TABLE_NAME);
+    succeed(driver.execute(&[&query]), "execute");
+    succeed(driver.rollback(), "rollback");
+
+    assert!(
+        names(driver).is_empty(),
+        "rows inserted on a rolled back transaction were kept"
+    );
+}
+
+/// Checks queries executed outside of a transaction are committed on their own
+pub fn executes_outside_of_transactions<D: Driver>(driver: &mut D) {
+    create_table(driver);
+    let queries = [
+        format!("INSERT INTO {} (id, name) VALUES (1, 'car')", TABLE_NAME),
+        format!("INSERT INTO {} (id, name) VALUES (2, 'moto')", TABLE_NAME),
+    ];
+    let refs: Vec<&str> = queries.iter().map(AsRef::as_ref).collect();
+    assert_eq!(2, succeed(driver.execute_no_tx(&refs), "execute_no_tx"));
+
+    assert_eq!(
+        vec![
+            vec![Some("car".to_string())],
+            vec![Some("moto".to_string())]
+        ],
+        names(driver)
+    );
+}
+
+/// Checks queries return their rows with every column as text, and NULL as `None`
+pub fn queries_rows_as_text<D: Driver>(driver: &mut D) {
+    let rows = succeed(driver.query("SELECT 1, 'car', NULL"), "query");
+    assert_eq!(
+        vec![vec![Some("1".to_string()), Some("car".to_string()), None]],
+        rows
+    );
+}
+
+/// Checks applied migrations inserted on the migrations table are selected back, and undone on rollback
+pub fn keeps_history<D: Driver>(driver: &mut D) {
+    let query = D::Dialect::assert_migrations_table_query(MIGRATION_TABLE_NAME);
+    succeed(driver.execute_no_tx(&[&query]), "execute_no_tx");
+    let migrations = migrations(MigrationFn::Sync(insert_moto));
+    let plugin = migrations[0].clone().set_namespace("plugin").unwrap();
+
+    succeed(driver.begin(), "begin");
+    for migration in &[&plugin, &migrations[1], &migrations[0]] {
+        succeed(
+            driver.insert_history(migration, MIGRATION_TABLE_NAME),
+            "insert_history",
+        );
+    }
+    succeed(driver.commit(), "commit");
+
+    succeed(driver.begin(), "begin");
+    succeed(
+        driver.insert_history(&migrations[2], MIGRATION_TABLE_NAME),
+        "insert_history",
+    );
+    succeed(driver.rollback(), "rollback");
+
+    let applied = succeed(driver.query_history(MIGRATION_TABLE_NAME), "query_history");
+    assert_eq!(
+        vec![
+            migrations[0].as_applied(),
+            migrations[1].as_applied(),
+            plugin.as_applied()
+        ],
+        applied
+    );
+}
+
+/// Checks a [`Runner`] applies migrations once, and leaves no trace of a failed one
+///
+/// [`Runner`]: ../struct.Runner.html
+pub fn migrates<D: Driver>(driver: &mut D) {
+    let mut migrations = migrations(MigrationFn::Sync(insert_moto));
+    let runner = Runner::new(&migrations).set_migration_table_name(MIGRATION_TABLE_NAME);
+    let report = succeed(runner.run(driver), "migrating");
+    assert_eq!(3, report.applied_migrations().len());
+    assert_eq!(
+        vec![
+            vec![Some("car".to_string())],
+            vec![Some("moto".to_string())]
+        ],
+        names(driver)
+    );
+
+    let report = succeed(runner.run(driver), "migrating again");
+    assert!(report.applied_migrations().is_empty());
+    assert_eq!(3, report.skipped_migrations().len());
+
+    migrations.push(
+        Migration::from_code(
+            "V4__insert_bike",
+            MigrationFn::Sync(insert_bike_and_fail),
+            "",
+        )
+        .unwrap(),
+    );
+    let runner = Runner::new(&migrations).set_migration_table_name(MIGRATION_TABLE_NAME);
+    assert!(
+        runner.run(driver).is_err(),
+        "a failing migration was applied"
+    );
+    assert_eq!(2, names(driver).len(), "a failed migration was kept");
+    let applied = succeed(driver.query_history(MIGRATION_TABLE_NAME), "query_history");
+    assert_eq!(3, applied.len(), "a failed migration was kept on history");
+}
+
+/// Runs every check, each on a new connection to an empty database returned by `connect`
+pub fn run<D, F>(mut connect: F)
+where
+    D: Driver,
+    F: FnMut() -> D,
+{
+    commits_transactions(&mut connect());
+    rolls_back_transactions(&mut connect());
+    executes_outside_of_transactions(&mut connect());
+    queries_rows_as_text(&mut connect());
+    keeps_history(&mut connect());
+    migrates(&mut connect());
+}
+
+async fn create_table_async<C: AsyncQuery<Vec<AppliedMigration>>>(connection: &mut C) {
+    let query = format!("CREATE TABLE {} (id INT, name VARCHAR(255))", TABLE_NAME);
+    succeed(connection.execute(&[&query]).await, "execute");
+}
+
+// selects the names on a transaction of their own, as async connections only run queries on transactions
+async fn names_async<C: AsyncTransactional>(connection: &mut C) -> Vec<Vec<Option<String>>> {
+    let query = format!("SELECT name FROM {} ORDER BY id", TABLE_NAME);
+    let mut transaction = succeed(connection.begin().await, "begin");
+    let rows = succeed(transaction.query(&query).await, "query");
+    succeed(transaction.commit().await, "commit");
+    rows
+}
+
+/// Checks queries executed on a transaction begun on an async connection are kept when it is committed
+pub async fn commits_transactions_async<C>(connection: &mut C)
+where
+    C: AsyncQuery<Vec<AppliedMigration>> + AsyncTransactional + Send,
+{
+    create_table_async(connection).await;
+    let mut transaction = succeed(connection.begin().await, "begin");
+    let query = format!("INSERT INTO {} (id, name) VALUES (1, 'car')", TABLE_NAME);
+    assert_eq!(1, succeed(transaction.execute(&[&query]).await, "execute"));
+    succeed(transaction.commit().await, "commit");
+
+    assert_eq!(
+        vec![vec![Some("car".to_string())]],
+        names_async(connection).await
+    );
+}
+
+/// Checks queries executed on a transaction begun on an async connection are undone when it is rolled back
+pub async fn rolls_back_transactions_async<C>(connection: &mut C)
+where
+    C: AsyncQuery<Vec<AppliedMigration>> + AsyncTransactional + Send,
+{
+    create_table_async(connection).await;
+    let mut transaction = succeed(connection.begin().await, "begin");
+    let query = format!("INSERT INTO {} (id, name) VALUES (1, 'car')", TABLE_NAME);
+    succeed(transaction.execute(&[&query]).await, "execute");
+    succeed(transaction.rollback().await, "rollback");
+
+    assert!(
+        names_async(connection).await.is_empty(),
+        "rows inserted on a rolled back transaction were kept"
+    );
+}
+
+/// Checks queries executed on an async connection are committed on a transaction of their own
+pub async fn executes_on_own_transaction_async<C>(connection: &mut C)
+where
+    C: AsyncQuery<Vec<AppliedMigration>> + AsyncTransactional + Send,
+{
+    create_table_async(connection).await;
+    let queries = [
+        format!("INSERT INTO {} (id, name) VALUES (1, 'car')", TABLE_NAME),
+        format!("INSERT INTO {} (id, name) VALUES (2, 'moto')", TABLE_NAME),
+    ];
+    let refs: Vec<&str> = queries.iter().map(AsRef::as_ref).collect();
+    assert_eq!(2, succeed(connection.execute(&refs).await, "execute"));
+
+    assert_eq!(
+        vec![
+            vec![Some("car".to_string())],
+            vec![Some("moto".to_string())]
+        ],
+        names_async(connection).await
+    );
+}
+
+/// Checks queries on a transaction begun on an async connection return their rows with every column as text,
+/// and NULL as `None`
+pub async fn queries_rows_as_text_async<C>(connection: &mut C)
+where
+    C: AsyncQuery<Vec<AppliedMigration>> + AsyncTransactional + Send,
+{
+    let mut transaction = succeed(connection.begin().await, "begin");
+    let rows = succeed(transaction.query("SELECT 1, 'car', NULL").await, "query");
+    succeed(transaction.commit().await, "commit");
+    assert_eq!(
+        vec![vec![Some("1".to_string()), Some("car".to_string()), None]],
+        rows
+    );
+}
+
+/// Checks applied migrations inserted on the migrations table are selected back, and undone on rollback
+pub async fn keeps_history_async<C>(connection: &mut C)
+where
+    C: AsyncQuery<Vec<AppliedMigration>> + AsyncTransactional + Send,
+{
+    let query = C::Dialect::assert_migrations_table_query(MIGRATION_TABLE_NAME);
+    succeed(connection.execute(&[&query]).await, "execute");
+    let migrations = migrations(MigrationFn::Async(insert_moto_async));
+    let plugin = migrations[0].clone().set_namespace("plugin").unwrap();
+
+    let queries: Vec<String> = [&plugin, &migrations[1], &migrations[0]]
+        .iter()
+        .map(|migration| C::Dialect::insert_migration_query(migration, MIGRATION_TABLE_NAME))
+        .collect();
+    let refs: Vec<&str> = queries.iter().map(AsRef::as_ref).collect();
+    succeed(connection.execute(&refs).await, "execute");
+
+    let mut transaction = succeed(connection.begin().await, "begin");
+    let query = C::Dialect::insert_migration_query(&migrations[2], MIGRATION_TABLE_NAME);
+    succeed(transaction.execute(&[&query]).await, "execute");
+    succeed(transaction.rollback().await, "rollback");
+
+    let query = C::Dialect::get_applied_migrations_query(MIGRATION_TABLE_NAME);
+    let applied = succeed(connection.query(&query).await, "query");
+    assert_eq!(
+        Some(vec![
+            migrations[0].as_applied(),
+            migrations[1].as_applied(),
+            plugin.as_applied()
+        ]),
+        applied
+    );
+}
+
+/// Checks a [`Runner`] applies migrations once asynchronously, and leaves no trace of a failed one
+///
+/// [`Runner`]: ../struct.Runner.html
+pub async fn migrates_async<C>(connection: &mut C)
+where
+    C: AsyncQuery<Vec<AppliedMigration>> + AsyncTransactional + Send,
+{
+    let mut migrations = migrations(MigrationFn::Async(insert_moto_async));
+    let runner = Runner::new(&migrations).set_migration_table_name(MIGRATION_TABLE_NAME);
+    let report = succeed(runner.run_async(connection).await, "migrating");
+    assert_eq!(3, report.applied_migrations().len());
+    assert_eq!(
+        vec![
+            vec![Some("car".to_string())],
+            vec![Some("moto".to_string())]
+        ],
+        names_async(connection).await
+    );
+
+    let report = succeed(runner.run_async(connection).await, "migrating again");
+    assert!(report.applied_migrations().is_empty());
+    assert_eq!(3, report.skipped_migrations().len());
+
+    migrations.push(
+        Migration::from_code(
+            "V4__insert_bike",
+            MigrationFn::Async(insert_bike_and_fail_async),
+            "",
+        )
+        .unwrap(),
+    );
+    let runner = Runner::new(&migrations).set_migration_table_name(MIGRATION_TABLE_NAME);
+    assert!(
+        runner.run_async(connection).await.is_err(),
+        "a failing migration was applied"
+    );
+    assert_eq!(
+        2,
+        names_async(connection).await.len(),
+        "a failed migration was kept"
+    );
+    let query = C::Dialect::get_applied_migrations_query(MIGRATION_TABLE_NAME);
+    let applied = succeed(connection.query(&query).await, "query");
+    assert_eq!(
+        Some(3),
+        applied.map(|applied| applied.len()),
+        "a failed migration was kept on history"
+    );
+}
+
+/// Runs every async check, each on a new connection to an empty database returned by the future `connect` returns
+pub async fn run_async<C, F, Fut>(mut connect: F)
+where
+    C: AsyncQuery<Vec<AppliedMigration>> + AsyncTransactional + Send,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = C>,
+{
+    commits_transactions_async(&mut connect().await).await;
+    rolls_back_transactions_async(&mut connect().await).await;
+    executes_on_own_transaction_async(&mut connect().await).await;
+    queries_rows_as_text_async(&mut connect().await).await;
+    keeps_history_async(&mut connect().await).await;
+    migrates_async(&mut connect().await).await;
+}
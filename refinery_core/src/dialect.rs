@@ -0,0 +1,431 @@
+/*!
+The SQL refinery runs on the migrations table, which differs between databases.
+
+Every driver names the [`Dialect`] of its database, the default methods of the trait produce
+standard SQL and each dialect overrides the statements its database understands differently.
+
+[`Dialect`]: trait.Dialect.html
+*/
+use crate::{AppliedMigration, Migration};
+use chrono::Local;
+
+// count the namespace columns of the migrations table, none on tables created before namespaces were kept on them,
+// and the backups left behind by an upgrade of the migrations table that stopped halfway
+const NAMESPACE_COLUMN_QUERY: &str = "SELECT COUNT(*) AS count FROM information_schema.columns \
+     WHERE table_schema = %SCHEMA% AND LOWER(table_name) = LOWER('%MIGRATION_TABLE_NAME%') AND column_name = 'namespace';";
+const BACKUP_TABLE_QUERY: &str = "SELECT COUNT(*) AS count FROM information_schema.tables \
+     WHERE table_schema = %SCHEMA% AND LOWER(table_name) = LOWER('%MIGRATION_TABLE_NAME%_backup');";
+
+// recreates a migrations table without the namespace column, with its migrations on the default namespace,
+// the table is created again between dropping it and copying its migrations back
+const BACKUP_MIGRATIONS_TABLE_QUERY: &str =
+    "CREATE TABLE %MIGRATION_TABLE_NAME%_backup AS SELECT version, name, applied_on, checksum FROM %MIGRATION_TABLE_NAME%;";
+const UPGRADE_MIGRATIONS_TABLE_QUERIES: [&str; 3] = [
+    "DROP TABLE %MIGRATION_TABLE_NAME%;",
+    "INSERT INTO %MIGRATION_TABLE_NAME% (version, name, applied_on, checksum, namespace) \
+     SELECT version, name, applied_on, checksum, '' FROM %MIGRATION_TABLE_NAME%_backup;",
+    "DROP TABLE %MIGRATION_TABLE_NAME%_backup;",
+];
+
+const GET_APPLIED_MIGRATIONS_QUERY: &str = "SELECT version, name, applied_on, checksum, namespace \
+     FROM %MIGRATION_TABLE_NAME% ORDER BY namespace ASC, version ASC;";
+
+// the migrations table with the version column of the given integer type
+fn assert_migrations_table(migration_table_name: &str, integer_type: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {}( \
+         version {}, \
+         name VARCHAR(255), \
+         applied_on VARCHAR(255), \
+         checksum VARCHAR(255), \
+         namespace VARCHAR(255) NOT NULL DEFAULT '', \
+         PRIMARY KEY (namespace, version));",
+        migration_table_name, integer_type
+    )
+}
+
+fn with_table_name(query: &str, migration_table_name: &str) -> String {
+    query.replace("%MIGRATION_TABLE_NAME%", migration_table_name)
+}
+
+// an information_schema query on the tables of the schema returned by schema_function
+fn in_schema(query: &str, schema_function: &str, migration_table_name: &str) -> String {
+    with_table_name(query, migration_table_name).replace("%SCHEMA%", schema_function)
+}
+
+fn upgrade_queries(
+    backup_query: &str,
+    migration_table_name: &str,
+    assert_query: String,
+) -> Vec<String> {
+    let mut queries = vec![with_table_name(backup_query, migration_table_name)];
+    queries.extend(
+        UPGRADE_MIGRATIONS_TABLE_QUERIES
+            .iter()
+            .map(|query| with_table_name(query, migration_table_name)),
+    );
+    queries.insert(2, assert_query);
+    queries
+}
+
+/// The statements a driver's database runs on the migrations table
+pub trait Dialect {
+    /// The query creating the migrations table if it doesn't exist
+    fn assert_migrations_table_query(migration_table_name: &str) -> String {
+        assert_migrations_table(migration_table_name, "INT")
+    }
+
+    /// The query counting the namespace columns of the migrations table,
+    /// 0 on migrations tables created before namespaces were kept on them
+    fn namespace_column_query(migration_table_name: &str) -> String {
+        in_schema(
+            NAMESPACE_COLUMN_QUERY,
+            "current_schema()",
+            migration_table_name,
+        )
+    }
+
+    /// The query counting the `<migrations table>_backup` tables, left behind by an upgrade
+    /// of the migrations table that stopped before copying its migrations back
+    fn backup_table_query(migration_table_name: &str) -> String {
+        in_schema(BACKUP_TABLE_QUERY, "current_schema()", migration_table_name)
+    }
+
+    /// The queries adding the namespace column to a migrations table created without it,
+    /// run on a single transaction. By default the table is copied to a backup, created again and copied back,
+    /// dialects whose schema changes commit implicitly alter it instead
+    fn upgrade_migrations_table_queries(migration_table_name: &str) -> Vec<String> {
+        upgrade_queries(
+            BACKUP_MIGRATIONS_TABLE_QUERY,
+            migration_table_name,
+            Self::assert_migrations_table_query(migration_table_name),
+        )
+    }
+
+    /// The query selecting the applied migrations, ordered by namespace and version
+    fn get_applied_migrations_query(migration_table_name: &str) -> String {
+        with_table_name(GET_APPLIED_MIGRATIONS_QUERY, migration_table_name)
+    }
+
+    /// The query inserting an applied migration on the migrations table
+    fn insert_migration_query(migration: &Migration, migration_table_name: &str) -> String {
+        format!(
+            "INSERT INTO {} (version, name, applied_on, checksum, namespace) VALUES ({}, '{}', '{}', '{}', '{}')",
+            migration_table_name,
+            migration.version,
+            migration.name,
+            Local::now().to_rfc3339(),
+            migration.checksum(),
+            migration.namespace()
+        )
+    }
+
+    /// The query deleting an applied migration from the migrations table
+    fn delete_migration_query(migration: &AppliedMigration, migration_table_name: &str) -> String {
+        format!(
+            "DELETE FROM {} WHERE namespace = '{}' AND version = {}",
+            migration_table_name, migration.namespace, migration.version
+        )
+    }
+
+    /// The statements taking the lock on the migrations table for the runner `owner`,
+    /// so that only one runner migrates the database at a time. `None` if the database has no such lock
+    fn lock_migrations_table_query(_migration_table_name: &str, _owner: &str) -> Option<String> {
+        None
+    }
+
+    /// The query counting the locks taken by [`lock_migrations_table_query`] held by `owner`,
+    /// 0 if another runner holds the lock
+    ///
+    /// [`lock_migrations_table_query`]: #method.lock_migrations_table_query
+    fn held_lock_query(_migration_table_name: &str, _owner: &str) -> Option<String> {
+        None
+    }
+
+    /// The query releasing the lock taken by [`lock_migrations_table_query`] if `owner` holds it
+    ///
+    /// [`lock_migrations_table_query`]: #method.lock_migrations_table_query
+    fn unlock_migrations_table_query(_migration_table_name: &str, _owner: &str) -> Option<String> {
+        None
+    }
+
+    /// Whether schema changes can be applied along other statements on a single transaction.
+    /// On databases that can't, grouped runs fail and the [`upgrade_migrations_table_queries`]
+    /// are run on a transaction each
+    ///
+    /// [`upgrade_migrations_table_queries`]: #method.upgrade_migrations_table_queries
+    fn supports_grouped_migrations() -> bool {
+        true
+    }
+}
+
+/// Postgres' dialect
+pub struct Postgres;
+
+impl Dialect for Postgres {
+    fn assert_migrations_table_query(migration_table_name: &str) -> String {
+        assert_migrations_table(migration_table_name, "INT4")
+    }
+}
+
+// CockroachDB has no advisory locks, a row on a lock table is held instead. The row is taken over
+// if it's older than an hour, left behind by a runner that stopped before unlocking,
+// otherwise it's kept and the runner finds out another one holds it
+const COCKROACH_LOCK_QUERY: &str = "CREATE TABLE IF NOT EXISTS %MIGRATION_TABLE_NAME%_lock (\
+     id INT4 PRIMARY KEY, owner VARCHAR(255) NOT NULL, locked_at TIMESTAMPTZ NOT NULL); \
+     INSERT INTO %MIGRATION_TABLE_NAME%_lock (id, owner, locked_at) VALUES (1, '%OWNER%', now()) \
+     ON CONFLICT (id) DO UPDATE SET owner = excluded.owner, locked_at = excluded.locked_at \
+     WHERE %MIGRATION_TABLE_NAME%_lock.locked_at < now() - INTERVAL '1 hour';";
+const COCKROACH_HELD_LOCK_QUERY: &str =
+    "SELECT COUNT(*) AS count FROM %MIGRATION_TABLE_NAME%_lock WHERE id = 1 AND owner = '%OWNER%';";
+const COCKROACH_UNLOCK_QUERY: &str =
+    "DELETE FROM %MIGRATION_TABLE_NAME%_lock WHERE id = 1 AND owner = '%OWNER%';";
+
+fn with_owner(query: &str, migration_table_name: &str, owner: &str) -> String {
+    with_table_name(query, migration_table_name).replace("%OWNER%", owner)
+}
+
+/// CockroachDB's dialect, Postgres' SQL locking with a row on the `<migrations table>_lock` table.
+///
+/// Runners fail while another one holds the row, a row older than an hour, left behind by a runner
+/// that stopped before unlocking, is taken over.
+/// CockroachDB doesn't apply schema changes along other statements on a single transaction reliably,
+/// so grouped runs fail, and upgrading a migrations table created without the namespace column
+/// runs each statement on its own transaction
+pub struct Cockroach;
+
+impl Dialect for Cockroach {
+    fn assert_migrations_table_query(migration_table_name: &str) -> String {
+        Postgres::assert_migrations_table_query(migration_table_name)
+    }
+
+    fn lock_migrations_table_query(migration_table_name: &str, owner: &str) -> Option<String> {
+        Some(with_owner(
+            COCKROACH_LOCK_QUERY,
+            migration_table_name,
+            owner,
+        ))
+    }
+
+    fn held_lock_query(migration_table_name: &str, owner: &str) -> Option<String> {
+        Some(with_owner(
+            COCKROACH_HELD_LOCK_QUERY,
+            migration_table_name,
+            owner,
+        ))
+    }
+
+    fn unlock_migrations_table_query(migration_table_name: &str, owner: &str) -> Option<String> {
+        Some(with_owner(
+            COCKROACH_UNLOCK_QUERY,
+            migration_table_name,
+            owner,
+        ))
+    }
+
+    fn supports_grouped_migrations() -> bool {
+        false
+    }
+}
+
+/// Mysql's dialect
+pub struct Mysql;
+
+impl Dialect for Mysql {
+    fn namespace_column_query(migration_table_name: &str) -> String {
+        in_schema(NAMESPACE_COLUMN_QUERY, "DATABASE()", migration_table_name)
+    }
+
+    fn backup_table_query(migration_table_name: &str) -> String {
+        in_schema(BACKUP_TABLE_QUERY, "DATABASE()", migration_table_name)
+    }
+
+    // schema changes commit implicitly, so the table is altered by a single statement
+    // that can't stop halfway, rather than being dropped and created again
+    fn upgrade_migrations_table_queries(migration_table_name: &str) -> Vec<String> {
+        vec![format!(
+            "ALTER TABLE {} ADD COLUMN namespace VARCHAR(255) NOT NULL DEFAULT '', \
+             DROP PRIMARY KEY, ADD PRIMARY KEY (namespace, version);",
+            migration_table_name
+        )]
+    }
+}
+
+/// Sqlite's dialect
+pub struct Sqlite;
+
+impl Dialect for Sqlite {
+    fn assert_migrations_table_query(migration_table_name: &str) -> String {
+        assert_migrations_table(migration_table_name, "INTEGER")
+    }
+
+    fn namespace_column_query(migration_table_name: &str) -> String {
+        format!(
+            "SELECT COUNT(*) AS count FROM pragma_table_info('{}') WHERE name = 'namespace';",
+            migration_table_name
+        )
+    }
+
+    fn backup_table_query(migration_table_name: &str) -> String {
+        format!(
+            "SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name = '{}_backup';",
+            migration_table_name
+        )
+    }
+}
+
+// T-SQL has no CREATE TABLE IF NOT EXISTS nor CREATE TABLE AS, and keeps unicode text on NVARCHAR columns
+const MSSQL_ASSERT_MIGRATIONS_TABLE_QUERY: &str =
+    "IF OBJECT_ID(N'%MIGRATION_TABLE_NAME%', N'U') IS NULL \
+     CREATE TABLE %MIGRATION_TABLE_NAME%( \
+     version INT, \
+     name NVARCHAR(255), \
+     applied_on NVARCHAR(255), \
+     checksum NVARCHAR(255), \
+     namespace NVARCHAR(255) NOT NULL DEFAULT '', \
+     PRIMARY KEY (namespace, version));";
+
+const MSSQL_BACKUP_MIGRATIONS_TABLE_QUERY: &str =
+    "SELECT version, name, applied_on, checksum INTO %MIGRATION_TABLE_NAME%_backup FROM %MIGRATION_TABLE_NAME%;";
+
+/// SQL Server's dialect, with unicode literals
+pub struct Mssql;
+
+impl Dialect for Mssql {
+    fn assert_migrations_table_query(migration_table_name: &str) -> String {
+        with_table_name(MSSQL_ASSERT_MIGRATIONS_TABLE_QUERY, migration_table_name)
+    }
+
+    fn namespace_column_query(migration_table_name: &str) -> String {
+        in_schema(
+            NAMESPACE_COLUMN_QUERY,
+            "SCHEMA_NAME()",
+            migration_table_name,
+        )
+    }
+
+    fn backup_table_query(migration_table_name: &str) -> String {
+        in_schema(BACKUP_TABLE_QUERY, "SCHEMA_NAME()", migration_table_name)
+    }
+
+    fn upgrade_migrations_table_queries(migration_table_name: &str) -> Vec<String> {
+        upgrade_queries(
+            MSSQL_BACKUP_MIGRATIONS_TABLE_QUERY,
+            migration_table_name,
+            Self::assert_migrations_table_query(migration_table_name),
+        )
+    }
+
+    fn insert_migration_query(migration: &Migration, migration_table_name: &str) -> String {
+        format!(
+            "INSERT INTO {} (version, name, applied_on, checksum, namespace) VALUES ({}, N'{}', N'{}', N'{}', N'{}')",
+            migration_table_name,
+            migration.version,
+            migration.name,
+            Local::now().to_rfc3339(),
+            migration.checksum(),
+            migration.namespace()
+        )
+    }
+
+    fn delete_migration_query(migration: &AppliedMigration, migration_table_name: &str) -> String {
+        format!(
+            "DELETE FROM {} WHERE namespace = N'{}' AND version = {}",
+            migration_table_name, migration.namespace, migration.version
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cockroach, Dialect, Mssql, Mysql, Postgres, Sqlite};
+
+    #[test]
+    fn assert_migrations_table_query_uses_dialect_types() {
+        assert!(Postgres::assert_migrations_table_query("history").contains("version INT4,"));
+        assert_eq!(
+            Postgres::assert_migrations_table_query("history"),
+            Cockroach::assert_migrations_table_query("history")
+        );
+        assert!(Mysql::assert_migrations_table_query("history").contains("version INT,"));
+        assert!(Sqlite::assert_migrations_table_query("history").contains("version INTEGER,"));
+        let query = Mssql::assert_migrations_table_query("history");
+        assert!(query.starts_with("IF OBJECT_ID(N'history', N'U') IS NULL CREATE TABLE history("));
+    }
+
+    #[test]
+    fn upgrade_migrations_table_queries_create_the_table_again_after_dropping_it() {
+        let queries = Postgres::upgrade_migrations_table_queries("history");
+        assert_eq!(5, queries.len());
+        assert!(queries[0].starts_with("CREATE TABLE history_backup AS SELECT"));
+        assert_eq!("DROP TABLE history;", queries[1]);
+        assert_eq!(
+            Postgres::assert_migrations_table_query("history"),
+            queries[2]
+        );
+
+        let queries = Mssql::upgrade_migrations_table_queries("history");
+        assert!(queries[0].contains(" INTO history_backup FROM history;"));
+        assert_eq!(Mssql::assert_migrations_table_query("history"), queries[2]);
+    }
+
+    #[test]
+    fn mysql_upgrades_migrations_table_with_a_single_statement() {
+        let queries = Mysql::upgrade_migrations_table_queries("history");
+        assert_eq!(1, queries.len());
+        assert!(queries[0].starts_with("ALTER TABLE history ADD COLUMN namespace"));
+        assert!(queries[0].ends_with("ADD PRIMARY KEY (namespace, version);"));
+    }
+
+    #[test]
+    fn metadata_queries_look_up_the_current_schema() {
+        assert!(Postgres::namespace_column_query("history")
+            .contains("table_schema = current_schema() AND LOWER(table_name) = LOWER('history')"));
+        assert!(Mysql::backup_table_query("history")
+            .contains("table_schema = DATABASE() AND LOWER(table_name) = LOWER('history_backup')"));
+        assert!(Mssql::namespace_column_query("history").contains("table_schema = SCHEMA_NAME()"));
+        assert!(Sqlite::namespace_column_query("history").contains("pragma_table_info('history')"));
+    }
+
+    #[test]
+    fn only_cockroach_locks_the_migrations_table() {
+        for lock in &[
+            Postgres::lock_migrations_table_query("history", "runner"),
+            Mysql::lock_migrations_table_query("history", "runner"),
+            Sqlite::lock_migrations_table_query("history", "runner"),
+            Mssql::lock_migrations_table_query("history", "runner"),
+        ] {
+            assert!(lock.is_none());
+        }
+        assert!(Postgres::unlock_migrations_table_query("history", "runner").is_none());
+        assert!(Cockroach::lock_migrations_table_query("history", "runner").is_some());
+    }
+
+    #[test]
+    fn cockroach_locks_with_a_row_owned_by_the_runner() {
+        let lock = Cockroach::lock_migrations_table_query("history", "runner").unwrap();
+        assert!(lock.starts_with("CREATE TABLE IF NOT EXISTS history_lock (id INT4 PRIMARY KEY, owner VARCHAR(255) NOT NULL, locked_at TIMESTAMPTZ NOT NULL);"));
+        assert!(lock.contains("VALUES (1, 'runner', now()) ON CONFLICT (id) DO UPDATE"));
+        assert!(lock.ends_with("WHERE history_lock.locked_at < now() - INTERVAL '1 hour';"));
+        assert_eq!(
+            Some(
+                "SELECT COUNT(*) AS count FROM history_lock WHERE id = 1 AND owner = 'runner';"
+                    .to_string()
+            ),
+            Cockroach::held_lock_query("history", "runner")
+        );
+        assert_eq!(
+            Some("DELETE FROM history_lock WHERE id = 1 AND owner = 'runner';".to_string()),
+            Cockroach::unlock_migrations_table_query("history", "runner")
+        );
+    }
+
+    #[test]
+    fn only_cockroach_doesnt_support_grouped_migrations() {
+        assert!(!Cockroach::supports_grouped_migrations());
+        assert!(Postgres::supports_grouped_migrations());
+        assert!(Mysql::supports_grouped_migrations());
+        assert!(Sqlite::supports_grouped_migrations());
+        assert!(Mssql::supports_grouped_migrations());
+    }
+}
@@ -0,0 +1,44 @@
+use crate::traits::r#async::{AsyncExecutor, AsyncQuery, AsyncTransaction, AsyncTransactional};
+use crate::{AppliedMigration, Error};
+use async_trait::async_trait;
+use bb8::{ManageConnection, PooledConnection};
+
+// a pool checkout derefs to the connection of its manager, which is migrated by its own driver
+#[async_trait]
+impl<M> AsyncTransaction for PooledConnection<'_, M>
+where
+    M: ManageConnection,
+    M::Connection: AsyncTransaction + Send,
+{
+    type Error = <M::Connection as AsyncTransaction>::Error;
+    type Dialect = <M::Connection as AsyncTransaction>::Dialect;
+
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        AsyncTransaction::execute(&mut **self, queries).await
+    }
+}
+
+#[async_trait]
+impl<M> AsyncQuery<Vec<AppliedMigration>> for PooledConnection<'_, M>
+where
+    M: ManageConnection,
+    M::Connection: AsyncQuery<Vec<AppliedMigration>> + Send,
+{
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Option<Vec<AppliedMigration>>, <Self as AsyncTransaction>::Error> {
+        AsyncQuery::query(&mut **self, query).await
+    }
+}
+
+#[async_trait]
+impl<M> AsyncTransactional for PooledConnection<'_, M>
+where
+    M: ManageConnection,
+    M::Connection: AsyncTransactional + Send,
+{
+    async fn begin<'a>(&'a mut self) -> Result<Box<dyn AsyncExecutor + 'a>, Error> {
+        AsyncTransactional::begin(&mut **self).await
+    }
+}
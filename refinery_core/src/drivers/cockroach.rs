@@ -0,0 +1,127 @@
+use crate::dialect::{Cockroach, Postgres};
+use crate::traits::r#async::{AsyncExecutor, AsyncQuery, AsyncTransaction, AsyncTransactional};
+use crate::traits::sync::Driver;
+use crate::{AppliedMigration, Error, Migration};
+use async_trait::async_trait;
+use std::ops::{Deref, DerefMut};
+
+/// A connection of a Postgres driver to a CockroachDB database, migrated with [`dialect::Cockroach`].
+///
+/// CockroachDB speaks Postgres' protocol, the connection runs its queries as it would on Postgres,
+/// but migrations take a row on a lock table so that only one runner migrates the database at a time,
+/// and grouped runs fail.
+///
+/// ```rust,ignore
+/// let mut client = CockroachConnection::new(postgres::Client::connect(url, NoTls)?);
+/// embedded::migrations::runner().run(&mut client)?;
+/// ```
+///
+/// [`dialect::Cockroach`]: dialect/struct.Cockroach.html
+pub struct CockroachConnection<C>(C);
+
+impl<C> CockroachConnection<C> {
+    /// Wraps `connection`, a connection to a CockroachDB database
+    pub fn new(connection: C) -> CockroachConnection<C> {
+        CockroachConnection(connection)
+    }
+
+    /// Gives back the wrapped connection
+    pub fn into_inner(self) -> C {
+        self.0
+    }
+}
+
+impl<C> Deref for CockroachConnection<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.0
+    }
+}
+
+impl<C> DerefMut for CockroachConnection<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.0
+    }
+}
+
+impl<C: Driver<Dialect = Postgres>> Driver for CockroachConnection<C> {
+    type Error = C::Error;
+    type Dialect = Cockroach;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        self.0.begin()
+    }
+
+    fn commit(&mut self) -> Result<(), Self::Error> {
+        self.0.commit()
+    }
+
+    fn rollback(&mut self) -> Result<(), Self::Error> {
+        self.0.rollback()
+    }
+
+    fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        self.0.execute(queries)
+    }
+
+    fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        self.0.execute_no_tx(queries)
+    }
+
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Self::Error> {
+        self.0.query(query)
+    }
+
+    fn query_count(&mut self, query: &str) -> Result<Option<usize>, Self::Error> {
+        self.0.query_count(query)
+    }
+
+    fn query_history(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<AppliedMigration>, Self::Error> {
+        self.0.query_history(migration_table_name)
+    }
+
+    fn insert_history(
+        &mut self,
+        migration: &Migration,
+        migration_table_name: &str,
+    ) -> Result<(), Self::Error> {
+        self.0.insert_history(migration, migration_table_name)
+    }
+}
+
+#[async_trait]
+impl<C> AsyncTransaction for CockroachConnection<C>
+where
+    C: AsyncTransaction<Dialect = Postgres> + Send,
+{
+    type Error = C::Error;
+    type Dialect = Cockroach;
+
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        self.0.execute(queries).await
+    }
+}
+
+#[async_trait]
+impl<C> AsyncQuery<Vec<AppliedMigration>> for CockroachConnection<C>
+where
+    C: AsyncQuery<Vec<AppliedMigration>> + AsyncTransaction<Dialect = Postgres> + Send,
+{
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Option<Vec<AppliedMigration>>, <Self as AsyncTransaction>::Error> {
+        self.0.query(query).await
+    }
+}
+
+#[async_trait]
+impl<C: AsyncTransactional + Send> AsyncTransactional for CockroachConnection<C> {
+    async fn begin<'a>(&'a mut self) -> Result<Box<dyn AsyncExecutor + 'a>, Error> {
+        self.0.begin().await
+    }
+}
@@ -0,0 +1,38 @@
+use crate::dialect::Postgres;
+use crate::traits::r#async::{AsyncExecutor, AsyncQuery, AsyncTransaction, AsyncTransactional};
+use crate::{AppliedMigration, Error};
+use async_trait::async_trait;
+use deadpool_postgres::Client;
+use tokio_postgres::error::Error as PgError;
+use tokio_postgres::Client as PgClient;
+
+// a pool checkout derefs to the tokio-postgres client, which is migrated by its own driver
+#[async_trait]
+impl AsyncTransaction for Client {
+    type Error = PgError;
+    type Dialect = Postgres;
+
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        let client: &mut PgClient = &mut *self;
+        AsyncTransaction::execute(client, queries).await
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<AppliedMigration>> for Client {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Option<Vec<AppliedMigration>>, <Self as AsyncTransaction>::Error> {
+        let client: &mut PgClient = &mut *self;
+        AsyncQuery::query(client, query).await
+    }
+}
+
+#[async_trait]
+impl AsyncTransactional for Client {
+    async fn begin<'a>(&'a mut self) -> Result<Box<dyn AsyncExecutor + 'a>, Error> {
+        let client: &mut PgClient = &mut *self;
+        AsyncTransactional::begin(client).await
+    }
+}
@@ -0,0 +1,281 @@
+use crate::dialect::Dialect;
+use crate::traits::sync::Driver;
+use crate::AppliedMigration;
+use chrono::{DateTime, Local};
+use diesel::backend::Backend;
+use diesel::connection::{Connection, TransactionManager};
+use diesel::deserialize::{self, FromSql, QueryableByName};
+use diesel::result::Error as DError;
+use diesel::row::NamedRow;
+use diesel::sql_types::{BigInt, Integer, Text};
+
+// a row of the migrations table, diesel loads rows of raw queries by the name and type of their columns
+struct AppliedMigrationRow {
+    version: i32,
+    name: String,
+    applied_on: String,
+    checksum: String,
+    namespace: String,
+}
+
+impl<DB> QueryableByName<DB> for AppliedMigrationRow
+where
+    DB: Backend,
+    i32: FromSql<Integer, DB>,
+    String: FromSql<Text, DB>,
+{
+    fn build<R: NamedRow<DB>>(row: &R) -> deserialize::Result<Self> {
+        Ok(AppliedMigrationRow {
+            version: row.get::<Integer, _>("version")?,
+            name: row.get::<Text, _>("name")?,
+            applied_on: row.get::<Text, _>("applied_on")?,
+            checksum: row.get::<Text, _>("checksum")?,
+            namespace: row.get::<Text, _>("namespace")?,
+        })
+    }
+}
+
+impl From<AppliedMigrationRow> for AppliedMigration {
+    fn from(row: AppliedMigrationRow) -> AppliedMigration {
+        let applied_on = DateTime::parse_from_rfc3339(&row.applied_on)
+            .unwrap()
+            .with_timezone(&Local);
+        AppliedMigration {
+            version: row.version as usize,
+            name: row.name,
+            applied_on,
+            checksum: row.checksum,
+            namespace: row.namespace,
+        }
+    }
+}
+
+// the row of a `SELECT COUNT(*) AS count` query
+struct CountRow {
+    count: i64,
+}
+
+impl<DB> QueryableByName<DB> for CountRow
+where
+    DB: Backend,
+    i64: FromSql<BigInt, DB>,
+{
+    fn build<R: NamedRow<DB>>(row: &R) -> deserialize::Result<Self> {
+        Ok(CountRow {
+            count: row.get::<BigInt, _>("count")?,
+        })
+    }
+}
+
+// a row of any query with its columns as text, diesel loads rows of raw queries by the name of their columns only,
+// so each backend wraps the query into one whose columns it knows how to read
+struct TextRow(Vec<Option<String>>);
+
+trait TextRows: Backend {
+    fn text_rows_query(query: &str) -> String;
+
+    fn text_row<R: NamedRow<Self>>(row: &R) -> deserialize::Result<Vec<Option<String>>>;
+}
+
+impl<DB: TextRows> QueryableByName<DB> for TextRow {
+    fn build<R: NamedRow<DB>>(row: &R) -> deserialize::Result<Self> {
+        DB::text_row(row).map(TextRow)
+    }
+}
+
+// the query without the semicolon ending it, to be wrapped on a subquery
+fn subquery(query: &str) -> &str {
+    query.trim().trim_end_matches(';')
+}
+
+// the columns of the query are followed by one of a known name, whose index is the number of columns before it
+const TEXT_ROW_END_COLUMN: &str = "refinery_row_end";
+
+#[cfg(any(feature = "diesel-sqlite", feature = "diesel-mysql"))]
+fn text_rows_query_with_end_column(query: &str) -> String {
+    format!(
+        "SELECT q.*, 1 AS {} FROM ({}) AS q",
+        TEXT_ROW_END_COLUMN,
+        subquery(query)
+    )
+}
+
+#[cfg(any(feature = "diesel-sqlite", feature = "diesel-mysql"))]
+fn text_row_until_end_column<DB, R>(row: &R) -> deserialize::Result<Vec<Option<String>>>
+where
+    DB: Backend,
+    String: FromSql<Text, DB>,
+    R: NamedRow<DB>,
+{
+    let columns = row
+        .index_of(TEXT_ROW_END_COLUMN)
+        .ok_or("the query row has no end column")?;
+    (0..columns)
+        .map(|index| match row.get_raw_value(index) {
+            None => Ok(None),
+            value => String::from_sql(value).map(Some),
+        })
+        .collect()
+}
+
+// SQLite gives every value back as text when asked to
+#[cfg(feature = "diesel-sqlite")]
+impl TextRows for diesel::sqlite::Sqlite {
+    fn text_rows_query(query: &str) -> String {
+        text_rows_query_with_end_column(query)
+    }
+
+    fn text_row<R: NamedRow<Self>>(row: &R) -> deserialize::Result<Vec<Option<String>>> {
+        text_row_until_end_column(row)
+    }
+}
+
+// diesel loads MySQL values in their binary format, so columns that aren't text have to be cast to CHAR by the query
+#[cfg(feature = "diesel-mysql")]
+impl TextRows for diesel::mysql::Mysql {
+    fn text_rows_query(query: &str) -> String {
+        text_rows_query_with_end_column(query)
+    }
+
+    fn text_row<R: NamedRow<Self>>(row: &R) -> deserialize::Result<Vec<Option<String>>> {
+        text_row_until_end_column(row)
+    }
+}
+
+// diesel loads Postgres values in their binary format, so each row is cast to the text of its record instead,
+// i.e. `(1,car,)`, and split back into its columns
+#[cfg(feature = "diesel-postgres")]
+impl TextRows for diesel::pg::Pg {
+    fn text_rows_query(query: &str) -> String {
+        format!(
+            "SELECT CAST(q AS TEXT) AS {} FROM ({}) AS q",
+            TEXT_ROW_END_COLUMN,
+            subquery(query)
+        )
+    }
+
+    fn text_row<R: NamedRow<Self>>(row: &R) -> deserialize::Result<Vec<Option<String>>> {
+        let record = row.get::<Text, String>(TEXT_ROW_END_COLUMN)?;
+        Ok(record_columns(&record))
+    }
+}
+
+// the columns of a Postgres record text, unquoted empty columns are NULL,
+// quoted ones may have doubled or backslash escaped quotes and backslashes
+#[cfg(feature = "diesel-postgres")]
+fn record_columns(record: &str) -> Vec<Option<String>> {
+    let inner = record
+        .get(1..record.len().saturating_sub(1))
+        .unwrap_or_default();
+    let mut columns = Vec::new();
+    let mut chars = inner.chars().peekable();
+    loop {
+        let mut column = String::new();
+        let mut quoted = false;
+        let mut in_quotes = false;
+        let mut last = true;
+        while let Some(c) = chars.next() {
+            match c {
+                ',' if !in_quotes => {
+                    last = false;
+                    break;
+                }
+                '"' if in_quotes && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    column.push('"');
+                }
+                '"' => {
+                    quoted = true;
+                    in_quotes = !in_quotes;
+                }
+                '\\' if in_quotes => column.extend(chars.next()),
+                c => column.push(c),
+            }
+        }
+        columns.push(if column.is_empty() && !quoted {
+            None
+        } else {
+            Some(column)
+        });
+        if last {
+            break;
+        }
+    }
+    columns
+}
+
+fn execute<C: Connection>(connection: &C, queries: &[&str]) -> Result<usize, DError> {
+    let mut count = 0;
+    for query in queries.iter() {
+        connection.batch_execute(query)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+// transactions are begun and ended through the connection's transaction manager, which keeps count of them,
+// nesting them in savepoints when the connection is already on a transaction
+macro_rules! impl_driver {
+    ($connection:ty, $dialect:ty) => {
+        impl Driver for $connection {
+            type Error = DError;
+            type Dialect = $dialect;
+
+            fn begin(&mut self) -> Result<(), Self::Error> {
+                self.transaction_manager().begin_transaction(self)
+            }
+
+            fn commit(&mut self) -> Result<(), Self::Error> {
+                self.transaction_manager().commit_transaction(self)
+            }
+
+            fn rollback(&mut self) -> Result<(), Self::Error> {
+                self.transaction_manager().rollback_transaction(self)
+            }
+
+            fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+                execute(self, queries)
+            }
+
+            // statements outside of a transaction are committed on their own
+            fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+                execute(self, queries)
+            }
+
+            fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Self::Error> {
+                use diesel::RunQueryDsl;
+
+                let query = <Self as Connection>::Backend::text_rows_query(query);
+                let rows = diesel::sql_query(query).load::<TextRow>(self)?;
+                Ok(rows.into_iter().map(|row| row.0).collect())
+            }
+
+            fn query_count(&mut self, query: &str) -> Result<Option<usize>, Self::Error> {
+                use diesel::RunQueryDsl;
+
+                let rows = diesel::sql_query(query).load::<CountRow>(self)?;
+                Ok(rows.first().map(|row| row.count as usize))
+            }
+
+            fn query_history(
+                &mut self,
+                migration_table_name: &str,
+            ) -> Result<Vec<AppliedMigration>, Self::Error> {
+                use diesel::RunQueryDsl;
+
+                let query = <$dialect>::get_applied_migrations_query(migration_table_name);
+                let rows = diesel::sql_query(query).load::<AppliedMigrationRow>(self)?;
+                Ok(rows.into_iter().map(Into::into).collect())
+            }
+        }
+    };
+}
+
+#[cfg(feature = "diesel-postgres")]
+impl_driver!(diesel::PgConnection, crate::dialect::Postgres);
+
+#[cfg(feature = "diesel-mysql")]
+impl_driver!(diesel::MysqlConnection, crate::dialect::Mysql);
+
+#[cfg(feature = "diesel-sqlite")]
+impl_driver!(diesel::SqliteConnection, crate::dialect::Sqlite);
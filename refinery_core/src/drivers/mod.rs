@@ -1,6 +1,9 @@
 #[cfg(feature = "rusqlite")]
 pub mod rusqlite;
 
+#[cfg(all(feature = "rusqlite", feature = "tokio"))]
+pub mod tokio_rusqlite;
+
 #[cfg(feature = "tokio-postgres")]
 pub mod tokio_postgres;
 
@@ -12,3 +15,24 @@ pub mod postgres;
 
 #[cfg(feature = "mysql")]
 pub mod mysql;
+
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+pub mod cockroach;
+
+#[cfg(feature = "diesel")]
+pub mod diesel;
+
+#[cfg(feature = "sqlx")]
+pub mod sqlx;
+
+#[cfg(feature = "r2d2")]
+pub mod r2d2;
+
+#[cfg(feature = "bb8")]
+pub mod bb8;
+
+#[cfg(all(feature = "deadpool-postgres", feature = "tokio-postgres"))]
+pub mod deadpool_postgres;
+
+#[cfg(all(feature = "tiberius", feature = "futures"))]
+pub mod tiberius;
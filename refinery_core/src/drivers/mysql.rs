@@ -1,15 +1,22 @@
-use crate::traits::sync::{Query, Transaction};
+use crate::dialect::{Dialect, Mysql};
+use crate::traits::sync::Driver;
 use crate::AppliedMigration;
 use chrono::{DateTime, Local};
 use mysql::{
-    error::Error as MError, Conn, IsolationLevel, PooledConn, Transaction as MTransaction,
+    error::Error as MError, prelude::GenericConnection, Conn, PooledConn,
+    Transaction as MTransaction, Value,
 };
 
-fn query_applied_migrations(
-    transaction: &mut MTransaction,
+// the savepoint a transaction given by the caller nests refinery's transactions in
+const SAVEPOINT: &str = "refinery";
+// the error code of a savepoint that no longer exists
+const SAVEPOINT_DOES_NOT_EXIST: u16 = 1305;
+
+fn query_applied_migrations<C: GenericConnection>(
+    conn: &mut C,
     query: &str,
 ) -> Result<Vec<AppliedMigration>, MError> {
-    let rows = transaction.query(query)?;
+    let rows = conn.query(query)?;
     let mut applied = Vec::new();
     for row in rows {
         let row = row?;
@@ -23,60 +30,184 @@ fn query_applied_migrations(
             name: row.get(1).unwrap(),
             applied_on,
             checksum: row.get(3).unwrap(),
+            namespace: row.get(4).unwrap(),
         })
     }
     Ok(applied)
 }
 
-impl Transaction for Conn {
+fn execute<C: GenericConnection>(conn: &mut C, queries: &[&str]) -> Result<usize, MError> {
+    let mut count = 0;
+    for query in queries.iter() {
+        conn.query(query)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+// reads every column of the rows of a query as text, for the queries of migrations and callbacks
+fn query_rows<C: GenericConnection>(
+    conn: &mut C,
+    query: &str,
+) -> Result<Vec<Vec<Option<String>>>, MError> {
+    let result = conn.query(query)?;
+    let mut rows = Vec::new();
+    for row in result {
+        let values = row?
+            .unwrap()
+            .into_iter()
+            .map(|value| match value {
+                Value::NULL => None,
+                Value::Bytes(bytes) => Some(String::from_utf8_lossy(&bytes).into_owned()),
+                value => Some(value.as_sql(true)),
+            })
+            .collect();
+        rows.push(values);
+    }
+    Ok(rows)
+}
+
+// a mysql::Transaction borrows its connection, so the connection's transaction is begun and ended with plain statements
+impl Driver for Conn {
     type Error = MError;
+    type Dialect = Mysql;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        execute(
+            self,
+            &[
+                "SET TRANSACTION ISOLATION LEVEL REPEATABLE READ",
+                "START TRANSACTION WITH CONSISTENT SNAPSHOT",
+            ],
+        )
+        .map(|_| ())
+    }
+
+    fn commit(&mut self) -> Result<(), Self::Error> {
+        execute(self, &["COMMIT"]).map(|_| ())
+    }
+
+    fn rollback(&mut self) -> Result<(), Self::Error> {
+        execute(self, &["ROLLBACK"]).map(|_| ())
+    }
 
     fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
-        let mut transaction =
-            self.start_transaction(true, Some(IsolationLevel::RepeatableRead), None)?;
-        let mut count = 0;
-        for query in queries.iter() {
-            transaction.query(query)?;
-            count += 1;
-        }
-        transaction.commit()?;
-        Ok(count as usize)
+        execute(self, queries)
+    }
+
+    // statements outside of START TRANSACTION and COMMIT are committed on their own
+    fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        execute(self, queries)
+    }
+
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Self::Error> {
+        query_rows(self, query)
+    }
+
+    fn query_history(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<AppliedMigration>, Self::Error> {
+        query_applied_migrations(
+            self,
+            &Mysql::get_applied_migrations_query(migration_table_name),
+        )
     }
 }
 
-impl Transaction for PooledConn {
+impl Driver for PooledConn {
     type Error = MError;
+    type Dialect = Mysql;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        Driver::begin(self.as_mut())
+    }
+
+    fn commit(&mut self) -> Result<(), Self::Error> {
+        Driver::commit(self.as_mut())
+    }
+
+    fn rollback(&mut self) -> Result<(), Self::Error> {
+        Driver::rollback(self.as_mut())
+    }
 
     fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
-        let mut transaction =
-            self.start_transaction(true, Some(IsolationLevel::RepeatableRead), None)?;
-        let mut count = 0;
+        Driver::execute(self.as_mut(), queries)
+    }
 
-        for query in queries.iter() {
-            transaction.query(query)?;
-            count += 1;
-        }
-        transaction.commit()?;
-        Ok(count as usize)
+    fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        Driver::execute_no_tx(self.as_mut(), queries)
     }
-}
 
-impl Query<Vec<AppliedMigration>> for Conn {
-    fn query(&mut self, query: &str) -> Result<Option<Vec<AppliedMigration>>, Self::Error> {
-        let mut transaction =
-            self.start_transaction(true, Some(IsolationLevel::RepeatableRead), None)?;
-        let applied = query_applied_migrations(&mut transaction, query)?;
-        transaction.commit()?;
-        Ok(Some(applied))
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Self::Error> {
+        Driver::query(self.as_mut(), query)
+    }
+
+    fn query_history(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<AppliedMigration>, Self::Error> {
+        Driver::query_history(self.as_mut(), migration_table_name)
     }
 }
 
-impl Query<Vec<AppliedMigration>> for PooledConn {
-    fn query(&mut self, query: &str) -> Result<Option<Vec<AppliedMigration>>, Self::Error> {
-        let mut transaction =
-            self.start_transaction(true, Some(IsolationLevel::RepeatableRead), None)?;
-        let applied = query_applied_migrations(&mut transaction, query)?;
-        transaction.commit()?;
-        Ok(Some(applied))
+// migrations run on a transaction given by the caller are nested in a savepoint instead of being committed.
+// MySQL implicitly commits the transaction on DDL statements, creating the migrations table among them,
+// and the statements run after one aren't part of a transaction anymore,
+// so once a DDL statement ran nothing is undone if the caller rolls the transaction back
+impl Driver for MTransaction<'_> {
+    type Error = MError;
+    type Dialect = Mysql;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        execute(self, &[&format!("SAVEPOINT {}", SAVEPOINT)]).map(|_| ())
+    }
+
+    // a DDL statement run since the savepoint was set committed the transaction and released it along,
+    // the migration is applied but the caller's transaction can't undo it anymore
+    fn commit(&mut self) -> Result<(), Self::Error> {
+        match execute(self, &[&format!("RELEASE SAVEPOINT {}", SAVEPOINT)]) {
+            Err(MError::MySqlError(ref err)) if err.code == SAVEPOINT_DOES_NOT_EXIST => {
+                log::warn!(
+                    "a DDL statement implicitly committed the caller's transaction, rolling it back won't undo the migration"
+                );
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn rollback(&mut self) -> Result<(), Self::Error> {
+        execute(
+            self,
+            &[
+                &format!("ROLLBACK TO SAVEPOINT {}", SAVEPOINT),
+                &format!("RELEASE SAVEPOINT {}", SAVEPOINT),
+            ],
+        )
+        .map(|_| ())
+    }
+
+    fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        execute(self, queries)
+    }
+
+    // there's no way out of the caller's transaction, the queries are part of it
+    fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        execute(self, queries)
+    }
+
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Self::Error> {
+        query_rows(self, query)
+    }
+
+    fn query_history(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<AppliedMigration>, Self::Error> {
+        query_applied_migrations(
+            self,
+            &Mysql::get_applied_migrations_query(migration_table_name),
+        )
     }
 }
@@ -1,10 +1,14 @@
-use crate::traits::r#async::{AsyncQuery, AsyncTransaction};
-use crate::AppliedMigration;
+use crate::dialect::Mysql;
+use crate::error::WrapMigrationError;
+use crate::traits::r#async::{
+    AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction, AsyncTransactional,
+};
+use crate::{AppliedMigration, Error, Report, Runner};
 use async_trait::async_trait;
 use chrono::{DateTime, Local};
 use mysql_async::{
-    error::Error as MError, prelude::Queryable, Conn, IsolationLevel, Pool,
-    Transaction as MTransaction, TransactionOptions,
+    error::Error as MError, prelude::Queryable, Conn, IsolationLevel, Opts, Pool,
+    Transaction as MTransaction, TransactionOptions, Value,
 };
 
 async fn query_applied_migrations(
@@ -15,8 +19,13 @@ async fn query_applied_migrations(
 
     let (transaction, applied) = result
         .map_and_drop(|row| {
-            let (version, name, applied_on, checksum): (i64, String, String, String) =
-                mysql_async::from_row(row);
+            let (version, name, applied_on, checksum, namespace): (
+                i64,
+                String,
+                String,
+                String,
+                String,
+            ) = mysql_async::from_row(row);
 
             let applied_on = DateTime::parse_from_rfc3339(&applied_on)
                 .unwrap()
@@ -26,6 +35,7 @@ async fn query_applied_migrations(
                 name,
                 applied_on,
                 checksum,
+                namespace,
             }
         })
         .await?;
@@ -33,39 +43,296 @@ async fn query_applied_migrations(
     Ok((transaction, applied))
 }
 
+// where a connection is opened from, again when reconnecting after a failed query closed the previous one
+enum Source {
+    Opts(Opts),
+    Pool(Pool),
+}
+
+impl Source {
+    async fn connect(&self) -> Result<Conn, MError> {
+        match self {
+            Source::Opts(opts) => Conn::new(opts.clone()).await,
+            Source::Pool(pool) => pool.get_conn().await,
+        }
+    }
+}
+
+/// A [`mysql_async::Conn`] that can be migrated with [`Runner::run_async`], running the whole migration on its session,
+/// so that session variables and locks set by callbacks and migrations hold until the migration ends.
+///
+/// mysql_async takes connections by value on every query and only gives them back if the query succeeds,
+/// so a failed query closes the connection along with its session. The connection then returns `Err` instead of
+/// running on a new session without the state the migration relied on, until [`reconnect`] opens a new one
+/// from the options or pool it was opened from.
+///
+/// [`reconnect`]: #method.reconnect
+/// [`mysql_async::Conn`]: https://docs.rs/mysql_async/*/mysql_async/struct.Conn.html
+/// [`Runner::run_async`]: struct.Runner.html#method.run_async
+pub struct AsyncMysqlConnection {
+    conn: Option<Conn>,
+    source: Source,
+}
+
+impl AsyncMysqlConnection {
+    /// Opens a connection with the given options, i.e. an url
+    pub async fn new<T: Into<Opts>>(opts: T) -> Result<AsyncMysqlConnection, MError> {
+        let source = Source::Opts(opts.into());
+        let conn = source.connect().await?;
+        Ok(AsyncMysqlConnection {
+            conn: Some(conn),
+            source,
+        })
+    }
+
+    /// Checks a connection out of `pool`, returned to it when dropped
+    pub async fn from_pool(pool: &Pool) -> Result<AsyncMysqlConnection, MError> {
+        let source = Source::Pool(pool.clone());
+        let conn = source.connect().await?;
+        Ok(AsyncMysqlConnection {
+            conn: Some(conn),
+            source,
+        })
+    }
+
+    /// Gives back the connection, `None` if it was closed by the last query failing
+    pub fn into_inner(self) -> Option<Conn> {
+        self.conn
+    }
+
+    /// Opens a new connection, on a new session, if a failed query closed the previous one
+    pub async fn reconnect(&mut self) -> Result<(), MError> {
+        if self.conn.is_none() {
+            self.conn = Some(self.source.connect().await?);
+        }
+        Ok(())
+    }
+}
+
+// the connection is taken out of its Option for the transaction, and only put back once it ends successfully
+async fn start_transaction(conn: &mut Option<Conn>) -> Result<MTransaction<Conn>, Error> {
+    let conn = conn.take().ok_or_else(closed_connection_error)?;
+    let mut options = TransactionOptions::new();
+    options.set_isolation_level(Some(IsolationLevel::ReadCommitted));
+    conn.start_transaction(options)
+        .await
+        .migration_err("error starting transaction")
+}
+
+// the session's locks and variables were lost along with the connection,
+// so migrating on a new one could run without them
+fn closed_connection_error() -> Error {
+    Error::Connection(
+        "connection closed by a failed query".into(),
+        "the session was lost along with it, reconnect to migrate again".into(),
+    )
+}
+
+// mysql_async takes connections by value on every query, so they are migrated held in an Option,
+// which a failed query leaves empty, and querying it again returns an error instead of opening a new session
 #[async_trait]
-impl AsyncTransaction for Pool {
-    type Error = MError;
+impl AsyncTransaction for Option<Conn> {
+    type Error = Error;
+    type Dialect = Mysql;
 
     async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
-        let conn = self.get_conn().await?;
-        let mut options = TransactionOptions::new();
-        options.set_isolation_level(Some(IsolationLevel::ReadCommitted));
-
-        let mut transaction = conn.start_transaction(options).await?;
+        let mut transaction = start_transaction(self).await?;
         let mut count = 0;
         for query in queries {
-            transaction = transaction.query(query).await?.drop_result().await?;
+            transaction = transaction
+                .query(query)
+                .await
+                .migration_err("error executing queries")?
+                .drop_result()
+                .await
+                .migration_err("error executing queries")?;
             count += 1;
         }
-        transaction.commit().await?;
+        *self = Some(
+            transaction
+                .commit()
+                .await
+                .migration_err("error committing transaction")?,
+        );
         Ok(count as usize)
     }
 }
 
+#[async_trait]
+impl AsyncQuery<Vec<AppliedMigration>> for Option<Conn> {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Option<Vec<AppliedMigration>>, <Self as AsyncTransaction>::Error> {
+        let transaction = start_transaction(self).await?;
+        let (transaction, applied) = query_applied_migrations(transaction, query)
+            .await
+            .migration_err("error getting applied migrations")?;
+        *self = Some(
+            transaction
+                .commit()
+                .await
+                .migration_err("error committing transaction")?,
+        );
+        Ok(Some(applied))
+    }
+}
+
+#[async_trait]
+impl AsyncTransaction for AsyncMysqlConnection {
+    type Error = Error;
+    type Dialect = Mysql;
+
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        self.conn.execute(queries).await
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<AppliedMigration>> for AsyncMysqlConnection {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Option<Vec<AppliedMigration>>, <Self as AsyncTransaction>::Error> {
+        self.conn.query(query).await
+    }
+}
+
+// every query runs on a connection checked out of the pool,
+// while `AsyncMigrate` runs the whole migration on a single one
+#[async_trait]
+impl AsyncTransaction for Pool {
+    type Error = Error;
+    type Dialect = Mysql;
+
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        let mut conn = Some(
+            self.get_conn()
+                .await
+                .migration_err("error checking out a connection")?,
+        );
+        conn.execute(queries).await
+    }
+}
+
 #[async_trait]
 impl AsyncQuery<Vec<AppliedMigration>> for Pool {
     async fn query(
         &mut self,
         query: &str,
     ) -> Result<Option<Vec<AppliedMigration>>, <Self as AsyncTransaction>::Error> {
-        let conn = self.get_conn().await?;
-        let mut options = TransactionOptions::new();
-        options.set_isolation_level(Some(IsolationLevel::ReadCommitted));
-        let transaction = conn.start_transaction(options).await?;
+        let mut conn = Some(
+            self.get_conn()
+                .await
+                .migration_err("error checking out a connection")?,
+        );
+        conn.query(query).await
+    }
+}
 
-        let (transaction, applied) = query_applied_migrations(transaction, query).await?;
-        transaction.commit().await?;
-        Ok(Some(applied))
+// mysql_async transactions are taken by value on every query and given back on success,
+// so the transaction is no longer usable after a query fails, even if the migration carries on after the error.
+// Ending it gives the connection back to the Option it was taken out of
+struct ConnTransaction<'a> {
+    transaction: Option<MTransaction<Conn>>,
+    conn: &'a mut Option<Conn>,
+}
+
+impl ConnTransaction<'_> {
+    fn take(&mut self) -> Result<MTransaction<Conn>, Error> {
+        self.transaction.take().ok_or_else(closed_connection_error)
+    }
+}
+
+#[async_trait]
+impl AsyncExecutor for ConnTransaction<'_> {
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Error> {
+        let mut count = 0;
+        for query in queries {
+            let transaction = self
+                .take()?
+                .query(query)
+                .await
+                .migration_err("error executing queries")?
+                .drop_result()
+                .await
+                .migration_err("error executing queries")?;
+            self.transaction = Some(transaction);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    async fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Error> {
+        let (transaction, rows) = self
+            .take()?
+            .query(query)
+            .await
+            .migration_err("error running query")?
+            .map_and_drop(|row| {
+                row.unwrap()
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::NULL => None,
+                        Value::Bytes(bytes) => Some(String::from_utf8_lossy(&bytes).into_owned()),
+                        value => Some(value.as_sql(true)),
+                    })
+                    .collect()
+            })
+            .await
+            .migration_err("error running query")?;
+        self.transaction = Some(transaction);
+        Ok(rows)
+    }
+
+    async fn commit(mut self: Box<Self>) -> Result<(), Error> {
+        let conn = self
+            .take()?
+            .commit()
+            .await
+            .migration_err("error committing transaction")?;
+        *self.conn = Some(conn);
+        Ok(())
+    }
+
+    // a transaction a query failed on was dropped along with its connection
+    async fn rollback(mut self: Box<Self>) -> Result<(), Error> {
+        if let Some(transaction) = self.transaction.take() {
+            let conn = transaction
+                .rollback()
+                .await
+                .migration_err("error rolling back transaction")?;
+            *self.conn = Some(conn);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsyncTransactional for Option<Conn> {
+    async fn begin<'a>(&'a mut self) -> Result<Box<dyn AsyncExecutor + 'a>, Error> {
+        let transaction = start_transaction(self).await?;
+        Ok(Box::new(ConnTransaction {
+            transaction: Some(transaction),
+            conn: self,
+        }))
+    }
+}
+
+#[async_trait]
+impl AsyncTransactional for AsyncMysqlConnection {
+    async fn begin<'a>(&'a mut self) -> Result<Box<dyn AsyncExecutor + 'a>, Error> {
+        self.conn.begin().await
+    }
+}
+
+// the whole migration runs on a single connection checked out of the pool
+#[async_trait]
+impl AsyncMigrate for Pool {
+    async fn migrate_with_runner(&mut self, runner: &Runner) -> Result<Report, Error> {
+        let mut connection = AsyncMysqlConnection::from_pool(self)
+            .await
+            .migration_err("error checking out a connection")?;
+        connection.migrate_with_runner(runner).await
     }
 }
@@ -1,13 +1,20 @@
-use crate::traits::sync::{Query, Transaction};
-use crate::AppliedMigration;
+use crate::dialect::{Dialect, Postgres};
+use crate::traits::sync::Driver;
+use crate::{AppliedMigration, Migration};
 use chrono::{DateTime, Local};
-use postgres::{Client as PgClient, Error as PgError, Transaction as PgTransaction};
+use postgres::{
+    Client as PgClient, Error as PgError, GenericClient, SimpleQueryMessage,
+    Transaction as PgTransaction,
+};
 
-fn query_applied_migrations(
-    transaction: &mut PgTransaction,
+// the savepoint a transaction given by the caller nests refinery's transactions in
+const SAVEPOINT: &str = "refinery";
+
+fn query_applied_migrations<C: GenericClient>(
+    client: &mut C,
     query: &str,
 ) -> Result<Vec<AppliedMigration>, PgError> {
-    let rows = transaction.query(query, &[])?;
+    let rows = client.query(query, &[])?;
     let mut applied = Vec::new();
     for row in rows.into_iter() {
         let version: i32 = row.get(0);
@@ -21,31 +28,156 @@ fn query_applied_migrations(
             name: row.get(1),
             applied_on,
             checksum: row.get(3),
+            namespace: row.get(4),
         });
     }
     Ok(applied)
 }
 
-impl Transaction for PgClient {
+fn execute<C: GenericClient>(client: &mut C, queries: &[&str]) -> Result<usize, PgError> {
+    let mut count = 0;
+    for query in queries.iter() {
+        client.simple_query(query)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn query<C: GenericClient>(
+    client: &mut C,
+    query: &str,
+) -> Result<Vec<Vec<Option<String>>>, PgError> {
+    let messages = client.simple_query(query)?;
+    let mut rows = Vec::new();
+    for message in messages {
+        if let SimpleQueryMessage::Row(row) = message {
+            rows.push((0..row.len()).map(|i| row.get(i).map(Into::into)).collect());
+        }
+    }
+    Ok(rows)
+}
+
+fn insert_migration<C: GenericClient>(
+    client: &mut C,
+    migration: &Migration,
+    migration_table_name: &str,
+) -> Result<(), PgError> {
+    let query = format!(
+        "INSERT INTO {} (version, name, applied_on, checksum, namespace) VALUES ($1, $2, $3, $4, $5)",
+        migration_table_name
+    );
+    client
+        .execute(
+            query.as_str(),
+            &[
+                &(migration.version as i32),
+                &migration.name,
+                &Local::now().to_rfc3339(),
+                &migration.checksum().to_string(),
+                &migration.namespace(),
+            ],
+        )
+        .map(|_| ())
+}
+
+// the client's transaction is begun and ended with plain statements, a postgres::Transaction borrows its client
+impl Driver for PgClient {
     type Error = PgError;
+    type Dialect = Postgres;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        self.batch_execute("BEGIN")
+    }
+
+    fn commit(&mut self) -> Result<(), Self::Error> {
+        self.batch_execute("COMMIT")
+    }
+
+    fn rollback(&mut self) -> Result<(), Self::Error> {
+        self.batch_execute("ROLLBACK")
+    }
 
     fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
-        let mut transaction = PgClient::transaction(self)?;
-        let mut count = 0;
-        for query in queries.iter() {
-            PgTransaction::batch_execute(&mut transaction, query)?;
-            count += 1;
-        }
-        transaction.commit()?;
-        Ok(count as usize)
+        execute(self, queries)
+    }
+
+    // statements outside of BEGIN and COMMIT are committed on their own
+    fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        execute(self, queries)
+    }
+
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Self::Error> {
+        self::query(self, query)
+    }
+
+    fn query_history(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<AppliedMigration>, Self::Error> {
+        query_applied_migrations(
+            self,
+            &Postgres::get_applied_migrations_query(migration_table_name),
+        )
+    }
+
+    fn insert_history(
+        &mut self,
+        migration: &Migration,
+        migration_table_name: &str,
+    ) -> Result<(), Self::Error> {
+        insert_migration(self, migration, migration_table_name)
     }
 }
 
-impl Query<Vec<AppliedMigration>> for PgClient {
-    fn query(&mut self, query: &str) -> Result<Option<Vec<AppliedMigration>>, Self::Error> {
-        let mut transaction = PgClient::transaction(self)?;
-        let applied = query_applied_migrations(&mut transaction, query)?;
-        transaction.commit()?;
-        Ok(Some(applied))
+// migrations run on a transaction given by the caller are nested in a savepoint instead of being committed,
+// and are undone if the caller rolls it back
+impl Driver for PgTransaction<'_> {
+    type Error = PgError;
+    type Dialect = Postgres;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        self.batch_execute(&format!("SAVEPOINT {}", SAVEPOINT))
+    }
+
+    fn commit(&mut self) -> Result<(), Self::Error> {
+        self.batch_execute(&format!("RELEASE SAVEPOINT {}", SAVEPOINT))
+    }
+
+    fn rollback(&mut self) -> Result<(), Self::Error> {
+        self.batch_execute(&format!(
+            "ROLLBACK TO SAVEPOINT {0}; RELEASE SAVEPOINT {0}",
+            SAVEPOINT
+        ))
+    }
+
+    fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        execute(self, queries)
+    }
+
+    // there's no way out of the caller's transaction, the queries are part of it
+    fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        execute(self, queries)
+    }
+
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Self::Error> {
+        self::query(self, query)
+    }
+
+    fn query_history(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<AppliedMigration>, Self::Error> {
+        query_applied_migrations(
+            self,
+            &Postgres::get_applied_migrations_query(migration_table_name),
+        )
+    }
+
+    fn insert_history(
+        &mut self,
+        migration: &Migration,
+        migration_table_name: &str,
+    ) -> Result<(), Self::Error> {
+        insert_migration(self, migration, migration_table_name)
     }
 }
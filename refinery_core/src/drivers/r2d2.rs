@@ -0,0 +1,56 @@
+use crate::traits::sync::Driver;
+use crate::{AppliedMigration, Migration};
+use r2d2::{ManageConnection, PooledConnection};
+
+// a pool checkout derefs to the connection of its manager, which is migrated by its own driver
+impl<M> Driver for PooledConnection<M>
+where
+    M: ManageConnection,
+    M::Connection: Driver,
+{
+    type Error = <M::Connection as Driver>::Error;
+    type Dialect = <M::Connection as Driver>::Dialect;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        Driver::begin(&mut **self)
+    }
+
+    fn commit(&mut self) -> Result<(), Self::Error> {
+        Driver::commit(&mut **self)
+    }
+
+    fn rollback(&mut self) -> Result<(), Self::Error> {
+        Driver::rollback(&mut **self)
+    }
+
+    fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        Driver::execute(&mut **self, queries)
+    }
+
+    fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        Driver::execute_no_tx(&mut **self, queries)
+    }
+
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Self::Error> {
+        Driver::query(&mut **self, query)
+    }
+
+    fn query_count(&mut self, query: &str) -> Result<Option<usize>, Self::Error> {
+        Driver::query_count(&mut **self, query)
+    }
+
+    fn query_history(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<AppliedMigration>, Self::Error> {
+        Driver::query_history(&mut **self, migration_table_name)
+    }
+
+    fn insert_history(
+        &mut self,
+        migration: &Migration,
+        migration_table_name: &str,
+    ) -> Result<(), Self::Error> {
+        Driver::insert_history(&mut **self, migration, migration_table_name)
+    }
+}
@@ -1,14 +1,21 @@
-use crate::traits::sync::{Query, Transaction};
+use crate::dialect::{Dialect, Sqlite};
+use crate::traits::sync::Driver;
 use crate::AppliedMigration;
 use chrono::{DateTime, Local};
-use rusqlite::{Connection as RqlConnection, Error as RqlError, NO_PARAMS};
+use rusqlite::types::Value;
+use rusqlite::{
+    params, Connection as RqlConnection, Error as RqlError, Transaction as RqlTransaction,
+};
 
-fn query_applied_migrations(
-    transaction: &RqlConnection,
+// the savepoint a transaction given by the caller nests refinery's transactions in
+const SAVEPOINT: &str = "refinery";
+
+pub(crate) fn query_applied_migrations(
+    connection: &RqlConnection,
     query: &str,
 ) -> Result<Vec<AppliedMigration>, RqlError> {
-    let mut stmt = transaction.prepare(query)?;
-    let mut rows = stmt.query(NO_PARAMS)?;
+    let mut stmt = connection.prepare(query)?;
+    let mut rows = stmt.query(params![])?;
     let mut applied = Vec::new();
     while let Some(row) = rows.next()? {
         let version: isize = row.get(0)?;
@@ -16,36 +23,135 @@ fn query_applied_migrations(
         let applied_on = DateTime::parse_from_rfc3339(&applied_on)
             .unwrap()
             .with_timezone(&Local);
-        //version, name, installed_on, checksum
+        //version, name, installed_on, checksum, namespace
         applied.push(AppliedMigration {
             version: version as usize,
             name: row.get(1)?,
             applied_on,
             checksum: row.get(3)?,
+            namespace: row.get(4)?,
         });
     }
     Ok(applied)
 }
 
-impl Transaction for RqlConnection {
+// reads every column of the rows of a query as text, for the queries of migrations and callbacks
+pub(crate) fn query_rows(
+    connection: &RqlConnection,
+    query: &str,
+) -> Result<Vec<Vec<Option<String>>>, RqlError> {
+    let mut stmt = connection.prepare(query)?;
+    let columns = stmt.column_count();
+    let mut rows = stmt.query(params![])?;
+    let mut result = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut values = Vec::new();
+        for i in 0..columns {
+            let value = match row.get(i)? {
+                Value::Null => None,
+                Value::Integer(value) => Some(value.to_string()),
+                Value::Real(value) => Some(value.to_string()),
+                Value::Text(value) => Some(value),
+                Value::Blob(value) => Some(String::from_utf8_lossy(&value).into_owned()),
+            };
+            values.push(value);
+        }
+        result.push(values);
+    }
+    Ok(result)
+}
+
+fn execute(connection: &RqlConnection, queries: &[&str]) -> Result<usize, RqlError> {
+    let mut count = 0;
+    for query in queries.iter() {
+        connection.execute_batch(query)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+// rusqlite transactions borrow their connection, so the connection's transaction is begun and ended with plain statements
+impl Driver for RqlConnection {
     type Error = RqlError;
+    type Dialect = Sqlite;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        self.execute_batch("BEGIN")
+    }
+
+    fn commit(&mut self) -> Result<(), Self::Error> {
+        self.execute_batch("COMMIT")
+    }
+
+    fn rollback(&mut self) -> Result<(), Self::Error> {
+        self.execute_batch("ROLLBACK")
+    }
+
     fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
-        let transaction = self.transaction()?;
-        let mut count = 0;
-        for query in queries.iter() {
-            transaction.execute_batch(query)?;
-            count += 1;
-        }
-        transaction.commit()?;
-        Ok(count)
+        execute(self, queries)
+    }
+
+    // statements outside of BEGIN and COMMIT are committed on their own
+    fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        execute(self, queries)
+    }
+
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Self::Error> {
+        query_rows(self, query)
+    }
+
+    fn query_history(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<AppliedMigration>, Self::Error> {
+        query_applied_migrations(
+            self,
+            &Sqlite::get_applied_migrations_query(migration_table_name),
+        )
     }
 }
 
-impl Query<Vec<AppliedMigration>> for RqlConnection {
-    fn query(&mut self, query: &str) -> Result<Option<Vec<AppliedMigration>>, Self::Error> {
-        let transaction = self.transaction()?;
-        let applied = query_applied_migrations(&transaction, query)?;
-        transaction.commit()?;
-        Ok(Some(applied))
+// migrations run on a transaction given by the caller are nested in a savepoint instead of being committed,
+// and are undone if the caller rolls it back
+impl Driver for RqlTransaction<'_> {
+    type Error = RqlError;
+    type Dialect = Sqlite;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        self.execute_batch(&format!("SAVEPOINT {}", SAVEPOINT))
+    }
+
+    fn commit(&mut self) -> Result<(), Self::Error> {
+        self.execute_batch(&format!("RELEASE SAVEPOINT {}", SAVEPOINT))
+    }
+
+    fn rollback(&mut self) -> Result<(), Self::Error> {
+        self.execute_batch(&format!(
+            "ROLLBACK TO SAVEPOINT {0}; RELEASE SAVEPOINT {0}",
+            SAVEPOINT
+        ))
+    }
+
+    fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        execute(self, queries)
+    }
+
+    // there's no way out of the caller's transaction, the queries are part of it
+    fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        execute(self, queries)
+    }
+
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Self::Error> {
+        query_rows(self, query)
+    }
+
+    fn query_history(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<AppliedMigration>, Self::Error> {
+        query_applied_migrations(
+            self,
+            &Sqlite::get_applied_migrations_query(migration_table_name),
+        )
     }
 }
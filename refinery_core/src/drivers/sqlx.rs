@@ -0,0 +1,144 @@
+use crate::error::WrapMigrationError;
+use crate::traits::r#async::{
+    AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction, AsyncTransactional,
+};
+use crate::{AppliedMigration, Error, Report, Runner};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use sqlx::Error as SqlxError;
+use sqlx::Row;
+
+fn applied_migration(
+    (version, name, applied_on, checksum, namespace): (i32, String, String, String, String),
+) -> AppliedMigration {
+    let applied_on = DateTime::parse_from_rfc3339(&applied_on)
+        .unwrap()
+        .with_timezone(&Local);
+    AppliedMigration {
+        version: version as usize,
+        name,
+        applied_on,
+        checksum,
+        namespace,
+    }
+}
+
+// sqlx executors are implemented per database, so connections, pools and transactions
+// of each database get their own impls
+macro_rules! impl_migrate {
+    ($db:ty, $dialect:ty, $connection:ty, $pool:ty) => {
+        #[async_trait]
+        impl AsyncTransaction for $connection {
+            type Error = SqlxError;
+            type Dialect = $dialect;
+
+            async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+                let mut transaction = sqlx::Connection::begin(self).await?;
+                let mut count = 0;
+                for query in queries {
+                    sqlx::Executor::execute(&mut transaction, *query).await?;
+                    count += 1;
+                }
+                transaction.commit().await?;
+                Ok(count)
+            }
+        }
+
+        #[async_trait]
+        impl AsyncQuery<Vec<AppliedMigration>> for $connection {
+            async fn query(
+                &mut self,
+                query: &str,
+            ) -> Result<Option<Vec<AppliedMigration>>, <Self as AsyncTransaction>::Error> {
+                let mut transaction = sqlx::Connection::begin(self).await?;
+                let rows = sqlx::query_as::<_, (i32, String, String, String, String)>(query)
+                    .fetch_all(&mut transaction)
+                    .await?;
+                transaction.commit().await?;
+                Ok(Some(rows.into_iter().map(applied_migration).collect()))
+            }
+        }
+
+        #[async_trait]
+        impl AsyncTransactional for $connection {
+            async fn begin<'a>(&'a mut self) -> Result<Box<dyn AsyncExecutor + 'a>, Error> {
+                let transaction = sqlx::Connection::begin(self)
+                    .await
+                    .migration_err("error starting transaction")?;
+                Ok(Box::new(transaction))
+            }
+        }
+
+        // the whole migration runs on a single connection checked out of the pool, so that the lock
+        // on the migrations table is held by the session running the migrations
+        #[async_trait]
+        impl AsyncMigrate for $pool {
+            async fn migrate_with_runner(&mut self, runner: &Runner) -> Result<Report, Error> {
+                let mut connection = self
+                    .acquire()
+                    .await
+                    .migration_err("error checking out a connection")?;
+                AsyncMigrate::migrate_with_runner(&mut *connection, runner).await
+            }
+        }
+
+        #[async_trait]
+        impl AsyncExecutor for sqlx::Transaction<'_, $db> {
+            async fn execute(&mut self, queries: &[&str]) -> Result<usize, Error> {
+                let mut count = 0;
+                for query in queries {
+                    sqlx::Executor::execute(&mut *self, *query)
+                        .await
+                        .migration_err("error executing queries")?;
+                    count += 1;
+                }
+                Ok(count)
+            }
+
+            async fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Error> {
+                // unprepared queries return their rows as text, so every column decodes to a string
+                let rows = sqlx::Executor::fetch_all(&mut *self, query)
+                    .await
+                    .migration_err("error running query")?;
+                rows.iter()
+                    .map(|row| {
+                        (0..row.len())
+                            .map(|i| row.try_get_unchecked::<Option<String>, _>(i))
+                            .collect::<Result<_, _>>()
+                    })
+                    .collect::<Result<_, _>>()
+                    .migration_err("error running query")
+            }
+
+            async fn commit(self: Box<Self>) -> Result<(), Error> {
+                sqlx::Transaction::commit(*self)
+                    .await
+                    .migration_err("error committing transaction")
+            }
+        }
+    };
+}
+
+#[cfg(feature = "sqlx-postgres")]
+impl_migrate!(
+    sqlx::Postgres,
+    crate::dialect::Postgres,
+    sqlx::PgConnection,
+    sqlx::PgPool
+);
+
+#[cfg(feature = "sqlx-mysql")]
+impl_migrate!(
+    sqlx::MySql,
+    crate::dialect::Mysql,
+    sqlx::MySqlConnection,
+    sqlx::MySqlPool
+);
+
+#[cfg(feature = "sqlx-sqlite")]
+impl_migrate!(
+    sqlx::Sqlite,
+    crate::dialect::Sqlite,
+    sqlx::SqliteConnection,
+    sqlx::SqlitePool
+);
@@ -0,0 +1,256 @@
+use crate::dialect::Mssql;
+use crate::error::WrapMigrationError;
+use crate::traits::r#async::{AsyncExecutor, AsyncQuery, AsyncTransaction, AsyncTransactional};
+use crate::{AppliedMigration, Error};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use futures::io::{AsyncRead, AsyncWrite};
+use tiberius::error::Error as TError;
+use tiberius::{Client, ColumnData, FromSql, Row};
+
+// splits a script into the batches separated by GO lines, which are understood by SQL Server tools but not the server
+fn split_batches(script: &str) -> Vec<String> {
+    let mut batches = Vec::new();
+    let mut batch = String::new();
+    for line in script.lines() {
+        if line.trim().eq_ignore_ascii_case("go") {
+            batches.push(std::mem::take(&mut batch));
+        } else {
+            batch.push_str(line);
+            batch.push('\n');
+        }
+    }
+    batches.push(batch);
+    batches
+        .into_iter()
+        .filter(|batch| !batch.trim().is_empty())
+        .collect()
+}
+
+async fn simple_query<S>(client: &mut Client<S>, query: &str) -> Result<Vec<Vec<Row>>, TError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    client.simple_query(query).await?.into_results().await
+}
+
+async fn execute_batches<S>(client: &mut Client<S>, queries: &[&str]) -> Result<usize, TError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let mut count = 0;
+    for query in queries {
+        for batch in split_batches(query) {
+            simple_query(client, &batch).await?;
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+// rolls back a transaction left open by a TiberiusTransaction dropped without being ended before beginning a new one
+const BEGIN_TRAN: &str = "IF @@TRANCOUNT > 0 ROLLBACK TRAN;\nBEGIN TRAN";
+
+// a failed statement may have already ended the transaction
+async fn rollback<S>(client: &mut Client<S>) -> Result<(), TError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    simple_query(client, "IF @@TRANCOUNT > 0 ROLLBACK TRAN")
+        .await
+        .map(|_| ())
+}
+
+fn column_text(data: ColumnData<'static>) -> Result<Option<String>, Error> {
+    let value = match data {
+        ColumnData::U8(value) => value.map(|value| value.to_string()),
+        ColumnData::I16(value) => value.map(|value| value.to_string()),
+        ColumnData::I32(value) => value.map(|value| value.to_string()),
+        ColumnData::I64(value) => value.map(|value| value.to_string()),
+        ColumnData::F32(value) => value.map(|value| value.to_string()),
+        ColumnData::F64(value) => value.map(|value| value.to_string()),
+        ColumnData::Bit(value) => value.map(|value| value.to_string()),
+        ColumnData::String(value) => value.map(|value| value.into_owned()),
+        ColumnData::Guid(value) => value.map(|value| value.to_string()),
+        ColumnData::Numeric(value) => value.map(|value| value.to_string()),
+        ColumnData::Binary(value) => {
+            value.map(|value| String::from_utf8_lossy(&value).into_owned())
+        }
+        _ => {
+            return Err(Error::Connection(
+                "error running query".into(),
+                "column type can't be read as text, CONVERT it to NVARCHAR on the query".into(),
+            ))
+        }
+    };
+    Ok(value)
+}
+
+// reads a column of the migrations table, whose columns are never NULL
+fn not_null<'a, T: FromSql<'a>>(row: &'a Row, index: usize) -> Result<T, TError> {
+    row.try_get(index)?.ok_or_else(|| {
+        TError::Conversion(format!("column {} of the migrations table is NULL", index).into())
+    })
+}
+
+#[async_trait]
+impl<S> AsyncTransaction for Client<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    type Error = TError;
+    type Dialect = Mssql;
+
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        simple_query(self, BEGIN_TRAN).await?;
+        match execute_batches(self, queries).await {
+            Ok(count) => {
+                simple_query(self, "COMMIT TRAN").await?;
+                Ok(count)
+            }
+            Err(err) => {
+                if let Err(rollback_err) = rollback(self).await {
+                    log::error!("error rolling back transaction: {}", rollback_err);
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S> AsyncQuery<Vec<AppliedMigration>> for Client<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Option<Vec<AppliedMigration>>, <Self as AsyncTransaction>::Error> {
+        let rows = self.simple_query(query).await?.into_first_result().await?;
+        let mut applied = Vec::new();
+        for row in rows.into_iter() {
+            let version: i32 = not_null(&row, 0)?;
+            let applied_on: &str = not_null(&row, 2)?;
+            let applied_on = DateTime::parse_from_rfc3339(applied_on)
+                .map_err(|err| {
+                    TError::Conversion(format!("invalid applied_on {}: {}", applied_on, err).into())
+                })?
+                .with_timezone(&Local);
+            let name: &str = not_null(&row, 1)?;
+            let checksum: &str = not_null(&row, 3)?;
+            let namespace: &str = not_null(&row, 4)?;
+
+            applied.push(AppliedMigration {
+                version: version as usize,
+                name: name.into(),
+                applied_on,
+                checksum: checksum.into(),
+                namespace: namespace.into(),
+            });
+        }
+        Ok(Some(applied))
+    }
+}
+
+// tiberius has no transaction type, its statements are sent on the client. Drop can't send the rollback
+// of a transaction dropped without being committed or rolled back, it's sent before the next transaction begins
+struct TiberiusTransaction<'a, S: AsyncRead + AsyncWrite + Unpin + Send> {
+    client: &'a mut Client<S>,
+    ended: bool,
+}
+
+#[async_trait]
+impl<S> AsyncExecutor for TiberiusTransaction<'_, S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Error> {
+        execute_batches(self.client, queries)
+            .await
+            .migration_err("error executing queries")
+    }
+
+    async fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Error> {
+        let rows = self
+            .client
+            .simple_query(query)
+            .await
+            .migration_err("error running query")?
+            .into_first_result()
+            .await
+            .migration_err("error running query")?;
+        rows.into_iter()
+            .map(|row| row.into_iter().map(column_text).collect())
+            .collect()
+    }
+
+    async fn commit(mut self: Box<Self>) -> Result<(), Error> {
+        self.ended = true;
+        simple_query(self.client, "COMMIT TRAN")
+            .await
+            .map(|_| ())
+            .migration_err("error committing transaction")
+    }
+
+    async fn rollback(mut self: Box<Self>) -> Result<(), Error> {
+        self.ended = true;
+        rollback(self.client)
+            .await
+            .migration_err("error rolling back transaction")
+    }
+}
+
+impl<S> Drop for TiberiusTransaction<'_, S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    fn drop(&mut self) {
+        if !self.ended {
+            log::warn!("transaction dropped without being ended, it's rolled back when the next one begins");
+        }
+    }
+}
+
+#[async_trait]
+impl<S> AsyncTransactional for Client<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    async fn begin<'a>(&'a mut self) -> Result<Box<dyn AsyncExecutor + 'a>, Error> {
+        simple_query(self, BEGIN_TRAN)
+            .await
+            .migration_err("error starting transaction")?;
+        Ok(Box::new(TiberiusTransaction {
+            client: self,
+            ended: false,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_batches;
+
+    #[test]
+    fn splits_batches_on_go_lines() {
+        let script = "CREATE TABLE cars (id int);\nGO\n\
+                      CREATE VIEW cars_view AS SELECT id FROM cars;\n  go  \nGO\n";
+        assert_eq!(
+            vec![
+                "CREATE TABLE cars (id int);\n".to_string(),
+                "CREATE VIEW cars_view AS SELECT id FROM cars;\n".to_string(),
+            ],
+            split_batches(script)
+        );
+    }
+
+    #[test]
+    fn doesnt_split_on_go_inside_lines() {
+        let script = "INSERT INTO cars (name) VALUES ('GO');\nSELECT 1 AS go;";
+        assert_eq!(
+            vec!["INSERT INTO cars (name) VALUES ('GO');\nSELECT 1 AS go;\n".to_string()],
+            split_batches(script)
+        );
+    }
+}
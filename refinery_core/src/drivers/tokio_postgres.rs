@@ -1,9 +1,11 @@
-use crate::traits::r#async::{AsyncQuery, AsyncTransaction};
-use crate::AppliedMigration;
+use crate::dialect::Postgres;
+use crate::error::WrapMigrationError;
+use crate::traits::r#async::{AsyncExecutor, AsyncQuery, AsyncTransaction, AsyncTransactional};
+use crate::{AppliedMigration, Error};
 use async_trait::async_trait;
 use chrono::{DateTime, Local};
 use tokio_postgres::error::Error as PgError;
-use tokio_postgres::{Client, Transaction as PgTransaction};
+use tokio_postgres::{Client, SimpleQueryMessage, Transaction as PgTransaction};
 
 async fn query_applied_migrations(
     transaction: &PgTransaction<'_>,
@@ -23,6 +25,7 @@ async fn query_applied_migrations(
             name: row.get(1),
             applied_on,
             checksum: row.get(3),
+            namespace: row.get(4),
         });
     }
     Ok(applied)
@@ -31,6 +34,7 @@ async fn query_applied_migrations(
 #[async_trait]
 impl AsyncTransaction for Client {
     type Error = PgError;
+    type Dialect = Postgres;
 
     async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
         let transaction = self.transaction().await?;
@@ -56,3 +60,89 @@ impl AsyncQuery<Vec<AppliedMigration>> for Client {
         Ok(Some(applied))
     }
 }
+
+#[async_trait]
+impl AsyncExecutor for PgTransaction<'_> {
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Error> {
+        let mut count = 0;
+        for query in queries {
+            self.batch_execute(query)
+                .await
+                .migration_err("error executing queries")?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    async fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Error> {
+        let messages = self
+            .simple_query(query)
+            .await
+            .migration_err("error running query")?;
+        let mut rows = Vec::new();
+        for message in messages {
+            if let SimpleQueryMessage::Row(row) = message {
+                rows.push((0..row.len()).map(|i| row.get(i).map(Into::into)).collect());
+            }
+        }
+        Ok(rows)
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), Error> {
+        PgTransaction::commit(*self)
+            .await
+            .migration_err("error committing transaction")
+    }
+}
+
+#[async_trait]
+impl AsyncTransactional for Client {
+    async fn begin<'a>(&'a mut self) -> Result<Box<dyn AsyncExecutor + 'a>, Error> {
+        let transaction = self
+            .transaction()
+            .await
+            .migration_err("error starting transaction")?;
+        Ok(Box::new(transaction))
+    }
+}
+
+// migrations run on a transaction given by the caller are nested in savepoints instead of being committed,
+// and are undone if the caller rolls it back
+#[async_trait]
+impl AsyncTransaction for PgTransaction<'_> {
+    type Error = PgError;
+    type Dialect = Postgres;
+
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        let transaction = self.transaction().await?;
+        let mut count = 0;
+        for query in queries {
+            transaction.batch_execute(*query).await?;
+            count += 1;
+        }
+        transaction.commit().await?;
+        Ok(count as usize)
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<AppliedMigration>> for PgTransaction<'_> {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Option<Vec<AppliedMigration>>, <Self as AsyncTransaction>::Error> {
+        let applied = query_applied_migrations(self, query).await?;
+        Ok(Some(applied))
+    }
+}
+
+#[async_trait]
+impl AsyncTransactional for PgTransaction<'_> {
+    async fn begin<'a>(&'a mut self) -> Result<Box<dyn AsyncExecutor + 'a>, Error> {
+        let transaction = self
+            .transaction()
+            .await
+            .migration_err("error starting transaction")?;
+        Ok(Box::new(transaction))
+    }
+}
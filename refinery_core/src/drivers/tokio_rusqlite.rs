@@ -0,0 +1,166 @@
+use crate::dialect::Sqlite;
+use crate::drivers::rusqlite::{query_applied_migrations, query_rows};
+use crate::error::WrapMigrationError;
+use crate::traits::r#async::{AsyncExecutor, AsyncQuery, AsyncTransaction, AsyncTransactional};
+use crate::{AppliedMigration, Error};
+use async_trait::async_trait;
+use rusqlite::{Connection as RqlConnection, Error as RqlError};
+use std::panic;
+use std::sync::{Arc, Mutex};
+
+/// A [`rusqlite::Connection`] that can be migrated with [`Runner::run_async`],
+/// its queries run on tokio's blocking thread pool.
+///
+/// [`rusqlite::Connection`]: https://docs.rs/rusqlite/*/rusqlite/struct.Connection.html
+/// [`Runner::run_async`]: struct.Runner.html#method.run_async
+#[derive(Clone)]
+pub struct AsyncSqliteConnection {
+    connection: Arc<Mutex<RqlConnection>>,
+}
+
+impl AsyncSqliteConnection {
+    /// Wraps `connection`, which is then only used from tokio's blocking thread pool
+    pub fn new(connection: RqlConnection) -> AsyncSqliteConnection {
+        AsyncSqliteConnection {
+            connection: Arc::new(Mutex::new(connection)),
+        }
+    }
+
+    /// Runs `f` with the connection on tokio's blocking thread pool, resuming its panic if it panics
+    pub async fn call<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut RqlConnection) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let connection = self.connection.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            // a panic on a previous call doesn't leave the connection itself in a bad state
+            let mut connection = connection.lock().unwrap_or_else(|err| err.into_inner());
+            f(&mut connection)
+        })
+        .await;
+
+        match result {
+            Ok(value) => value,
+            Err(err) => panic::resume_unwind(err.into_panic()),
+        }
+    }
+}
+
+impl From<RqlConnection> for AsyncSqliteConnection {
+    fn from(connection: RqlConnection) -> AsyncSqliteConnection {
+        AsyncSqliteConnection::new(connection)
+    }
+}
+
+fn to_owned(queries: &[&str]) -> Vec<String> {
+    queries.iter().map(|query| query.to_string()).collect()
+}
+
+#[async_trait]
+impl AsyncTransaction for AsyncSqliteConnection {
+    type Error = RqlError;
+    type Dialect = Sqlite;
+
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        let queries = to_owned(queries);
+        self.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let mut count = 0;
+            for query in queries.iter() {
+                transaction.execute_batch(query)?;
+                count += 1;
+            }
+            transaction.commit()?;
+            Ok(count)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<AppliedMigration>> for AsyncSqliteConnection {
+    async fn query(
+        &mut self,
+        query: &str,
+    ) -> Result<Option<Vec<AppliedMigration>>, <Self as AsyncTransaction>::Error> {
+        let query = query.to_string();
+        self.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let applied = query_applied_migrations(&transaction, &query)?;
+            transaction.commit()?;
+            Ok(Some(applied))
+        })
+        .await
+    }
+}
+
+// a rusqlite transaction borrows its connection, which can't be moved to the blocking thread pool,
+// so the transaction is begun and ended with plain statements, and rolled back when dropped without being committed
+struct AsyncSqliteTransaction<'a> {
+    connection: &'a AsyncSqliteConnection,
+    committed: bool,
+}
+
+#[async_trait]
+impl AsyncExecutor for AsyncSqliteTransaction<'_> {
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Error> {
+        let queries = to_owned(queries);
+        self.connection
+            .call(move |connection| {
+                let mut count = 0;
+                for query in queries.iter() {
+                    connection.execute_batch(query)?;
+                    count += 1;
+                }
+                Ok::<_, RqlError>(count)
+            })
+            .await
+            .migration_err("error executing queries")
+    }
+
+    async fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Error> {
+        let query = query.to_string();
+        self.connection
+            .call(move |connection| query_rows(connection, &query))
+            .await
+            .migration_err("error running query")
+    }
+
+    async fn commit(mut self: Box<Self>) -> Result<(), Error> {
+        self.committed = true;
+        self.connection
+            .call(|connection| connection.execute_batch("COMMIT"))
+            .await
+            .migration_err("error committing transaction")
+    }
+}
+
+impl Drop for AsyncSqliteTransaction<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            // drop can't await the blocking pool, rolling back is quick enough to run in place
+            let connection = self
+                .connection
+                .connection
+                .lock()
+                .unwrap_or_else(|err| err.into_inner());
+            if let Err(err) = connection.execute_batch("ROLLBACK") {
+                log::error!("error rolling back transaction: {}", err);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncTransactional for AsyncSqliteConnection {
+    async fn begin<'a>(&'a mut self) -> Result<Box<dyn AsyncExecutor + 'a>, Error> {
+        self.call(|connection| connection.execute_batch("BEGIN"))
+            .await
+            .migration_err("error starting transaction")?;
+        Ok(Box::new(AsyncSqliteTransaction {
+            connection: self,
+            committed: false,
+        }))
+    }
+}
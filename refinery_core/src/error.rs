@@ -8,6 +8,12 @@ pub enum Error {
     /// An Error from an invalid file name migration
     #[error("migration name must be in the format V{{number}}__{{name}}")]
     InvalidName,
+    /// An Error from an invalid callback file name
+    #[error("invalid callback name {0}, callback name must be an event name i.e. beforeMigrate, optionally followed by __{{name}}")]
+    InvalidCallbackName(String),
+    /// An Error from an invalid migrations namespace
+    #[error("invalid namespace {0}, namespaces can only have alphanumeric characters and \"_\"")]
+    InvalidNamespace(String),
     /// An Error from an invalid version on a file name migration
     #[error("migration version must be a valid integer")]
     InvalidVersion,
@@ -17,6 +23,35 @@ pub enum Error {
     /// An Error from an divergent version, the applied version is missing on the filesystem
     #[error("migration {0} is missing from the filesystem")]
     MissingVersion(AppliedMigration),
+    /// An Error from an invalid migrations table name
+    #[error("invalid migrations table name {0}, table names can only have alphanumeric characters and \"_\"")]
+    InvalidTableName(String),
+    /// An Error from a backup of the migrations table left behind by an upgrade that stopped halfway
+    #[error("found {0}_backup, left behind by an upgrade of the migrations table {0} that stopped halfway, copy its missing migrations back to {0} and drop it")]
+    MigrationsTableBackup(String),
+    /// An Error from a lock on the migrations table held by another runner
+    #[error("migrations table {0} is locked by another runner, a lock left behind by a runner that stopped before unlocking is taken over after an hour")]
+    MigrationsTableLocked(String),
+    /// An Error from a grouped run on a database that can't apply several migrations' schema changes on a single transaction
+    #[error(
+        "grouped migrations aren't supported on this database, run them with set_grouped(false)"
+    )]
+    GroupedMigrationsNotSupported,
+    /// An Error from a placeholder on a migration with no value given
+    #[error("no value given for placeholder {0} on migration {1}")]
+    MissingPlaceholder(String, Migration),
+    /// An Error from a placeholder on a .sql callback with no value given
+    #[error("no value given for placeholder {0} on callback {1}")]
+    MissingCallbackPlaceholder(String, String),
+    /// An Error from a Rust code migration run by a runner of the other kind, i.e. an async migration run by `Runner::run`
+    #[error("migration {0} can't be run by a {1} runner")]
+    IncompatibleMigration(Migration, &'static str),
+    /// An Error from two migration files with the same version
+    #[error("migration version {0} is defined by both {1} and {2}")]
+    DuplicateVersion(usize, PathBuf, PathBuf),
+    /// An Error from a migration file that couldn't be loaded, with its path
+    #[error("invalid migration file {0}: {1}")]
+    InvalidMigrationFile(PathBuf, #[source] Box<Error>),
     /// An Error from an invalid migrations path location
     #[error("invalid migrations path {0}, {1}")]
     InvalidMigrationPath(PathBuf, std::io::Error),
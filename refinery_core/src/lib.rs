@@ -1,15 +1,37 @@
+mod callbacks;
 pub mod config;
+pub mod conformance;
+pub mod dialect;
 mod drivers;
 mod error;
 mod runner;
 mod traits;
 mod util;
 
+pub use crate::callbacks::{AsyncCallback, Callback, CallbackEvent, SqlCallback};
+pub use crate::dialect::Dialect;
 pub use crate::error::Error;
-pub use crate::runner::{AppliedMigration, Migration, Runner};
-pub use crate::traits::r#async::AsyncMigrate;
-pub use crate::traits::sync::Migrate;
-pub use crate::util::{find_migration_files, MigrationType};
+pub use crate::runner::{
+    AppliedMigration, Migration, MigrationError, MigrationFn, MigrationFuture, Report, Runner,
+};
+pub use crate::traits::r#async::{
+    AsyncExecutor, AsyncMigrate, AsyncQuery, AsyncTransaction, AsyncTransactional,
+};
+pub use crate::traits::sync::{Driver, Executor, Migrate, Transactional};
+pub use crate::traits::DEFAULT_MIGRATION_TABLE_NAME;
+pub use crate::util::{
+    find_migration_files, find_migration_files_with, rerun_if_migrations_changed,
+    walk_migrations_dir, FindOptions, MigrationType, IGNORE_FILE_NAME,
+};
+
+#[cfg(all(feature = "rusqlite", feature = "tokio"))]
+pub use crate::drivers::tokio_rusqlite::AsyncSqliteConnection;
+
+#[cfg(feature = "mysql_async")]
+pub use crate::drivers::mysql_async::AsyncMysqlConnection;
+
+#[cfg(any(feature = "postgres", feature = "tokio-postgres"))]
+pub use crate::drivers::cockroach::CockroachConnection;
 
 #[cfg(feature = "rusqlite")]
 pub use rusqlite;
@@ -28,3 +50,21 @@ pub use mysql_async;
 
 #[cfg(feature = "tokio")]
 pub use tokio;
+
+#[cfg(feature = "diesel")]
+pub use diesel;
+
+#[cfg(feature = "sqlx")]
+pub use sqlx;
+
+#[cfg(feature = "r2d2")]
+pub use r2d2;
+
+#[cfg(feature = "bb8")]
+pub use bb8;
+
+#[cfg(feature = "deadpool-postgres")]
+pub use deadpool_postgres;
+
+#[cfg(feature = "tiberius")]
+pub use tiberius;
@@ -1,12 +1,20 @@
 use chrono::{DateTime, Local};
-use regex::Regex;
+use regex::{Captures, Regex};
 use siphasher::sip::SipHasher13;
 
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt;
+use std::future::Future;
 use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 
-use crate::{AsyncMigrate, Error, Migrate};
+use crate::callbacks::{AsyncCallback, Callback, SqlCallback};
+use crate::config::Config;
+use crate::traits::DEFAULT_MIGRATION_TABLE_NAME;
+use crate::util::{load_sql_callbacks, load_sql_migrations, FindOptions};
+use crate::{AsyncExecutor, AsyncMigrate, Error, Executor, Migrate};
 
 // regex used to match file names
 pub fn file_match_re() -> Regex {
@@ -15,6 +23,32 @@ pub fn file_match_re() -> Regex {
 
 lazy_static::lazy_static! {
     static ref RE: regex::Regex = file_match_re();
+    static ref PLACEHOLDER_RE: regex::Regex = Regex::new(r"\$\{(\w+)\}").unwrap();
+    static ref NAMESPACE_RE: regex::Regex = Regex::new(r"^\w+$").unwrap();
+    pub(crate) static ref TABLE_NAME_RE: regex::Regex = Regex::new(r"^\w+$").unwrap();
+}
+
+// replaces every `${name}` placeholder on sql by the value given for `name`,
+// returns the name of the first placeholder with no value given, if any
+pub(crate) fn replace_placeholders(
+    sql: &str,
+    placeholders: &HashMap<String, String>,
+) -> Result<String, String> {
+    let mut missing = None;
+    let sql = PLACEHOLDER_RE.replace_all(sql, |captures: &Captures| {
+        match placeholders.get(&captures[1]) {
+            Some(value) => value.clone(),
+            None => {
+                missing.get_or_insert_with(|| captures[1].to_string());
+                captures[0].to_string()
+            }
+        }
+    });
+
+    match missing {
+        Some(placeholder) => Err(placeholder),
+        None => Ok(sql.into_owned()),
+    }
 }
 
 /// An enum set that represents the prefix for the Migration, at the moment only Versioned is supported
@@ -23,6 +57,36 @@ pub enum MigrationPrefix {
     Versioned,
 }
 
+/// The error returned by Rust code migrations, wrapped on a [`Error::Connection`] when the migration fails
+///
+/// [`Error::Connection`]: enum.Error.html#variant.Connection
+pub type MigrationError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The future returned by async Rust code migrations
+pub type MigrationFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<(), MigrationError>> + Send + 'a>>;
+
+/// The function of a Rust code migration, invoked with an [`Executor`] on the migration's transaction,
+/// `Sync` migrations can only be run by [`Runner::run`] and `Async` ones by [`Runner::run_async`]
+///
+/// [`Executor`]: trait.Executor.html
+/// [`Runner::run`]: struct.Runner.html#method.run
+/// [`Runner::run_async`]: struct.Runner.html#method.run_async
+#[derive(Clone, Copy)]
+pub enum MigrationFn {
+    Sync(fn(&mut dyn Executor) -> Result<(), MigrationError>),
+    Async(for<'a> fn(&'a mut dyn AsyncExecutor) -> MigrationFuture<'a>),
+}
+
+impl fmt::Debug for MigrationFn {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationFn::Sync(_) => write!(fmt, "Sync"),
+            MigrationFn::Async(_) => write!(fmt, "Async"),
+        }
+    }
+}
+
 /// Represents a schema migration to be run on the database,
 /// this struct is used by the [`embed_migrations!`] and [`include_migration_mods!`] macros to gather migration files
 /// and shouldn't be needed by the user
@@ -35,6 +99,12 @@ pub struct Migration {
     pub version: usize,
     pub prefix: MigrationPrefix,
     pub sql: String,
+    // computed on creation so that it stays the same after placeholders are replaced on the sql
+    checksum: u64,
+    // the function run instead of the sql, which is empty, for Rust code migrations
+    pub(crate) code: Option<MigrationFn>,
+    // the set of migrations this one belongs to, empty for the default one
+    pub(crate) namespace: String,
 }
 
 impl Migration {
@@ -45,21 +115,83 @@ impl Migration {
             .ok_or(Error::InvalidName)?;
         let version = captures[2].parse().map_err(|_| Error::InvalidVersion)?;
 
-        let name = (&captures[3]).into();
+        let name: String = (&captures[3]).into();
         let prefix = match &captures[1] {
             "V" => MigrationPrefix::Versioned,
             _ => unreachable!(),
         };
 
+        let checksum = Migration::compute_checksum(&name, version, sql);
         Ok(Migration {
             name,
             version,
             sql: sql.into(),
             prefix,
+            checksum,
+            code: None,
+            namespace: String::new(),
+        })
+    }
+
+    /// Creates a Rust code migration, run by invoking `code` instead of executing sql.
+    /// As the function can't be hashed, the checksum is computed from `source`, the source of the module it's defined on
+    pub fn from_code(name: &str, code: MigrationFn, source: &str) -> Result<Migration, Error> {
+        let migration = Migration::from_filename(name, source)?;
+        Ok(Migration {
+            sql: String::new(),
+            code: Some(code),
+            ..migration
+        })
+    }
+
+    /// Returns a copy of this migration with every `${name}` placeholder on its sql replaced by the value given for `name`,
+    /// the checksum is kept as the one of the original sql, so changing placeholder values doesn't make the migration divergent.
+    /// Returns `Err` if there's no value for a placeholder found on the sql
+    pub fn replace_placeholders(
+        &self,
+        placeholders: &HashMap<String, String>,
+    ) -> Result<Migration, Error> {
+        let sql = replace_placeholders(&self.sql, placeholders)
+            .map_err(|placeholder| Error::MissingPlaceholder(placeholder, self.clone()))?;
+
+        Ok(Migration {
+            sql,
+            ..self.clone()
         })
     }
 
     pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+
+    /// The function run by this migration if it's a Rust code migration
+    pub fn code(&self) -> Option<MigrationFn> {
+        self.code
+    }
+
+    /// The namespace of this migration, empty for migrations on the default namespace
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Returns this migration on the given namespace. Migrations on a namespace have their own version sequence,
+    /// kept on the `namespace` column of the migrations table, so that migration sets of different components
+    /// can be run by the same [`Runner`] without their versions clashing, or on their own with [`Runner::set_namespace`].
+    /// Returns `Err` if the namespace has characters other than alphanumeric ones and "_"
+    ///
+    /// [`Runner`]: struct.Runner.html
+    /// [`Runner::set_namespace`]: struct.Runner.html#method.set_namespace
+    pub fn set_namespace(self, namespace: &str) -> Result<Migration, Error> {
+        if !NAMESPACE_RE.is_match(namespace) {
+            return Err(Error::InvalidNamespace(namespace.into()));
+        }
+        Ok(Migration {
+            namespace: namespace.into(),
+            ..self
+        })
+    }
+
+    fn compute_checksum(name: &str, version: usize, sql: &str) -> u64 {
         // Previously, `std::collections::hash_map::DefaultHasher` was used
         // to calculate the checksum and the implementation at that time
         // was SipHasher13. However, that implementation is not guaranteed:
@@ -69,24 +201,56 @@ impl Migration {
         // existing migrations and prevent breaking from possible future
         // changes to `DefaultHasher`.
         let mut hasher = SipHasher13::new();
-        self.name.hash(&mut hasher);
-        self.version.hash(&mut hasher);
-        self.sql.hash(&mut hasher);
+        name.hash(&mut hasher);
+        version.hash(&mut hasher);
+        sql.hash(&mut hasher);
         hasher.finish()
     }
 
+    /// Loads the .sql migration files found on `location` and its subdirectories, sorted by version,
+    /// returns `Err` if a file can't be read or has an invalid name, with the path of the file
+    pub fn load_dir(location: impl AsRef<Path>) -> Result<Vec<Migration>, Error> {
+        Migration::load_dir_with(location, &FindOptions::default())
+    }
+
+    /// Same as [`load_dir`], searching for the files with the given [`FindOptions`]
+    ///
+    /// [`load_dir`]: #method.load_dir
+    /// [`FindOptions`]: struct.FindOptions.html
+    pub fn load_dir_with(
+        location: impl AsRef<Path>,
+        options: &FindOptions,
+    ) -> Result<Vec<Migration>, Error> {
+        load_sql_migrations(&[location.as_ref().to_path_buf()], options)
+    }
+
+    /// Same as [`load_dir_with`], merging the migrations found on several locations,
+    /// returns `Err` if two migrations on any of the locations have the same version
+    ///
+    /// [`load_dir_with`]: #method.load_dir_with
+    pub fn load_dirs_with(
+        locations: &[PathBuf],
+        options: &FindOptions,
+    ) -> Result<Vec<Migration>, Error> {
+        load_sql_migrations(locations, options)
+    }
+
     pub fn as_applied(&self) -> AppliedMigration {
         AppliedMigration {
             name: self.name.clone(),
             version: self.version,
             checksum: self.checksum().to_string(),
             applied_on: Local::now(),
+            namespace: self.namespace.clone(),
         }
     }
 }
 
 impl fmt::Display for Migration {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.namespace.is_empty() {
+            write!(fmt, "{}/", self.namespace)?;
+        }
         write!(fmt, "V{}__{}", self.version, self.name)
     }
 }
@@ -97,13 +261,20 @@ impl PartialEq for Migration {
     fn eq(&self, other: &Migration) -> bool {
         self.version == other.version
             && self.name == other.name
+            && self.namespace == other.namespace
             && self.checksum() == other.checksum()
     }
 }
 
+// migrations are ordered by version, the other fields only tell apart migrations with the same version
+// so that the ordering agrees with PartialEq, i.e. the same version on different namespaces
 impl Ord for Migration {
     fn cmp(&self, other: &Migration) -> Ordering {
-        self.version.cmp(&other.version)
+        self.version
+            .cmp(&other.version)
+            .then_with(|| self.namespace.cmp(&other.namespace))
+            .then_with(|| self.name.cmp(&other.name))
+            .then_with(|| self.checksum().cmp(&other.checksum()))
     }
 }
 
@@ -119,22 +290,90 @@ pub struct AppliedMigration {
     pub version: usize,
     pub applied_on: DateTime<Local>,
     pub checksum: String,
+    /// the namespace the migration was applied on, empty for the default one
+    pub namespace: String,
 }
 
 impl Eq for AppliedMigration {}
 
 impl PartialEq for AppliedMigration {
     fn eq(&self, other: &AppliedMigration) -> bool {
-        self.version == other.version && self.name == other.name && self.checksum == other.checksum
+        self.version == other.version
+            && self.name == other.name
+            && self.namespace == other.namespace
+            && self.checksum == other.checksum
     }
 }
 
 impl fmt::Display for AppliedMigration {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.namespace.is_empty() {
+            write!(fmt, "{}/", self.namespace)?;
+        }
         write!(fmt, "V{}__{}", self.version, self.name)
     }
 }
 
+/// Struct that represents the report of the migration cycle,
+/// a `Report` instance is returned by the [`Runner::run`] and [`Runner::run_async`] methods
+/// via [`Result`]`<Report, Error>`, on success.
+///
+/// [`Runner::run`]: struct.Runner.html#method.run
+/// [`Runner::run_async`]: struct.Runner.html#method.run_async
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    applied_migrations: Vec<Migration>,
+    skipped_migrations: Vec<Migration>,
+    divergent_migrations: Vec<(AppliedMigration, Migration)>,
+    missing_migrations: Vec<AppliedMigration>,
+}
+
+impl Report {
+    /// Retrieves the list of migrations applied on this run
+    pub fn applied_migrations(&self) -> &[Migration] {
+        &self.applied_migrations
+    }
+
+    /// Retrieves the list of migrations that were already applied
+    pub fn skipped_migrations(&self) -> &[Migration] {
+        &self.skipped_migrations
+    }
+
+    /// Retrieves the list of divergent migrations found, applied migrations with the same version
+    /// but different name or checksum from the ones on the filesystem,
+    /// only possible with [`Runner::set_abort_divergent`] set to false
+    ///
+    /// [`Runner::set_abort_divergent`]: struct.Runner.html#method.set_abort_divergent
+    pub fn divergent_migrations(&self) -> &[(AppliedMigration, Migration)] {
+        &self.divergent_migrations
+    }
+
+    /// Retrieves the list of missing migrations found, applied migrations that are not found on the filesystem
+    /// or migrations on the filesystem with a version inferior to the last one applied but not applied,
+    /// only possible with [`Runner::set_abort_missing`] set to false
+    ///
+    /// [`Runner::set_abort_missing`]: struct.Runner.html#method.set_abort_missing
+    pub fn missing_migrations(&self) -> &[AppliedMigration] {
+        &self.missing_migrations
+    }
+
+    pub(crate) fn push_applied(&mut self, migration: Migration) {
+        self.applied_migrations.push(migration);
+    }
+
+    pub(crate) fn push_skipped(&mut self, migration: Migration) {
+        self.skipped_migrations.push(migration);
+    }
+
+    pub(crate) fn push_divergent(&mut self, applied: AppliedMigration, migration: Migration) {
+        self.divergent_migrations.push((applied, migration));
+    }
+
+    pub(crate) fn push_missing(&mut self, missing: AppliedMigration) {
+        self.missing_migrations.push(missing);
+    }
+}
+
 /// Struct that represents the entrypoint to run the migrations,
 /// an instance of this struct is returned by the [`embed_migrations!`] and [`include_migration_mods!`] macros.
 /// `Runner` should not need to be instantiated manually
@@ -142,22 +381,69 @@ impl fmt::Display for AppliedMigration {
 /// [`embed_migrations!`]: macro.embed_migrations.html
 /// [`include_migration_mods!`]: macro.include_migration_mods.html
 pub struct Runner {
-    grouped: bool,
-    abort_divergent: bool,
-    abort_missing: bool,
+    pub(crate) grouped: bool,
+    pub(crate) abort_divergent: bool,
+    pub(crate) abort_missing: bool,
+    migration_table_name: String,
     migrations: Vec<Migration>,
+    callbacks: Vec<Box<dyn Callback>>,
+    async_callbacks: Vec<Box<dyn AsyncCallback>>,
+    sql_callbacks: Vec<SqlCallback>,
+    placeholders: HashMap<String, String>,
+    namespace: Option<String>,
 }
 
 impl Runner {
+    /// Creates a new `Runner` with the given migrations. Migrations of several crates, i.e. the ones returned by the
+    /// `migrations()` function generated by the macros, can be run together with `Runner::new(&[auth::migrations(), migrations()].concat())`,
+    /// see [`Migration::set_namespace`] for keeping their versions from clashing
+    ///
+    /// [`Migration::set_namespace`]: struct.Migration.html#method.set_namespace
     pub fn new(migrations: &[Migration]) -> Runner {
         Runner {
             grouped: false,
             abort_divergent: true,
             abort_missing: true,
+            migration_table_name: DEFAULT_MIGRATION_TABLE_NAME.into(),
             migrations: migrations.to_vec(),
+            callbacks: Vec::new(),
+            async_callbacks: Vec::new(),
+            sql_callbacks: Vec::new(),
+            placeholders: HashMap::new(),
+            namespace: None,
         }
     }
 
+    /// Creates a new `Runner` from a [`Config`], loading the .sql migration and callback files found on its `locations`
+    /// and applying its runner settings, i.e. `grouped`, `abort_divergent`, `abort_missing` and `table_name`
+    ///
+    /// [`Config`]: config/struct.Config.html
+    pub fn from_config(config: &Config) -> Result<Runner, Error> {
+        Ok(
+            Runner::from_locations(config.get_locations(), &config.get_find_options())?
+                .set_grouped(config.get_grouped())
+                .set_abort_divergent(config.get_abort_divergent())
+                .set_abort_missing(config.get_abort_missing())
+                .set_migration_table_name(config.get_table_name())
+                .set_placeholders(config.get_placeholders().clone()),
+        )
+    }
+
+    /// Creates a new `Runner` with the .sql migration and callback files found on `location` and its subdirectories,
+    /// returns `Err` if a file can't be read or has an invalid name, with the path of the file
+    pub fn from_directory(location: impl AsRef<Path>) -> Result<Runner, Error> {
+        Runner::from_locations(&[location.as_ref().to_path_buf()], &FindOptions::default())
+    }
+
+    fn from_locations(locations: &[PathBuf], options: &FindOptions) -> Result<Runner, Error> {
+        let migrations = load_sql_migrations(locations, options)?;
+        let mut runner = Runner::new(&migrations);
+        for callback in load_sql_callbacks(locations, options)? {
+            runner = runner.add_sql_callback(callback);
+        }
+        Ok(runner)
+    }
+
     /// Set true if all migrations should be grouped and run in a single transaction.
     /// by default this is set to false, each migration runs on their own transaction
     ///
@@ -165,6 +451,9 @@ impl Runner {
     ///
     /// set_grouped won't probbaly work on MySQL Databases as MySQL lacks support for transactions around schema alteration operations,
     /// meaning that if a migration fails to apply you will have to manually unpick the changes in order to try again (it’s impossible to roll back to an earlier point).
+    /// On CockroachDB, whose dialect doesn't support grouped migrations, grouped runs fail with [`Error::GroupedMigrationsNotSupported`].
+    ///
+    /// [`Error::GroupedMigrationsNotSupported`]: enum.Error.html#variant.GroupedMigrationsNotSupported
     pub fn set_grouped(self, grouped: bool) -> Runner {
         Runner { grouped, ..self }
     }
@@ -183,39 +472,149 @@ impl Runner {
     /// i.e. applied migrations that are not found on the filesystem,
     /// or migrations found on filesystem with a version inferior to the last one applied but not applied.
     /// by default this is set to true
-    pub fn set_abort_missing(self, abort_divergent: bool) -> Runner {
+    pub fn set_abort_missing(self, abort_missing: bool) -> Runner {
         Runner {
-            abort_divergent,
+            abort_missing,
+            ..self
+        }
+    }
+
+    /// Set the table name to use on the migrations table,
+    /// by default this is set to `refinery_schema_history`.
+    /// The name is checked when the migrations are run, which returns `Err` if it has characters other than alphanumeric ones and "_"
+    pub fn set_migration_table_name<S: AsRef<str>>(self, migration_table_name: S) -> Runner {
+        Runner {
+            migration_table_name: migration_table_name.as_ref().into(),
+            ..self
+        }
+    }
+
+    /// Set the values of the `${name}` placeholders replaced on the migrations and .sql callbacks sql before they are applied.
+    /// Checksums are computed on the sql before replacing them, so values can differ between environments.
+    /// by default there are no placeholders, and the migrations sql is applied as is
+    pub fn set_placeholders(self, placeholders: HashMap<String, String>) -> Runner {
+        Runner {
+            placeholders,
+            ..self
+        }
+    }
+
+    /// Set the namespace of the migrations to run, so that only the migrations set of one component is run,
+    /// and only the migrations applied on that namespace are checked for divergent and missing ones.
+    /// by default the migrations of every namespace are run, see [`Migration::set_namespace`]
+    ///
+    /// [`Migration::set_namespace`]: struct.Migration.html#method.set_namespace
+    pub fn set_namespace<S: AsRef<str>>(self, namespace: S) -> Runner {
+        Runner {
+            namespace: Some(namespace.as_ref().into()),
             ..self
         }
     }
 
+    // the migrations table name, which is pasted on the queries run on the migrations table
+    pub(crate) fn get_migration_table_name(&self) -> Result<&str, Error> {
+        if !TABLE_NAME_RE.is_match(&self.migration_table_name) {
+            return Err(Error::InvalidTableName(self.migration_table_name.clone()));
+        }
+        Ok(&self.migration_table_name)
+    }
+
+    // the migrations on the namespace set to run, if any, with their placeholders replaced, if any were set
+    pub(crate) fn get_migrations(&self) -> Result<Vec<Migration>, Error> {
+        let migrations = self.migrations.iter().filter(|migration| {
+            self.namespace
+                .as_ref()
+                .map_or(true, |namespace| migration.namespace() == namespace)
+        });
+        if self.placeholders.is_empty() {
+            return Ok(migrations.cloned().collect());
+        }
+        migrations
+            .map(|migration| migration.replace_placeholders(&self.placeholders))
+            .collect()
+    }
+
+    // the .sql callbacks with their placeholders replaced, if any were set
+    pub(crate) fn get_sql_callbacks(&self) -> Result<Vec<SqlCallback>, Error> {
+        if self.placeholders.is_empty() {
+            return Ok(self.sql_callbacks.clone());
+        }
+        self.sql_callbacks
+            .iter()
+            .map(|callback| callback.replace_placeholders(&self.placeholders))
+            .collect()
+    }
+
+    // the .sql callbacks along with the ones added with add_callback, invoked by Runner::run
+    pub(crate) fn get_callbacks<'a>(
+        &'a self,
+        sql_callbacks: &'a [SqlCallback],
+    ) -> Vec<&'a dyn Callback> {
+        sql_callbacks
+            .iter()
+            .map(|callback| callback as &dyn Callback)
+            .chain(self.callbacks.iter().map(AsRef::as_ref))
+            .collect()
+    }
+
+    // the .sql callbacks along with the ones added with add_async_callback, invoked by Runner::run_async
+    pub(crate) fn get_async_callbacks<'a>(
+        &'a self,
+        sql_callbacks: &'a [SqlCallback],
+    ) -> Vec<&'a dyn AsyncCallback> {
+        sql_callbacks
+            .iter()
+            .map(|callback| callback as &dyn AsyncCallback)
+            .chain(self.async_callbacks.iter().map(AsRef::as_ref))
+            .collect()
+    }
+
+    /// Add a [`Callback`] invoked around the migration cycle by [`Runner::run`],
+    /// callbacks are invoked in the order they were added
+    ///
+    /// [`Callback`]: trait.Callback.html
+    /// [`Runner::run`]: struct.Runner.html#method.run
+    pub fn add_callback<C: Callback + 'static>(mut self, callback: C) -> Runner {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// Add an [`AsyncCallback`] invoked around the migration cycle by [`Runner::run_async`],
+    /// callbacks are invoked in the order they were added
+    ///
+    /// [`AsyncCallback`]: trait.AsyncCallback.html
+    /// [`Runner::run_async`]: struct.Runner.html#method.run_async
+    pub fn add_async_callback<C: AsyncCallback + 'static>(mut self, callback: C) -> Runner {
+        self.async_callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// Add a .sql callback file, invoked by both [`Runner::run`] and [`Runner::run_async`] before the other callbacks,
+    /// with its `${name}` placeholders replaced as the migrations ones, see [`Runner::set_placeholders`].
+    /// This is used by the [`embed_migrations!`] macro for the callback files found along the migrations
+    ///
+    /// [`Runner::run`]: struct.Runner.html#method.run
+    /// [`Runner::run_async`]: struct.Runner.html#method.run_async
+    /// [`Runner::set_placeholders`]: struct.Runner.html#method.set_placeholders
+    /// [`embed_migrations!`]: macro.embed_migrations.html
+    pub fn add_sql_callback(mut self, callback: SqlCallback) -> Runner {
+        self.sql_callbacks.push(callback);
+        self
+    }
+
     /// Runs the Migrations in the supplied database connection
-    pub fn run<'a, C>(&self, conn: &'a mut C) -> Result<(), Error>
+    pub fn run<'a, C>(&self, conn: &'a mut C) -> Result<Report, Error>
     where
         C: Migrate,
     {
-        Migrate::migrate(
-            conn,
-            &self.migrations,
-            self.abort_divergent,
-            self.abort_missing,
-            self.grouped,
-        )
+        Migrate::migrate_with_runner(conn, self)
     }
 
     /// Runs the Migrations asynchronously in the supplied database connection
-    pub async fn run_async<C>(&self, conn: &mut C) -> Result<(), Error>
+    pub async fn run_async<C>(&self, conn: &mut C) -> Result<Report, Error>
     where
         C: AsyncMigrate + Send,
     {
-        AsyncMigrate::migrate(
-            conn,
-            &self.migrations,
-            self.abort_divergent,
-            self.abort_missing,
-            self.grouped,
-        )
-        .await
+        AsyncMigrate::migrate_with_runner(conn, self).await
     }
 }
@@ -1,52 +1,203 @@
+use crate::callbacks::AsyncCallback;
+use crate::dialect::Dialect;
 use crate::error::WrapMigrationError;
-use crate::traits::{check_missing_divergent, ASSERT_MIGRATIONS_TABLE, GET_APPLIED_MIGRATIONS};
-use crate::{AppliedMigration, Error, Migration};
+use crate::runner::MigrationFn;
+use crate::traits::{
+    check_missing_divergent, expect_count, group_by_namespace, lock_owner, parse_count,
+};
+use crate::{AppliedMigration, Error, Migration, Report, Runner};
 
 use async_trait::async_trait;
-use chrono::Local;
 
+/// A connection refinery can migrate asynchronously, the async counterpart of [`Driver`], implemented by the async drivers
+/// and open to connection types of other crates. Connections also implementing [`AsyncQuery`] and [`AsyncTransactional`]
+/// are [`AsyncMigrate`], and can check their implementation with the async checks of the [`conformance`] suite.
+///
+/// [`Driver`]: trait.Driver.html
+/// [`AsyncQuery`]: trait.AsyncQuery.html
+/// [`AsyncTransactional`]: trait.AsyncTransactional.html
+/// [`AsyncMigrate`]: trait.AsyncMigrate.html
+/// [`conformance`]: conformance/index.html
 #[async_trait]
 pub trait AsyncTransaction {
     type Error: std::error::Error + Send + Sync + 'static;
+    /// The SQL dialect of the database, producing the statements run on the migrations table
+    type Dialect: Dialect;
 
+    /// Executes the queries on a transaction of their own, returning how many were executed
     async fn execute(&mut self, query: &[&str]) -> Result<usize, Self::Error>;
 }
 
+/// Async connections selecting the migrations applied on the migrations table, see [`AsyncTransaction`]
+///
+/// [`AsyncTransaction`]: trait.AsyncTransaction.html
 #[async_trait]
 pub trait AsyncQuery<T>: AsyncTransaction {
+    /// Runs the dialect's query selecting the applied migrations, `None` if they can't be read
     async fn query(&mut self, query: &str) -> Result<Option<T>, Self::Error>;
 }
 
-async fn migrate<T: AsyncTransaction>(
+/// Async counterpart of [`Executor`], given to async Rust code migrations and [`AsyncCallback`] hooks
+///
+/// [`Executor`]: trait.Executor.html
+/// [`AsyncCallback`]: trait.AsyncCallback.html
+#[async_trait]
+pub trait AsyncExecutor: Send {
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Error>;
+
+    /// Runs a query returning its rows, with every column value as text, or `None` if it is NULL
+    async fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Error>;
+
+    async fn commit(self: Box<Self>) -> Result<(), Error>;
+
+    /// Rolls back the transaction after a migration or hook failed on it.
+    /// Most drivers roll back transactions dropped without being committed and don't override it
+    async fn rollback(self: Box<Self>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Async counterpart of [`Transactional`]
+///
+/// [`Transactional`]: trait.Transactional.html
+#[async_trait]
+pub trait AsyncTransactional {
+    async fn begin<'a>(&'a mut self) -> Result<Box<dyn AsyncExecutor + 'a>, Error>;
+}
+
+// rolls back a transaction on which a migration or hook failed, returning the error it failed with
+async fn rollback(transaction: Box<dyn AsyncExecutor + '_>, err: Error) -> Error {
+    if let Err(rollback_err) = transaction.rollback().await {
+        log::error!("error rolling back transaction: {}", rollback_err);
+    }
+    err
+}
+
+// runs the query on a transaction of its own, rolled back if it fails
+async fn query_in_transaction<T: AsyncTransactional + Send>(
+    connection: &mut T,
+    query: &str,
+) -> Result<Vec<Vec<Option<String>>>, Error> {
+    let mut transaction = connection.begin().await?;
+    match transaction.query(query).await {
+        Ok(rows) => {
+            transaction.commit().await?;
+            Ok(rows)
+        }
+        Err(err) => Err(rollback(transaction, err).await),
+    }
+}
+
+// invokes the given hook of every callback on a new transaction, stopping on the first one that fails
+macro_rules! run_callbacks {
+    ($connection:expr, $callbacks:expr, $hook:ident($($arg:expr),*)) => {
+        if !$callbacks.is_empty() {
+            let mut transaction = $connection.begin().await?;
+            for callback in $callbacks.iter() {
+                let result = callback.$hook($($arg,)* transaction.as_mut()).await;
+                if let Err(err) = result {
+                    return Err(rollback(transaction, err).await);
+                }
+            }
+            transaction.commit().await?;
+        }
+    };
+}
+
+// runs the migration's code and inserts its row on the migrations table with the given transaction
+async fn apply_code(
+    transaction: &mut dyn AsyncExecutor,
+    migration: &Migration,
+    update_query: &str,
+) -> Result<(), Error> {
+    match migration.code {
+        Some(MigrationFn::Async(code)) => code(transaction).await.map_err(|err| {
+            Error::Connection(format!("error applying migration {}", migration), err)
+        })?,
+        _ => return Err(Error::IncompatibleMigration(migration.clone(), "async")),
+    }
+    transaction.execute(&[update_query]).await?;
+    Ok(())
+}
+
+async fn migrate<T: AsyncTransaction + AsyncTransactional + Send>(
     transaction: &mut T,
     migrations: Vec<Migration>,
+    migration_table_name: &str,
+    callbacks: &[&dyn AsyncCallback],
+    report: &mut Report,
 ) -> Result<(), Error> {
-    for migration in migrations.iter() {
+    for migration in migrations.into_iter() {
+        run_callbacks!(transaction, callbacks, before_each(&migration));
+
         log::info!("applying migration: {}", migration);
-        let update_query = &format!(
-                "INSERT INTO refinery_schema_history (version, name, applied_on, checksum) VALUES ({}, '{}', '{}', '{}')",
-                migration.version, migration.name, Local::now().to_rfc3339(), migration.checksum().to_string());
-        transaction
-            .execute(&[&migration.sql, update_query])
+        let update_query = T::Dialect::insert_migration_query(&migration, migration_table_name);
+        let result = if migration.code.is_some() {
+            apply_in_transaction(
+                transaction,
+                std::slice::from_ref(&migration),
+                migration_table_name,
+            )
             .await
-            .migration_err(&format!("error applying migration {}", migration))?;
+        } else {
+            transaction
+                .execute(&[&migration.sql, &update_query])
+                .await
+                .migration_err(&format!("error applying migration {}", migration))
+                .map(|_| ())
+        };
+
+        if let Err(err) = result {
+            run_callbacks!(transaction, callbacks, after_each_error(&migration, &err));
+            return Err(err);
+        }
+
+        run_callbacks!(transaction, callbacks, after_each(&migration));
+        report.push_applied(migration);
     }
     Ok(())
 }
 
-async fn migrate_grouped<T: AsyncTransaction>(
+// applies the migrations along with their rows on the migrations table in a single transaction begun on the connection,
+// used instead of executing batches of queries when some of them are Rust code migrations
+async fn apply_in_transaction<T: AsyncTransaction + AsyncTransactional + Send>(
+    connection: &mut T,
+    migrations: &[Migration],
+    migration_table_name: &str,
+) -> Result<(), Error> {
+    let mut transaction = connection.begin().await?;
+    for migration in migrations {
+        let update_query = T::Dialect::insert_migration_query(migration, migration_table_name);
+        let result = if migration.code.is_some() {
+            apply_code(transaction.as_mut(), migration, &update_query).await
+        } else {
+            transaction
+                .execute(&[&migration.sql, &update_query])
+                .await
+                .map(|_| ())
+        };
+        if let Err(err) = result {
+            return Err(rollback(transaction, err).await);
+        }
+    }
+    transaction.commit().await
+}
+
+async fn migrate_grouped<T: AsyncTransaction + AsyncTransactional + Send>(
     transaction: &mut T,
     migrations: Vec<Migration>,
+    migration_table_name: &str,
+    callbacks: &[&dyn AsyncCallback],
+    report: &mut Report,
 ) -> Result<(), Error> {
     let mut grouped_migrations = Vec::new();
     let mut display_migrations = Vec::new();
-    for migration in migrations.into_iter() {
-        let query = format!(
-            "INSERT INTO refinery_schema_history (version, name, applied_on, checksum) VALUES ({}, '{}', '{}', '{}')",
-            migration.version, migration.name, Local::now().to_rfc3339(), migration.checksum().to_string()
-        );
+    for migration in migrations.iter() {
+        run_callbacks!(transaction, callbacks, before_each(migration));
+
+        let query = T::Dialect::insert_migration_query(migration, migration_table_name);
         display_migrations.push(migration.to_string());
-        grouped_migrations.push(migration.sql);
+        grouped_migrations.push(migration.sql.clone());
         grouped_migrations.push(query);
     }
     log::info!(
@@ -54,57 +205,247 @@ async fn migrate_grouped<T: AsyncTransaction>(
         display_migrations
     );
 
-    let refs: Vec<&str> = grouped_migrations.iter().map(AsRef::as_ref).collect();
+    let result = if migrations.iter().any(|migration| migration.code.is_some()) {
+        apply_in_transaction(transaction, &migrations, migration_table_name).await
+    } else {
+        let refs: Vec<&str> = grouped_migrations.iter().map(AsRef::as_ref).collect();
+        transaction
+            .execute(refs.as_ref())
+            .await
+            .migration_err("error applying migrations")
+            .map(|_| ())
+    };
 
-    transaction
-        .execute(refs.as_ref())
-        .await
-        .migration_err("error applying migrations")?;
+    if let Err(err) = result {
+        for migration in migrations.iter() {
+            run_callbacks!(transaction, callbacks, after_each_error(migration, &err));
+        }
+        return Err(err);
+    }
+
+    for migration in migrations.into_iter() {
+        run_callbacks!(transaction, callbacks, after_each(&migration));
+        report.push_applied(migration);
+    }
 
     Ok(())
 }
 
+/// Connections that can be migrated asynchronously, implemented by every async driver's connection,
+/// and by pools that run the whole migration on a single connection checked out of them
 #[async_trait]
-pub trait AsyncMigrate: AsyncQuery<Vec<AppliedMigration>>
-where
-    Self: Sized,
-{
+pub trait AsyncMigrate: Send {
+    /// Migrates the connection with `migrations`, on the default migrations table,
+    /// see [`AsyncMigrate::migrate_with_runner`] for the other settings of a [`Runner`]
+    ///
+    /// [`AsyncMigrate::migrate_with_runner`]: trait.AsyncMigrate.html#method.migrate_with_runner
+    /// [`Runner`]: struct.Runner.html
     async fn migrate(
         &mut self,
         migrations: &[Migration],
         abort_divergent: bool,
         abort_missing: bool,
         grouped: bool,
-    ) -> Result<(), Error> {
-        self.execute(&[ASSERT_MIGRATIONS_TABLE])
-            .await
-            .migration_err("error asserting migrations table")?;
+    ) -> Result<Report, Error> {
+        let runner = Runner::new(migrations)
+            .set_abort_divergent(abort_divergent)
+            .set_abort_missing(abort_missing)
+            .set_grouped(grouped);
+        self.migrate_with_runner(&runner).await
+    }
 
-        let applied_migrations = self
-            .query(GET_APPLIED_MIGRATIONS)
-            .await
-            .migration_err("error getting current schema version")?
-            .unwrap_or_default();
+    /// Migrates the connection with the migrations and settings of `runner`, see [`Runner::run_async`]
+    ///
+    /// [`Runner::run_async`]: struct.Runner.html#method.run_async
+    async fn migrate_with_runner(&mut self, runner: &Runner) -> Result<Report, Error>;
+}
 
-        let migrations = check_missing_divergent(
-            applied_migrations,
-            migrations.to_vec(),
+// migrates the database once the migrations table is locked
+async fn migrate_locked<T>(
+    connection: &mut T,
+    migrations: &[Migration],
+    abort_divergent: bool,
+    abort_missing: bool,
+    grouped: bool,
+    migration_table_name: &str,
+    callbacks: &[&dyn AsyncCallback],
+) -> Result<Report, Error>
+where
+    T: AsyncQuery<Vec<AppliedMigration>> + AsyncTransactional + Send,
+{
+    let mut report = Report::default();
+    run_callbacks!(connection, callbacks, before_migrate());
+
+    connection
+        .execute(&[&T::Dialect::assert_migrations_table_query(
+            migration_table_name,
+        )])
+        .await
+        .migration_err("error asserting migrations table")?;
+    let backup_tables = query_in_transaction(
+        connection,
+        &T::Dialect::backup_table_query(migration_table_name),
+    )
+    .await?;
+    let namespace_columns = query_in_transaction(
+        connection,
+        &T::Dialect::namespace_column_query(migration_table_name),
+    )
+    .await?;
+    if expect_count(parse_count(&backup_tables))? > 0 {
+        return Err(Error::MigrationsTableBackup(migration_table_name.into()));
+    }
+    if expect_count(parse_count(&namespace_columns))? == 0 {
+        log::info!("adding the namespace column to {}", migration_table_name);
+        let queries = T::Dialect::upgrade_migrations_table_queries(migration_table_name);
+        let refs: Vec<&str> = queries.iter().map(AsRef::as_ref).collect();
+        if T::Dialect::supports_grouped_migrations() {
+            connection
+                .execute(&refs)
+                .await
+                .migration_err("error upgrading migrations table")?;
+        } else {
+            // a backup left behind if one of them fails is found by the next run
+            for query in refs {
+                connection
+                    .execute(&[query])
+                    .await
+                    .migration_err("error upgrading migrations table")?;
+            }
+        }
+    }
+
+    let applied_migrations = connection
+        .query(&T::Dialect::get_applied_migrations_query(
+            migration_table_name,
+        ))
+        .await
+        .migration_err("error getting current schema version")?
+        .unwrap_or_default();
+
+    let mut to_be_applied = Vec::new();
+    // each namespace has its own version sequence, and is checked only against the migrations applied on it
+    for (namespace, migrations) in group_by_namespace(migrations) {
+        let applied = applied_migrations
+            .iter()
+            .filter(|applied| applied.namespace == namespace)
+            .cloned()
+            .collect();
+        to_be_applied.extend(check_missing_divergent(
+            applied,
+            migrations,
             abort_divergent,
             abort_missing,
-        )?;
+            &mut report,
+        )?);
+    }
+    let migrations = to_be_applied;
 
-        if migrations.is_empty() {
-            log::info!("no migrations to apply");
-        }
+    if migrations.is_empty() {
+        log::info!("no migrations to apply");
+    }
 
-        if grouped {
-            migrate_grouped(self, migrations).await?
-        } else {
-            migrate(self, migrations).await?
+    if grouped {
+        migrate_grouped(
+            connection,
+            migrations,
+            migration_table_name,
+            callbacks,
+            &mut report,
+        )
+        .await?
+    } else {
+        migrate(
+            connection,
+            migrations,
+            migration_table_name,
+            callbacks,
+            &mut report,
+        )
+        .await?
+    }
+
+    run_callbacks!(connection, callbacks, after_migrate(&report));
+
+    Ok(report)
+}
+
+// takes the lock on the migrations table for owner, Err if another runner holds it
+async fn lock<T: AsyncTransaction + AsyncTransactional + Send>(
+    connection: &mut T,
+    migration_table_name: &str,
+    owner: &str,
+) -> Result<(), Error> {
+    let query = match T::Dialect::lock_migrations_table_query(migration_table_name, owner) {
+        Some(query) => query,
+        None => return Ok(()),
+    };
+    connection
+        .execute(&[&query])
+        .await
+        .migration_err("error locking migrations table")?;
+    if let Some(query) = T::Dialect::held_lock_query(migration_table_name, owner) {
+        let held = query_in_transaction(connection, &query).await?;
+        if expect_count(parse_count(&held))? == 0 {
+            return Err(Error::MigrationsTableLocked(migration_table_name.into()));
         }
+    }
+    Ok(())
+}
 
-        Ok(())
+// releases the lock on the migrations table after the migration ended, whether it succeeded or not,
+// an error unlocking is only returned if the migration succeeded
+async fn unlock<T: AsyncTransaction + Send>(
+    connection: &mut T,
+    migration_table_name: &str,
+    owner: &str,
+    result: Result<Report, Error>,
+) -> Result<Report, Error> {
+    let query = match T::Dialect::unlock_migrations_table_query(migration_table_name, owner) {
+        Some(query) => query,
+        None => return result,
+    };
+    let unlocked = connection
+        .execute(&[&query])
+        .await
+        .migration_err("error unlocking migrations table");
+    match (result, unlocked) {
+        (Ok(report), Ok(_)) => Ok(report),
+        (Ok(_), Err(err)) => Err(err),
+        (Err(err), unlocked) => {
+            if let Err(unlock_err) = unlocked {
+                log::error!("{}", unlock_err);
+            }
+            Err(err)
+        }
     }
 }
 
-impl<T> AsyncMigrate for T where T: AsyncQuery<Vec<AppliedMigration>> {}
+#[async_trait]
+impl<T> AsyncMigrate for T
+where
+    T: AsyncQuery<Vec<AppliedMigration>> + AsyncTransactional + Send,
+{
+    async fn migrate_with_runner(&mut self, runner: &Runner) -> Result<Report, Error> {
+        let migration_table_name = runner.get_migration_table_name()?;
+        let migrations = runner.get_migrations()?;
+        let sql_callbacks = runner.get_sql_callbacks()?;
+        if runner.grouped && !T::Dialect::supports_grouped_migrations() {
+            return Err(Error::GroupedMigrationsNotSupported);
+        }
+        // only one runner migrates the database at a time, the others fail while it holds the lock
+        let owner = lock_owner();
+        lock(self, migration_table_name, &owner).await?;
+        let result = migrate_locked(
+            self,
+            &migrations,
+            runner.abort_divergent,
+            runner.abort_missing,
+            runner.grouped,
+            migration_table_name,
+            &runner.get_async_callbacks(&sql_callbacks),
+        )
+        .await;
+        unlock(self, migration_table_name, &owner, result).await
+    }
+}
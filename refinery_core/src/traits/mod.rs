@@ -1,15 +1,18 @@
 pub mod r#async;
 pub mod sync;
 
-use crate::{AppliedMigration, Error, Migration};
+use crate::{AppliedMigration, Error, Migration, Report};
+use chrono::Local;
 
 //checks for missing migrations on filesystem or apllied migrations with a different name and checksum but same version
 //if abort_divergent or abort_missing are true returns Err on those cases, else returns the list of migrations to be applied
+//skipped, divergent and missing migrations found are added to the report
 pub(crate) fn check_missing_divergent(
     applied: Vec<AppliedMigration>,
     mut migrations: Vec<Migration>,
     abort_divergent: bool,
     abort_missing: bool,
+    report: &mut Report,
 ) -> Result<Vec<Migration>, Error> {
     migrations.sort();
     let current = match applied.last() {
@@ -29,6 +32,7 @@ pub(crate) fn check_missing_divergent(
                     return Err(Error::MissingVersion(app.clone()));
                 } else {
                     log::error!("migration {} is missing from the filesystem", app);
+                    report.push_missing(app.clone());
                 }
             }
             Some(migration) => {
@@ -41,7 +45,10 @@ pub(crate) fn check_missing_divergent(
                             app,
                             migration
                         );
+                        report.push_divergent(app.clone(), migration.clone());
                     }
+                } else {
+                    report.push_skipped(migration.clone());
                 }
             }
         }
@@ -63,6 +70,7 @@ pub(crate) fn check_missing_divergent(
                     return Err(Error::MissingVersion(migration.as_applied()));
                 } else {
                     log::error!("found migration on filsystem {} not applied", migration);
+                    report.push_missing(migration.as_applied());
                 }
             } else {
                 to_be_applied.push(migration);
@@ -75,19 +83,52 @@ pub(crate) fn check_missing_divergent(
     Ok(to_be_applied)
 }
 
-pub(crate) const ASSERT_MIGRATIONS_TABLE: &str =
-    "CREATE TABLE IF NOT EXISTS refinery_schema_history( \
-             version INT4 PRIMARY KEY,\
-             name VARCHAR(255),\
-             applied_on VARCHAR(255),
-             checksum VARCHAR(255));";
+/// Default name of the table where applied migrations are kept
+pub const DEFAULT_MIGRATION_TABLE_NAME: &str = "refinery_schema_history";
+
+// the migrations of each namespace, in the order their namespaces are first found
+pub(crate) fn group_by_namespace(migrations: &[Migration]) -> Vec<(String, Vec<Migration>)> {
+    let mut groups: Vec<(String, Vec<Migration>)> = Vec::new();
+    for migration in migrations {
+        match groups
+            .iter_mut()
+            .find(|(namespace, _)| namespace == migration.namespace())
+        {
+            Some((_, group)) => group.push(migration.clone()),
+            None => groups.push((migration.namespace().into(), vec![migration.clone()])),
+        }
+    }
+    groups
+}
+
+// the value returned by a COUNT(*) query, on the first column of its only row
+pub(crate) fn parse_count(rows: &[Vec<Option<String>>]) -> Option<usize> {
+    rows.first()
+        .and_then(|row| row.first())
+        .and_then(Option::as_deref)
+        .and_then(|count| count.parse().ok())
+}
+
+// Err if the count couldn't be read, rather than taking it as 0
+pub(crate) fn expect_count(count: Option<usize>) -> Result<usize, Error> {
+    count.ok_or_else(|| {
+        Error::Connection(
+            "error reading count".into(),
+            "a COUNT(*) query returned no count".into(),
+        )
+    })
+}
 
-pub(crate) const GET_APPLIED_MIGRATIONS: &str = "SELECT version, name, applied_on, checksum \
-     FROM refinery_schema_history ORDER BY version ASC;";
+// identifies the runner holding the lock on the migrations table, by its process and the time it took the lock
+pub(crate) fn lock_owner() -> String {
+    format!("{}@{}", std::process::id(), Local::now().to_rfc3339())
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{check_missing_divergent, AppliedMigration, Error, Migration};
+    use super::{
+        check_missing_divergent, group_by_namespace, AppliedMigration, Error, Migration, Report,
+    };
 
     fn get_migrations() -> Vec<Migration> {
         let migration1 = Migration::from_filename(
@@ -125,7 +166,14 @@ mod tests {
     fn check_missing_divergent_returns_all_migrations_if_applied_are_empty() {
         let migrations = get_migrations();
         let applied: Vec<AppliedMigration> = Vec::new();
-        let result = check_missing_divergent(applied, migrations.clone(), true, true).unwrap();
+        let result = check_missing_divergent(
+            applied,
+            migrations.clone(),
+            true,
+            true,
+            &mut Report::default(),
+        )
+        .unwrap();
         assert_eq!(migrations, result);
     }
 
@@ -138,7 +186,9 @@ mod tests {
             migrations[2].clone().as_applied(),
         ];
         let remaining = vec![migrations[3].clone()];
-        let result = check_missing_divergent(applied, migrations, true, true).unwrap();
+        let result =
+            check_missing_divergent(applied, migrations, true, true, &mut Report::default())
+                .unwrap();
         assert_eq!(remaining, result);
     }
 
@@ -152,7 +202,8 @@ mod tests {
         ];
         applied[2].checksum = "3a6d3a3".into();
         let migration = migrations[2].clone();
-        let err = check_missing_divergent(applied, migrations, true, true).unwrap_err();
+        let err = check_missing_divergent(applied, migrations, true, true, &mut Report::default())
+            .unwrap_err();
         match err {
             Error::DivergentVersion(applied, divergent) => {
                 assert_eq!(migration, divergent);
@@ -172,10 +223,29 @@ mod tests {
         ];
         applied[2].checksum = "3a6d3a3".into();
         let remaining = vec![migrations[3].clone()];
-        let result = check_missing_divergent(applied, migrations, false, true).unwrap();
+        let result =
+            check_missing_divergent(applied, migrations, false, true, &mut Report::default())
+                .unwrap();
         assert_eq!(remaining, result);
     }
 
+    #[test]
+    fn check_missing_divergent_reports_skipped_and_divergent() {
+        let migrations = get_migrations();
+        let mut applied: Vec<AppliedMigration> = vec![
+            migrations[0].clone().as_applied(),
+            migrations[1].clone().as_applied(),
+            migrations[2].clone().as_applied(),
+        ];
+        applied[2].checksum = "3a6d3a3".into();
+        let mut report = Report::default();
+        check_missing_divergent(applied, migrations.clone(), false, true, &mut report).unwrap();
+        assert_eq!(&migrations[..2], report.skipped_migrations());
+        assert_eq!(1, report.divergent_migrations().len());
+        assert_eq!(migrations[2], report.divergent_migrations()[0].1);
+        assert!(report.missing_migrations().is_empty());
+    }
+
     #[test]
     fn check_missing_divergent_fails_on_missing_on_applied() {
         let migrations = get_migrations();
@@ -184,7 +254,8 @@ mod tests {
             migrations[2].clone().as_applied(),
         ];
         let migration = migrations[1].clone();
-        let err = check_missing_divergent(applied, migrations, true, true).unwrap_err();
+        let err = check_missing_divergent(applied, migrations, true, true, &mut Report::default())
+            .unwrap_err();
         match err {
             Error::MissingVersion(missing) => {
                 assert_eq!(migration.as_applied(), missing);
@@ -202,7 +273,8 @@ mod tests {
             migrations[2].clone().as_applied(),
         ];
         let migration = migrations.remove(1);
-        let err = check_missing_divergent(applied, migrations, true, true).unwrap_err();
+        let err = check_missing_divergent(applied, migrations, true, true, &mut Report::default())
+            .unwrap_err();
         match err {
             Error::MissingVersion(missing) => {
                 assert_eq!(migration.as_applied(), missing);
@@ -219,7 +291,9 @@ mod tests {
             migrations[2].clone().as_applied(),
         ];
         let remaining = vec![migrations[3].clone()];
-        let result = check_missing_divergent(applied, migrations, true, false).unwrap();
+        let result =
+            check_missing_divergent(applied, migrations, true, false, &mut Report::default())
+                .unwrap();
         assert_eq!(remaining, result);
     }
 
@@ -233,7 +307,53 @@ mod tests {
         ];
         migrations.remove(1);
         let remaining = vec![migrations[2].clone()];
-        let result = check_missing_divergent(applied, migrations, true, false).unwrap();
+        let result =
+            check_missing_divergent(applied, migrations, true, false, &mut Report::default())
+                .unwrap();
         assert_eq!(remaining, result);
     }
+
+    #[test]
+    fn groups_migrations_by_namespace() {
+        let migrations = get_migrations();
+        let plugin = migrations[0].clone().set_namespace("plugin").unwrap();
+        let groups =
+            group_by_namespace(&[migrations[0].clone(), plugin.clone(), migrations[1].clone()]);
+        assert_eq!(
+            vec![
+                (
+                    String::new(),
+                    vec![migrations[0].clone(), migrations[1].clone()]
+                ),
+                ("plugin".to_string(), vec![plugin]),
+            ],
+            groups
+        );
+        assert!(group_by_namespace(&[]).is_empty());
+    }
+
+    #[test]
+    fn orders_migrations_with_the_same_version_by_namespace() {
+        let migrations = get_migrations();
+        let plugin = migrations[0].clone().set_namespace("plugin").unwrap();
+        assert_ne!(migrations[0], plugin);
+        assert_eq!(std::cmp::Ordering::Less, migrations[0].cmp(&plugin));
+        assert_eq!(std::cmp::Ordering::Less, plugin.cmp(&migrations[1]));
+        assert_eq!(
+            std::cmp::Ordering::Equal,
+            plugin.cmp(&migrations[0].clone().set_namespace("plugin").unwrap())
+        );
+    }
+
+    #[test]
+    fn set_namespace_fails_on_invalid_namespace() {
+        let err = get_migrations()[0]
+            .clone()
+            .set_namespace("my-plugin")
+            .unwrap_err();
+        match err {
+            Error::InvalidNamespace(namespace) => assert_eq!("my-plugin", namespace),
+            _ => panic!("failed test"),
+        }
+    }
 }
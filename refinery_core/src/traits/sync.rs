@@ -1,96 +1,475 @@
+use crate::callbacks::Callback;
+use crate::dialect::Dialect;
 use crate::error::WrapMigrationError;
-use crate::traits::{check_missing_divergent, ASSERT_MIGRATIONS_TABLE, GET_APPLIED_MIGRATIONS};
-use crate::{AppliedMigration, Error, Migration};
-use chrono::Local;
+use crate::runner::MigrationFn;
+use crate::traits::{
+    check_missing_divergent, expect_count, group_by_namespace, lock_owner, parse_count,
+};
+use crate::{AppliedMigration, Error, Migration, Report, Runner};
 
-pub trait Transaction {
+/// A connection refinery can migrate, implemented by the sync drivers and open to connection types of other crates,
+/// which can check their implementation with the [`conformance`] suite.
+///
+/// Transactions are begun, committed and rolled back on the connection itself, queries given to [`execute`]
+/// run on the transaction begun, if any. Drivers of connections that already are on a transaction nest theirs with savepoints.
+///
+/// [`conformance`]: conformance/index.html
+/// [`execute`]: #tymethod.execute
+pub trait Driver {
     type Error: std::error::Error + Send + Sync + 'static;
+    /// The SQL dialect of the database, producing the statements run on the migrations table
+    type Dialect: Dialect;
 
+    fn begin(&mut self) -> Result<(), Self::Error>;
+
+    fn commit(&mut self) -> Result<(), Self::Error>;
+
+    fn rollback(&mut self) -> Result<(), Self::Error>;
+
+    /// Executes the queries, on the transaction begun if any, returning how many were executed
     fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error>;
+
+    /// Executes the queries outside of the transaction begun, each one committed on its own.
+    /// Used for statements that aren't undone along with the migrations, like creating the migrations table
+    fn execute_no_tx(&mut self, queries: &[&str]) -> Result<usize, Self::Error>;
+
+    /// Runs a query returning its rows, with every column value as text, or `None` if it is NULL
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Self::Error>;
+
+    /// Runs a `SELECT COUNT(*) AS count` query returning the count, `None` if it can't be read.
+    /// Reads it from the rows returned by [`query`] by default,
+    /// drivers that can't run queries with unknown columns read the `count` column instead
+    ///
+    /// [`query`]: #tymethod.query
+    fn query_count(&mut self, query: &str) -> Result<Option<usize>, Self::Error> {
+        self.query(query).map(|rows| parse_count(&rows))
+    }
+
+    /// Selects the migrations applied on the migrations table, ordered by namespace and version
+    fn query_history(
+        &mut self,
+        migration_table_name: &str,
+    ) -> Result<Vec<AppliedMigration>, Self::Error>;
+
+    /// Inserts an applied migration on the migrations table, on the transaction begun.
+    /// Runs the dialect's insert query by default, drivers able to bind parameters can bind them instead
+    fn insert_history(
+        &mut self,
+        migration: &Migration,
+        migration_table_name: &str,
+    ) -> Result<(), Self::Error> {
+        let query = Self::Dialect::insert_migration_query(migration, migration_table_name);
+        self.execute(&[&query]).map(|_| ())
+    }
 }
 
-pub trait Query<T>: Transaction {
-    fn query(&mut self, query: &str) -> Result<Option<T>, Self::Error>;
+/// Executes queries inside a transaction, given to Rust code migrations and [`Callback`] hooks.
+/// The transaction is committed by refinery after the migration or hook returns successfully,
+/// and rolled back otherwise.
+///
+/// [`Callback`]: trait.Callback.html
+pub trait Executor {
+    fn execute(&mut self, queries: &[&str]) -> Result<usize, Error>;
+
+    /// Runs a query returning its rows, with every column value as text, or `None` if it is NULL
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Error>;
+
+    fn commit(self: Box<Self>) -> Result<(), Error>;
 }
 
-fn migrate<T: Transaction>(transaction: &mut T, migrations: Vec<Migration>) -> Result<(), Error> {
-    for migration in migrations.iter() {
-        log::info!("applying migration: {}", migration);
-        let update_query = &format!(
-                "INSERT INTO refinery_schema_history (version, name, applied_on, checksum) VALUES ({}, '{}', '{}', '{}')",
-                migration.version, migration.name, Local::now().to_rfc3339(), migration.checksum().to_string());
+/// Connections able to begin a transaction given as an [`Executor`],
+/// needed to run Rust code migrations and callbacks. Implemented for every [`Driver`]
+///
+/// [`Executor`]: trait.Executor.html
+/// [`Driver`]: trait.Driver.html
+pub trait Transactional {
+    fn begin(&mut self) -> Result<Box<dyn Executor + '_>, Error>;
+}
+
+// the transaction begun on a driver, given as an Executor to migrations and callbacks,
+// rolled back when dropped without being committed
+struct DriverTransaction<'a, D: Driver> {
+    driver: &'a mut D,
+    committed: bool,
+}
+
+impl<'a, D: Driver> DriverTransaction<'a, D> {
+    fn begin(driver: &'a mut D) -> Result<DriverTransaction<'a, D>, Error> {
+        driver.begin().migration_err("error starting transaction")?;
+        Ok(DriverTransaction {
+            driver,
+            committed: false,
+        })
+    }
+
+    fn commit(mut self) -> Result<(), Error> {
+        self.committed = true;
+        self.driver
+            .commit()
+            .migration_err("error committing transaction")
+    }
+}
+
+impl<D: Driver> Executor for DriverTransaction<'_, D> {
+    fn execute(&mut self, queries: &[&str]) -> Result<usize, Error> {
+        self.driver
+            .execute(queries)
+            .migration_err("error executing queries")
+    }
+
+    fn query(&mut self, query: &str) -> Result<Vec<Vec<Option<String>>>, Error> {
+        self.driver
+            .query(query)
+            .migration_err("error running query")
+    }
+
+    fn commit(self: Box<Self>) -> Result<(), Error> {
+        DriverTransaction::commit(*self)
+    }
+}
+
+impl<D: Driver> Drop for DriverTransaction<'_, D> {
+    fn drop(&mut self) {
+        if !self.committed {
+            if let Err(err) = self.driver.rollback() {
+                log::error!("error rolling back transaction: {}", err);
+            }
+        }
+    }
+}
+
+impl<D: Driver> Transactional for D {
+    fn begin(&mut self) -> Result<Box<dyn Executor + '_>, Error> {
+        Ok(Box::new(DriverTransaction::begin(self)?))
+    }
+}
+
+// executes the queries on a transaction of their own
+fn execute_in_transaction<D: Driver>(driver: &mut D, queries: &[&str]) -> Result<usize, D::Error> {
+    driver.begin()?;
+    match driver.execute(queries) {
+        Ok(count) => {
+            driver.commit()?;
+            Ok(count)
+        }
+        Err(err) => {
+            if let Err(rollback_err) = driver.rollback() {
+                log::error!("error rolling back transaction: {}", rollback_err);
+            }
+            Err(err)
+        }
+    }
+}
+
+// invokes the given hook of every callback on a new transaction, stopping on the first one that fails
+fn run_callbacks<D, F>(driver: &mut D, callbacks: &[&dyn Callback], hook: F) -> Result<(), Error>
+where
+    D: Driver,
+    F: Fn(&dyn Callback, &mut dyn Executor) -> Result<(), Error>,
+{
+    if callbacks.is_empty() {
+        return Ok(());
+    }
+    let mut transaction = DriverTransaction::begin(driver)?;
+    for callback in callbacks {
+        hook(*callback, &mut transaction)?;
+    }
+    transaction.commit()
+}
+
+// applies the migrations along with their rows on the migrations table in a single transaction,
+// Rust code migrations run their code with the transaction's Executor
+fn apply_in_transaction<D: Driver>(
+    driver: &mut D,
+    migrations: &[Migration],
+    migration_table_name: &str,
+) -> Result<(), Error> {
+    let mut transaction = DriverTransaction::begin(driver)?;
+    for migration in migrations {
+        match migration.code {
+            Some(MigrationFn::Sync(code)) => code(&mut transaction).map_err(|err| {
+                Error::Connection(format!("error applying migration {}", migration), err)
+            })?,
+            Some(_) => return Err(Error::IncompatibleMigration(migration.clone(), "sync")),
+            None => {
+                transaction
+                    .driver
+                    .execute(&[&migration.sql])
+                    .migration_err(&format!("error applying migration {}", migration))?;
+            }
+        }
         transaction
-            .execute(&[&migration.sql, update_query])
+            .driver
+            .insert_history(migration, migration_table_name)
             .migration_err(&format!("error applying migration {}", migration))?;
     }
-    Ok(())
+    transaction.commit()
 }
 
-fn migrate_grouped<T: Transaction>(
-    transaction: &mut T,
+fn migrate<D: Driver>(
+    driver: &mut D,
     migrations: Vec<Migration>,
+    migration_table_name: &str,
+    callbacks: &[&dyn Callback],
+    report: &mut Report,
 ) -> Result<(), Error> {
-    let mut grouped_migrations = Vec::new();
-    let mut display_migrations = Vec::new();
     for migration in migrations.into_iter() {
-        let query = format!(
-            "INSERT INTO refinery_schema_history (version, name, applied_on, checksum) VALUES ({}, '{}', '{}', '{}')",
-            migration.version, migration.name, Local::now().to_rfc3339(), migration.checksum().to_string()
+        run_callbacks(driver, callbacks, |callback, executor| {
+            callback.before_each(&migration, executor)
+        })?;
+
+        log::info!("applying migration: {}", migration);
+        let result = apply_in_transaction(
+            driver,
+            std::slice::from_ref(&migration),
+            migration_table_name,
         );
+
+        if let Err(err) = result {
+            run_callbacks(driver, callbacks, |callback, executor| {
+                callback.after_each_error(&migration, &err, executor)
+            })?;
+            return Err(err);
+        }
+
+        run_callbacks(driver, callbacks, |callback, executor| {
+            callback.after_each(&migration, executor)
+        })?;
+        report.push_applied(migration);
+    }
+    Ok(())
+}
+
+fn migrate_grouped<D: Driver>(
+    driver: &mut D,
+    migrations: Vec<Migration>,
+    migration_table_name: &str,
+    callbacks: &[&dyn Callback],
+    report: &mut Report,
+) -> Result<(), Error> {
+    let mut display_migrations = Vec::new();
+    for migration in migrations.iter() {
+        run_callbacks(driver, callbacks, |callback, executor| {
+            callback.before_each(migration, executor)
+        })?;
         display_migrations.push(migration.to_string());
-        grouped_migrations.push(migration.sql);
-        grouped_migrations.push(query);
     }
     log::info!(
         "going to apply batch migrations in single transaction: {:#?}",
         display_migrations
     );
 
-    let refs: Vec<&str> = grouped_migrations.iter().map(AsRef::as_ref).collect();
+    let result = apply_in_transaction(driver, &migrations, migration_table_name);
 
-    transaction
-        .execute(refs.as_ref())
-        .migration_err("error applying migrations")?;
+    if let Err(err) = result {
+        for migration in migrations.iter() {
+            run_callbacks(driver, callbacks, |callback, executor| {
+                callback.after_each_error(migration, &err, executor)
+            })?;
+        }
+        return Err(err);
+    }
+
+    for migration in migrations.into_iter() {
+        run_callbacks(driver, callbacks, |callback, executor| {
+            callback.after_each(&migration, executor)
+        })?;
+        report.push_applied(migration);
+    }
 
     Ok(())
 }
 
-pub trait Migrate: Query<Vec<AppliedMigration>>
+// migrates the database once the migrations table is locked
+fn migrate_locked<D: Driver>(
+    driver: &mut D,
+    migrations: &[Migration],
+    abort_divergent: bool,
+    abort_missing: bool,
+    grouped: bool,
+    migration_table_name: &str,
+    callbacks: &[&dyn Callback],
+) -> Result<Report, Error> {
+    let mut report = Report::default();
+    run_callbacks(driver, callbacks, |callback, executor| {
+        callback.before_migrate(executor)
+    })?;
+
+    driver
+        .execute_no_tx(&[&D::Dialect::assert_migrations_table_query(
+            migration_table_name,
+        )])
+        .migration_err("error asserting migrations table")?;
+    let backup_tables = driver
+        .query_count(&D::Dialect::backup_table_query(migration_table_name))
+        .migration_err("error looking up migrations table backup")?;
+    if expect_count(backup_tables)? > 0 {
+        return Err(Error::MigrationsTableBackup(migration_table_name.into()));
+    }
+    let namespace_columns = driver
+        .query_count(&D::Dialect::namespace_column_query(migration_table_name))
+        .migration_err("error looking up migrations table columns")?;
+    if expect_count(namespace_columns)? == 0 {
+        log::info!("adding the namespace column to {}", migration_table_name);
+        let queries = D::Dialect::upgrade_migrations_table_queries(migration_table_name);
+        let refs: Vec<&str> = queries.iter().map(AsRef::as_ref).collect();
+        if D::Dialect::supports_grouped_migrations() {
+            execute_in_transaction(driver, &refs)
+                .migration_err("error upgrading migrations table")?;
+        } else {
+            // a backup left behind if one of them fails is found by the next run
+            for query in refs {
+                execute_in_transaction(driver, &[query])
+                    .migration_err("error upgrading migrations table")?;
+            }
+        }
+    }
+
+    let applied_migrations = driver
+        .query_history(migration_table_name)
+        .migration_err("error getting current schema version")?;
+
+    let mut to_be_applied = Vec::new();
+    // each namespace has its own version sequence, and is checked only against the migrations applied on it
+    for (namespace, migrations) in group_by_namespace(migrations) {
+        let applied = applied_migrations
+            .iter()
+            .filter(|applied| applied.namespace == namespace)
+            .cloned()
+            .collect();
+        to_be_applied.extend(check_missing_divergent(
+            applied,
+            migrations,
+            abort_divergent,
+            abort_missing,
+            &mut report,
+        )?);
+    }
+    let migrations = to_be_applied;
+
+    if migrations.is_empty() {
+        log::info!("no migrations to apply");
+    }
+
+    if grouped {
+        migrate_grouped(
+            driver,
+            migrations,
+            migration_table_name,
+            callbacks,
+            &mut report,
+        )?
+    } else {
+        migrate(
+            driver,
+            migrations,
+            migration_table_name,
+            callbacks,
+            &mut report,
+        )?
+    }
+
+    run_callbacks(driver, callbacks, |callback, executor| {
+        callback.after_migrate(&report, executor)
+    })?;
+
+    Ok(report)
+}
+
+// takes the lock on the migrations table for owner, Err if another runner holds it
+fn lock<D: Driver>(driver: &mut D, migration_table_name: &str, owner: &str) -> Result<(), Error> {
+    let query = match D::Dialect::lock_migrations_table_query(migration_table_name, owner) {
+        Some(query) => query,
+        None => return Ok(()),
+    };
+    driver
+        .execute_no_tx(&[&query])
+        .migration_err("error locking migrations table")?;
+    if let Some(query) = D::Dialect::held_lock_query(migration_table_name, owner) {
+        let held = driver
+            .query_count(&query)
+            .migration_err("error locking migrations table")?;
+        if expect_count(held)? == 0 {
+            return Err(Error::MigrationsTableLocked(migration_table_name.into()));
+        }
+    }
+    Ok(())
+}
+
+// releases the lock on the migrations table after the migration ended, whether it succeeded or not,
+// an error unlocking is only returned if the migration succeeded
+fn unlock<D: Driver>(
+    driver: &mut D,
+    migration_table_name: &str,
+    owner: &str,
+    result: Result<Report, Error>,
+) -> Result<Report, Error> {
+    let query = match D::Dialect::unlock_migrations_table_query(migration_table_name, owner) {
+        Some(query) => query,
+        None => return result,
+    };
+    let unlocked = driver
+        .execute_no_tx(&[&query])
+        .migration_err("error unlocking migrations table");
+    match (result, unlocked) {
+        (Ok(report), Ok(_)) => Ok(report),
+        (Ok(_), Err(err)) => Err(err),
+        (Err(err), unlocked) => {
+            if let Err(unlock_err) = unlocked {
+                log::error!("{}", unlock_err);
+            }
+            Err(err)
+        }
+    }
+}
+
+pub trait Migrate: Driver
 where
     Self: Sized,
 {
+    /// Migrates the connection with `migrations`, on the default migrations table,
+    /// see [`Migrate::migrate_with_runner`] for the other settings of a [`Runner`]
+    ///
+    /// [`Migrate::migrate_with_runner`]: trait.Migrate.html#method.migrate_with_runner
+    /// [`Runner`]: struct.Runner.html
     fn migrate(
         &mut self,
         migrations: &[Migration],
         abort_divergent: bool,
         abort_missing: bool,
         grouped: bool,
-    ) -> Result<(), Error> {
-        self.execute(&[ASSERT_MIGRATIONS_TABLE])
-            .migration_err("error asserting migrations table")?;
-
-        let applied_migrations = self
-            .query(GET_APPLIED_MIGRATIONS)
-            .migration_err("error getting current schema version")?
-            .unwrap_or_default();
-
-        let migrations = check_missing_divergent(
-            applied_migrations,
-            migrations.to_vec(),
-            abort_divergent,
-            abort_missing,
-        )?;
-
-        if migrations.is_empty() {
-            log::info!("no migrations to apply");
-        }
+    ) -> Result<Report, Error> {
+        let runner = Runner::new(migrations)
+            .set_abort_divergent(abort_divergent)
+            .set_abort_missing(abort_missing)
+            .set_grouped(grouped);
+        self.migrate_with_runner(&runner)
+    }
 
-        if grouped {
-            migrate_grouped(self, migrations)
-        } else {
-            migrate(self, migrations)
+    /// Migrates the connection with the migrations and settings of `runner`, see [`Runner::run`]
+    ///
+    /// [`Runner::run`]: struct.Runner.html#method.run
+    fn migrate_with_runner(&mut self, runner: &Runner) -> Result<Report, Error> {
+        let migration_table_name = runner.get_migration_table_name()?;
+        let migrations = runner.get_migrations()?;
+        let sql_callbacks = runner.get_sql_callbacks()?;
+        if runner.grouped && !Self::Dialect::supports_grouped_migrations() {
+            return Err(Error::GroupedMigrationsNotSupported);
         }
+        // only one runner migrates the database at a time, the others fail while it holds the lock
+        let owner = lock_owner();
+        lock(self, migration_table_name, &owner)?;
+        let result = migrate_locked(
+            self,
+            &migrations,
+            runner.abort_divergent,
+            runner.abort_missing,
+            runner.grouped,
+            migration_table_name,
+            &runner.get_callbacks(&sql_callbacks),
+        );
+        unlock(self, migration_table_name, &owner, result)
     }
 }
 
-impl<T: Query<Vec<AppliedMigration>>> Migrate for T {}
+impl<D: Driver> Migrate for D {}
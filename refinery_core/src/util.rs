@@ -1,4 +1,6 @@
-use crate::Error;
+use crate::callbacks::CALLBACK_NAME_RE;
+use crate::{Error, Migration, SqlCallback};
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use walkdir::{DirEntry, WalkDir};
@@ -6,38 +8,165 @@ use walkdir::{DirEntry, WalkDir};
 use regex::Regex;
 
 /// enum containing the migration types used to search for migrations
-/// either Rust Modules or Sql files
+/// either Rust Modules, Sql files or Sql callback files
+#[derive(Clone, Copy, Debug)]
 pub enum MigrationType {
     Mod,
     Sql,
+    Callback,
 }
 
 impl MigrationType {
     fn file_match_re(&self) -> Regex {
-        let ext = match self {
-            MigrationType::Mod => "rs",
-            MigrationType::Sql => "sql",
+        let re_str = match self {
+            MigrationType::Mod => r"^(V)(\d+(?:\.\d+)?)__(\w+)\.rs$".to_string(),
+            MigrationType::Sql => r"^(V)(\d+(?:\.\d+)?)__(\w+)\.sql$".to_string(),
+            MigrationType::Callback => format!(r"^{}\.sql$", CALLBACK_NAME_RE),
         };
-        let re_str = format!(r"^(V)(\d+(?:\.\d+)?)__(\w+)\.{}$", ext);
         Regex::new(re_str.as_str()).unwrap()
     }
 }
 
-/// find migrations on file system recursively across directories given a location and [MigrationType]
-pub fn find_migration_files(
+/// name of the file, at the root of a migrations location, listing the paths to skip when searching for migrations
+pub const IGNORE_FILE_NAME: &str = ".refineryignore";
+
+/// Options to search for migration files.
+/// By default symbolic links are not followed and subdirectories are searched with no depth limit.
+/// Paths matching the patterns of a [`IGNORE_FILE_NAME`] file at the root of the location are always skipped,
+/// one pattern per line, relative to the location, where `*` and `?` match within a path component and `**` across them.
+/// Patterns without a `/` match any file or directory with that name, and lines starting with `#` are comments.
+///
+/// [`IGNORE_FILE_NAME`]: constant.IGNORE_FILE_NAME.html
+#[derive(Clone, Debug, Default)]
+pub struct FindOptions {
+    follow_links: bool,
+    max_depth: Option<usize>,
+}
+
+impl FindOptions {
+    pub fn get_follow_links(&self) -> bool {
+        self.follow_links
+    }
+
+    pub fn get_max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Set true to follow symbolic links to files and directories
+    pub fn set_follow_links(self, follow_links: bool) -> FindOptions {
+        FindOptions {
+            follow_links,
+            ..self
+        }
+    }
+
+    /// Set the depth of the subdirectories searched, with 1 only searching the files directly on the location
+    pub fn set_max_depth(self, max_depth: Option<usize>) -> FindOptions {
+        FindOptions { max_depth, ..self }
+    }
+}
+
+// converts an ignore file pattern to a regex matched against '/' separated paths relative to the location,
+// matching a directory also matches everything below it
+fn ignore_pattern_re(pattern: &str) -> Result<Regex, regex::Error> {
+    let pattern = pattern.trim_end_matches('/');
+    // patterns with a '/' other than a trailing one are relative to the location
+    let anchored = pattern.contains('/');
+    let pattern = pattern.trim_start_matches('/');
+
+    let mut re = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                re.push_str(".*");
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push_str("(?:/.*)?$");
+    Regex::new(&re)
+}
+
+fn load_ignore_patterns(location: &Path) -> Result<Vec<Regex>, Error> {
+    let ignore_file = location.join(IGNORE_FILE_NAME);
+    if !ignore_file.is_file() {
+        return Ok(Vec::new());
+    }
+
+    let contents = std::fs::read_to_string(&ignore_file)
+        .map_err(|err| Error::InvalidMigrationPath(ignore_file.clone(), err))?;
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            ignore_pattern_re(line).map_err(|err| {
+                Error::ConfigError(format!(
+                    "invalid pattern {} on {}, {}",
+                    line,
+                    ignore_file.display(),
+                    err
+                ))
+            })
+        })
+        .collect()
+}
+
+/// walks the files on location with the given [FindOptions], skipping the ones matching its ignore file
+pub fn walk_migrations_dir(
     location: impl AsRef<Path>,
-    migration_type: MigrationType,
+    options: &FindOptions,
 ) -> Result<impl Iterator<Item = PathBuf>, Error> {
-    let re = migration_type.file_match_re();
     let location: &Path = location.as_ref();
     let location = location
         .canonicalize()
         .map_err(|err| Error::InvalidMigrationPath(location.to_path_buf(), err))?;
+    let ignore_patterns = load_ignore_patterns(&location)?;
 
-    let file_paths = WalkDir::new(location)
+    let mut walker = WalkDir::new(&location).follow_links(options.follow_links);
+    if let Some(max_depth) = options.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+
+    let file_paths = walker
         .into_iter()
+        .filter_entry(move |entry| {
+            //safe to call unwrap as every entry is below location
+            let relative = entry.path().strip_prefix(&location).unwrap();
+            let relative: Vec<_> = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect();
+            let relative = relative.join("/");
+            relative.is_empty() || !ignore_patterns.iter().any(|re| re.is_match(&relative))
+        })
         .filter_map(Result::ok)
-        .map(DirEntry::into_path)
+        .filter(|entry| !entry.file_type().is_dir())
+        .map(DirEntry::into_path);
+
+    Ok(file_paths)
+}
+
+/// find migrations on file system recursively across directories given a location and [MigrationType]
+pub fn find_migration_files(
+    location: impl AsRef<Path>,
+    migration_type: MigrationType,
+) -> Result<impl Iterator<Item = PathBuf>, Error> {
+    find_migration_files_with(location, migration_type, &FindOptions::default())
+}
+
+/// find migrations on file system given a location, [MigrationType] and [FindOptions]
+pub fn find_migration_files_with(
+    location: impl AsRef<Path>,
+    migration_type: MigrationType,
+    options: &FindOptions,
+) -> Result<impl Iterator<Item = PathBuf>, Error> {
+    let re = migration_type.file_match_re();
+    let file_paths = walk_migrations_dir(location, options)?
         // filter by migration file regex
         .filter(
             move |entry| match entry.file_name().and_then(OsStr::to_str) {
@@ -49,11 +178,112 @@ pub fn find_migration_files(
     Ok(file_paths)
 }
 
+/// load the .sql migration files found on the given locations, sorted by version,
+/// returns `Err` if two files have the same version
+pub(crate) fn load_sql_migrations(
+    locations: &[PathBuf],
+    options: &FindOptions,
+) -> Result<Vec<Migration>, Error> {
+    let mut migrations = Vec::new();
+    let mut versions: HashMap<usize, PathBuf> = HashMap::new();
+    for location in locations {
+        for path in find_migration_files_with(location, MigrationType::Sql, options)? {
+            let sql = std::fs::read_to_string(path.as_path())
+                .map_err(|err| Error::InvalidMigrationPath(path.clone(), err))?;
+
+            //safe to call unwrap as find_migration_filenames returns canonical paths
+            let filename = path
+                .file_stem()
+                .and_then(|file| file.to_os_string().into_string().ok())
+                .unwrap();
+
+            let migration = Migration::from_filename(&filename, &sql)
+                .map_err(|err| Error::InvalidMigrationFile(path.clone(), Box::new(err)))?;
+            if let Some(other) = versions.insert(migration.version, path.clone()) {
+                return Err(Error::DuplicateVersion(migration.version, other, path));
+            }
+            migrations.push(migration);
+        }
+    }
+    migrations.sort();
+    Ok(migrations)
+}
+
+/// load the .sql callback files found on the given locations, sorted by name
+pub(crate) fn load_sql_callbacks(
+    locations: &[PathBuf],
+    options: &FindOptions,
+) -> Result<Vec<SqlCallback>, Error> {
+    let mut callbacks = Vec::new();
+    for location in locations {
+        for path in find_migration_files_with(location, MigrationType::Callback, options)? {
+            let sql = std::fs::read_to_string(path.as_path())
+                .map_err(|err| Error::InvalidMigrationPath(path.clone(), err))?;
+
+            //safe to call unwrap as find_migration_filenames returns canonical paths
+            let filename = path
+                .file_stem()
+                .and_then(|file| file.to_os_string().into_string().ok())
+                .unwrap();
+
+            let callback = SqlCallback::from_filename(&filename, &sql)
+                .map_err(|err| Error::InvalidMigrationFile(path.clone(), Box::new(err)))?;
+            callbacks.push(callback);
+        }
+    }
+    callbacks.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(callbacks)
+}
+
+/// Tells cargo to run the build script again when files are added to or removed from the migrations on `location`,
+/// to be called from the `build.rs` of the crate using [`embed_migrations!`] or [`include_migration_mods!`].
+/// Those macros can only make cargo track the files that existed when they were expanded,
+/// so without it new migrations are only embedded on the next clean build.
+///
+/// The `cargo:rerun-if-changed` lines are only read by cargo from the output of a build script, calling it anywhere else does nothing.
+/// They are printed for `location` and the directories below it, not for the files on them, as adding, removing or renaming a file
+/// changes the modification time of the directory it's on, while cargo checks the files embedded by the macros on its own.
+/// Once a build script prints any `cargo:rerun-if-changed` line, cargo stops running it again when any file of the package changes,
+/// and only does when the printed paths do, so a build script relying on that has to print its other inputs as well,
+/// i.e. `println!("cargo:rerun-if-changed=build.rs")`.
+///
+/// [`embed_migrations!`]: macro.embed_migrations.html
+/// [`include_migration_mods!`]: macro.include_migration_mods.html
+pub fn rerun_if_migrations_changed(location: impl AsRef<Path>) -> Result<(), Error> {
+    for path in rerun_if_changed_paths(location.as_ref())? {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+    Ok(())
+}
+
+// the location and every directory below it, as the modification time of a directory
+// changes when entries are added to or removed from it
+fn rerun_if_changed_paths(location: &Path) -> Result<Vec<PathBuf>, Error> {
+    let location = location
+        .canonicalize()
+        .map_err(|err| Error::InvalidMigrationPath(location.to_path_buf(), err))?;
+
+    let mut paths: Vec<PathBuf> = WalkDir::new(location)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_dir())
+        .map(DirEntry::into_path)
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{find_migration_files, MigrationType};
+    use super::{
+        find_migration_files, find_migration_files_with, rerun_if_changed_paths, FindOptions,
+        MigrationType,
+    };
+    use crate::{Error, Migration};
     use std::fs;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
+    use std::thread;
+    use std::time::Duration;
     use tempfile::TempDir;
 
     #[test]
@@ -119,4 +349,181 @@ mod tests {
         let mut mods = find_migration_files(migrations_dir, MigrationType::Sql).unwrap();
         assert!(mods.next().is_none());
     }
+
+    #[test]
+    fn finds_sql_callbacks() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir(&migrations_dir).unwrap();
+        let callback1 = migrations_dir.join("afterMigrate__grants.sql");
+        fs::File::create(&callback1).unwrap();
+        let callback2 = migrations_dir.join("beforeEach.sql");
+        fs::File::create(&callback2).unwrap();
+        fs::File::create(migrations_dir.join("V1__first.sql")).unwrap();
+        fs::File::create(migrations_dir.join("afterAll.sql")).unwrap();
+
+        let mut callbacks: Vec<PathBuf> =
+            find_migration_files(migrations_dir, MigrationType::Callback)
+                .unwrap()
+                .collect();
+        callbacks.sort();
+        assert_eq!(2, callbacks.len());
+        assert_eq!(callback1.canonicalize().unwrap(), callbacks[0]);
+        assert_eq!(callback2.canonicalize().unwrap(), callbacks[1]);
+    }
+
+    #[test]
+    fn reruns_if_any_migrations_dir_changed() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        let nested_dir = migrations_dir.join("V1-2");
+        fs::create_dir_all(&nested_dir).unwrap();
+        fs::File::create(migrations_dir.join("V3__third.sql")).unwrap();
+        fs::File::create(nested_dir.join("V1__first.sql")).unwrap();
+
+        let paths = rerun_if_changed_paths(&migrations_dir).unwrap();
+        assert_eq!(
+            vec![
+                migrations_dir.canonicalize().unwrap(),
+                nested_dir.canonicalize().unwrap()
+            ],
+            paths
+        );
+    }
+
+    #[test]
+    fn reruns_when_a_migration_is_added_to_a_nested_dir() {
+        let tmp_dir = TempDir::new().unwrap();
+        let migrations_dir = tmp_dir.path().join("migrations");
+        let nested_dir = migrations_dir.join("V1-2");
+        fs::create_dir_all(&nested_dir).unwrap();
+        fs::File::create(nested_dir.join("V1__first.sql")).unwrap();
+
+        let modified = |path: &Path| fs::metadata(path).unwrap().modified().unwrap();
+        let paths = rerun_if_changed_paths(&migrations_dir).unwrap();
+        let nested_dir = nested_dir.canonicalize().unwrap();
+        assert!(paths.contains(&nested_dir));
+        let before = modified(&nested_dir);
+
+        thread::sleep(Duration::from_millis(50));
+        fs::File::create(nested_dir.join("V2__second.sql")).unwrap();
+        assert!(modified(&nested_dir) > before);
+    }
+
+    #[test]
+    fn loads_sql_migrations_sorted_by_version() {
+        let tmp_dir = TempDir::new().unwrap();
+        let nested_dir = tmp_dir.path().join("V1-2");
+        fs::create_dir(&nested_dir).unwrap();
+        fs::write(tmp_dir.path().join("V3__third.sql"), "SELECT 3;").unwrap();
+        fs::write(nested_dir.join("V2__second.sql"), "SELECT 2;").unwrap();
+        fs::write(nested_dir.join("V1__first.sql"), "SELECT 1;").unwrap();
+
+        let migrations = Migration::load_dir(tmp_dir.path()).unwrap();
+        let versions: Vec<usize> = migrations.iter().map(|m| m.version).collect();
+        assert_eq!(vec![1, 2, 3], versions);
+        assert_eq!("SELECT 2;", migrations[1].sql);
+    }
+
+    #[test]
+    fn fails_loading_sql_migrations_with_the_invalid_file_path() {
+        let tmp_dir = TempDir::new().unwrap();
+        let invalid = tmp_dir.path().join("V1.5__first.sql");
+        fs::write(&invalid, "SELECT 1;").unwrap();
+
+        match Migration::load_dir(tmp_dir.path()).unwrap_err() {
+            Error::InvalidMigrationFile(path, err) => {
+                assert_eq!(invalid.canonicalize().unwrap(), path);
+                match *err {
+                    Error::InvalidVersion => {}
+                    _ => panic!("test failed"),
+                }
+            }
+            _ => panic!("test failed"),
+        }
+    }
+
+    #[test]
+    fn skips_paths_matching_ignore_file_patterns() {
+        let tmp_dir = TempDir::new().unwrap();
+        let drafts_dir = tmp_dir.path().join("drafts");
+        fs::create_dir(&drafts_dir).unwrap();
+        let sql1 = tmp_dir.path().join("V1__first.sql");
+        fs::File::create(&sql1).unwrap();
+        fs::File::create(drafts_dir.join("V2__second.sql")).unwrap();
+        fs::File::create(tmp_dir.path().join("V3__third_wip.sql")).unwrap();
+        fs::write(
+            tmp_dir.path().join(".refineryignore"),
+            "# work in progress\ndrafts/\n*_wip.sql\n",
+        )
+        .unwrap();
+
+        let mods: Vec<PathBuf> = find_migration_files(tmp_dir.path(), MigrationType::Sql)
+            .unwrap()
+            .collect();
+        assert_eq!(vec![sql1.canonicalize().unwrap()], mods);
+    }
+
+    #[test]
+    fn limits_the_depth_of_searched_dirs() {
+        let tmp_dir = TempDir::new().unwrap();
+        let nested_dir = tmp_dir.path().join("V2");
+        fs::create_dir(&nested_dir).unwrap();
+        let sql1 = tmp_dir.path().join("V1__first.sql");
+        fs::File::create(&sql1).unwrap();
+        fs::File::create(nested_dir.join("V2__second.sql")).unwrap();
+
+        let options = FindOptions::default().set_max_depth(Some(1));
+        let mods: Vec<PathBuf> =
+            find_migration_files_with(tmp_dir.path(), MigrationType::Sql, &options)
+                .unwrap()
+                .collect();
+        assert_eq!(vec![sql1.canonicalize().unwrap()], mods);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_links_only_if_set() {
+        let tmp_dir = TempDir::new().unwrap();
+        let shared_dir = tmp_dir.path().join("shared");
+        let migrations_dir = tmp_dir.path().join("migrations");
+        fs::create_dir(&shared_dir).unwrap();
+        fs::create_dir(&migrations_dir).unwrap();
+        fs::File::create(shared_dir.join("V1__first.sql")).unwrap();
+        std::os::unix::fs::symlink(&shared_dir, migrations_dir.join("shared")).unwrap();
+
+        let mut mods = find_migration_files(&migrations_dir, MigrationType::Sql).unwrap();
+        assert!(mods.next().is_none());
+
+        let options = FindOptions::default().set_follow_links(true);
+        let mods: Vec<PathBuf> =
+            find_migration_files_with(&migrations_dir, MigrationType::Sql, &options)
+                .unwrap()
+                .collect();
+        assert_eq!(1, mods.len());
+    }
+
+    #[test]
+    fn fails_loading_sql_migrations_with_duplicate_versions() {
+        let tmp_dir = TempDir::new().unwrap();
+        let nested_dir = tmp_dir.path().join("V1");
+        fs::create_dir(&nested_dir).unwrap();
+        let sql1 = tmp_dir.path().join("V1__first.sql");
+        fs::write(&sql1, "SELECT 1;").unwrap();
+        let sql2 = nested_dir.join("V1__other.sql");
+        fs::write(&sql2, "SELECT 1;").unwrap();
+
+        match Migration::load_dir(tmp_dir.path()).unwrap_err() {
+            Error::DuplicateVersion(version, first, second) => {
+                assert_eq!(1, version);
+                let mut paths = vec![first, second];
+                paths.sort();
+                assert_eq!(
+                    vec![sql2.canonicalize().unwrap(), sql1.canonicalize().unwrap()],
+                    paths
+                );
+            }
+            _ => panic!("test failed"),
+        }
+    }
 }
@@ -6,12 +6,19 @@ use proc_macro::TokenStream;
 use proc_macro2::{Span as Span2, TokenStream as TokenStream2};
 use quote::quote;
 use quote::ToTokens;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::ffi::OsStr;
-use std::path::PathBuf;
-use syn::{parse_macro_input, Ident, LitStr};
+use std::fs;
+use std::path::{Path, PathBuf};
+use syn::parse::{ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::{Ident, Item, LitStr, Token};
 
-use refinery_core::{find_migration_files, MigrationType};
+use refinery_core::{
+    find_migration_files, walk_migrations_dir, FindOptions, Migration, MigrationType,
+};
 
 pub(crate) fn crate_root() -> PathBuf {
     let crate_root = env::var("CARGO_MANIFEST_DIR")
@@ -19,32 +26,285 @@ pub(crate) fn crate_root() -> PathBuf {
     PathBuf::from(crate_root)
 }
 
-fn migration_fn_quoted<T: ToTokens>(_migrations: Vec<T>) -> TokenStream2 {
+fn migration_fn_quoted<T: ToTokens>(
+    _migrations: Vec<T>,
+    _code_migrations: Vec<T>,
+    _callbacks: Vec<T>,
+    namespace: Option<String>,
+) -> TokenStream2 {
+    let _namespace = namespace.map(|namespace| {
+        quote! {
+            let migrations: Vec<Migration> = migrations.into_iter().map(|migration| migration.set_namespace(#namespace).unwrap()).collect();
+        }
+    });
     let result = quote! {
-        use refinery::{Migration, Runner};
-        pub fn runner() -> Runner {
+        use refinery::{Migration, Runner, SqlCallback};
+        pub fn migrations() -> Vec<Migration> {
             let quoted_migrations: Vec<(&str, String)> = vec![#(#_migrations),*];
-            let mut migrations: Vec<Migration> = Vec::new();
+            let mut migrations: Vec<Migration> = vec![#(#_code_migrations),*];
             for module in quoted_migrations.into_iter() {
                 migrations.push(Migration::from_filename(module.0, &module.1).unwrap());
             }
-            Runner::new(&migrations)
+            #_namespace
+            migrations
+        }
+        pub fn runner() -> Runner {
+            let quoted_callbacks: Vec<(&str, String)> = vec![#(#_callbacks),*];
+            let mut runner = Runner::new(&migrations());
+            for callback in quoted_callbacks.into_iter() {
+                runner = runner.add_sql_callback(SqlCallback::from_filename(callback.0, &callback.1).unwrap());
+            }
+            runner
         }
     };
     result
 }
 
+// a migrations location given to the macros, with the span of its literal to point errors at
+struct Location {
+    path: PathBuf,
+    span: Span2,
+}
+
+// the arguments given to the macros
+struct Args {
+    // the namespace set on the migrations, if given with `namespace = "name"`
+    namespace: Option<String>,
+    locations: Vec<Location>,
+}
+
+// parses an optional `namespace = "name"` followed by the comma separated locations, relative to the crate root,
+// given to the macros, using the default location when none is given
+fn parse_args(input: TokenStream, default: PathBuf) -> Result<Args, syn::Error> {
+    let parser = |input: ParseStream| {
+        let mut namespace = None;
+        if input.peek(Ident) && input.peek2(Token![=]) {
+            let key: Ident = input.parse()?;
+            if key != "namespace" {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "unknown argument, expected `namespace`",
+                ));
+            }
+            input.parse::<Token![=]>()?;
+            let literal: LitStr = input.parse()?;
+            if !Regex::new(r"^\w+$").unwrap().is_match(&literal.value()) {
+                return Err(syn::Error::new(
+                    literal.span(),
+                    "namespaces can only have alphanumeric characters and \"_\"",
+                ));
+            }
+            namespace = Some(literal.value());
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        let literals = Punctuated::<LitStr, Token![,]>::parse_terminated(input)?;
+        Ok((namespace, literals))
+    };
+    let (namespace, literals) = parser.parse(input)?;
+
+    let mut locations: Vec<Location> = literals
+        .into_iter()
+        .map(|literal| Location {
+            path: crate_root().join(literal.value()),
+            span: literal.span(),
+        })
+        .collect();
+    if locations.is_empty() {
+        locations.push(Location {
+            path: default,
+            span: Span2::call_site(),
+        });
+    }
+    Ok(Args {
+        namespace,
+        locations,
+    })
+}
+
+// checks the migrations found on locations at compile time, so that duplicate versions, also across locations,
+// invalid names or empty files fail the build instead of the generated runner when the binary starts
+fn validate_migrations(
+    locations: &[Location],
+    migration_type: MigrationType,
+) -> Result<(), syn::Error> {
+    let extension = match migration_type {
+        MigrationType::Mod => "rs",
+        _ => "sql",
+    };
+    // files not matching the migration names that look like they were meant to be migrations
+    let versioned_re = Regex::new(r"^V\d").unwrap();
+
+    let mut errors = Vec::new();
+    let mut versions = HashMap::new();
+    for location in locations {
+        let span = location.span;
+        let valid_files: HashSet<PathBuf> = find_migration_files(&location.path, migration_type)
+            .map_err(|err| syn::Error::new(span, err))?
+            .collect();
+        let mut files: Vec<PathBuf> = walk_migrations_dir(&location.path, &FindOptions::default())
+            .map_err(|err| syn::Error::new(span, err))?
+            .filter(|path| path.extension() == Some(OsStr::new(extension)))
+            .collect();
+        files.sort();
+        //safe to call unwrap as walk_migrations_dir already checked that location exists
+        let canonical_location = location.path.canonicalize().unwrap();
+        // with several locations files are displayed with the location they were found on
+        let display_location = if locations.len() > 1 {
+            location
+                .path
+                .strip_prefix(crate_root())
+                .unwrap_or(&location.path)
+                .to_path_buf()
+        } else {
+            PathBuf::new()
+        };
+
+        for path in files {
+            // the path relative to location, to tell apart files with the same name on different directories
+            //safe to call unwrap as walk_migrations_dir only returns paths below location with a file name
+            let file_name = display_location
+                .join(path.strip_prefix(&canonical_location).unwrap())
+                .display()
+                .to_string();
+            let name = path.file_stem().unwrap().to_string_lossy();
+            if !valid_files.contains(&path) {
+                if versioned_re.is_match(&name) {
+                    errors.push(syn::Error::new(span, format!(
+                        "invalid migration file name {}, migration names must be in the format V{{number}}__{{name}}.{}",
+                        file_name, extension
+                    )));
+                }
+                continue;
+            }
+
+            let contents = fs::read_to_string(&path).map_err(|err| {
+                syn::Error::new(
+                    span,
+                    format!("error reading migration {}: {}", file_name, err),
+                )
+            })?;
+            if contents.trim().is_empty() {
+                errors.push(syn::Error::new(
+                    span,
+                    format!("migration file {} is empty", file_name),
+                ));
+                continue;
+            }
+
+            match Migration::from_filename(&name, &contents) {
+                Ok(migration) => {
+                    if let Some(other) = versions.insert(migration.version, file_name.clone()) {
+                        errors.push(syn::Error::new(
+                            span,
+                            format!(
+                                "migration file {} has the same version {} as {}",
+                                file_name, migration.version, other
+                            ),
+                        ));
+                    }
+                }
+                Err(err) => errors.push(syn::Error::new(
+                    span,
+                    format!("invalid migration file {}, {}", file_name, err),
+                )),
+            }
+        }
+    }
+
+    combine_errors(errors)
+}
+
+// all the errors found are reported at once on the compile_error! output
+fn combine_errors(errors: Vec<syn::Error>) -> Result<(), syn::Error> {
+    let mut errors = errors.into_iter();
+    match errors.next() {
+        None => Ok(()),
+        Some(mut error) => {
+            errors.for_each(|other| error.combine(other));
+            Err(error)
+        }
+    }
+}
+
+// the file name without extension and the file contents, included at compile time
+fn embedded_file_quoted(path: PathBuf) -> TokenStream2 {
+    //safe to call unwrap as find_migration_filenames returns canonical paths
+    let filename = path
+        .file_stem()
+        .and_then(|file| file.to_os_string().into_string().ok())
+        .unwrap();
+    let path = path.display().to_string();
+    quote! {
+        (#filename, include_str!(#path).to_string())
+    }
+}
+
+// how a migration module is run, found by looking up the functions it defines
+enum ModKind {
+    // `fn migration() -> String`, returning the sql to run
+    Sql,
+    // `fn migrate(executor: &mut dyn Executor)`, run inside the migration's transaction
+    Sync,
+    // `async fn migrate(executor: &mut dyn AsyncExecutor)`, run inside the migration's transaction
+    Async,
+}
+
+// errors point at the literal of the location the module was found on
+fn migration_mod_kind(path: &Path, span: Span2) -> Result<ModKind, syn::Error> {
+    let source = fs::read_to_string(path).map_err(|err| {
+        syn::Error::new(
+            span,
+            format!("error reading migration module {}: {}", path.display(), err),
+        )
+    })?;
+    let file = syn::parse_file(&source).map_err(|err| {
+        syn::Error::new(
+            span,
+            format!("error parsing migration module {}: {}", path.display(), err),
+        )
+    })?;
+    for item in file.items {
+        if let Item::Fn(function) = item {
+            if function.sig.ident == "migrate" {
+                return Ok(match function.sig.asyncness {
+                    Some(_) => ModKind::Async,
+                    None => ModKind::Sync,
+                });
+            }
+        }
+    }
+    Ok(ModKind::Sql)
+}
+
 /// Imports Rust migration modules with migrations and inserts a function called runner that when called returns a [`Runner`] instance with the collected migration modules.
 ///
 /// `include_migration_mods` expects to be called from a `mod.rs` file in directory called migrations below the src directory of your Rust project.
 /// if you want the directory to have another name you have to call `include_migration_mods` with it's path relative to the crate root.
 /// In the future this will not be needed and `include_migration_mods` will detect automatically from which module it is being called.
+/// Several comma separated locations can be given, i.e. `include_migration_mods!("src/migrations", "src/plugin_migrations")`,
+/// their modules are merged into the same [`Runner`].
+///
+/// A function called `migrations` returning the collected migrations is also inserted, so that a library crate can export them
+/// to be run by the crates depending on it along their own, i.e. `Runner::new(&[auth::migrations(), migrations::migrations()].concat())`.
+/// Calling the macro with `namespace = "name"` before the locations, i.e. `include_migration_mods!(namespace = "auth", "src/migrations")`,
+/// sets the namespace on the migrations so their versions don't clash with the ones of other crates, see [`Migration::set_namespace`].
 ///
 /// To be a valid migration module, it has to be named in the format `V{1}__{2}.rs ` where `{1}` represents the migration version and `{2}` the name.
 /// For the name alphanumeric characters plus "_" are supported.
-/// The migration module must have a function named `migration()` that returns a [`std::string::String`].
+/// The migration module must have a function named `migration()` that returns a [`std::string::String`],
+/// or a function named `migrate` that runs the migration itself with the [`Executor`] of the migration's transaction,
+/// returning a `Result<(), refinery::MigrationError>`. When `migrate` is an `async fn` it takes an [`AsyncExecutor`] instead,
+/// and the migration can only be run by [`Runner::run_async`]. The checksum of such migrations is computed from the module source.
+///
+/// Duplicate versions, also across locations, files named `V{number}...` that aren't valid migration names, and empty files are reported as compile errors.
 ///
 /// [`Runner`]: https://docs.rs/refinery/latest/refinery/struct.Runner.html
+/// [`Migration::set_namespace`]: https://docs.rs/refinery/latest/refinery/struct.Migration.html#method.set_namespace
+/// [`Executor`]: https://docs.rs/refinery/latest/refinery/trait.Executor.html
+/// [`AsyncExecutor`]: https://docs.rs/refinery/latest/refinery/trait.AsyncExecutor.html
+/// [`Runner::run_async`]: https://docs.rs/refinery/latest/refinery/struct.Runner.html#method.run_async
 ///
 /// # Example using [Barrel](https://docs.rs/barrel/)
 /// ```ignore
@@ -64,30 +324,89 @@ fn migration_fn_quoted<T: ToTokens>(_migrations: Vec<T>) -> TokenStream2 {
 ///    m.make::<MySql>()
 /// }
 /// ```
+///
+/// # Example running queries on the migration's transaction
+/// ```ignore
+/// // module named V2__capitalize_persons_names.rs in src/db/migrations
+/// use refinery::{Executor, MigrationError};
+///
+/// pub fn migrate(executor: &mut dyn Executor) -> Result<(), MigrationError> {
+///     for row in executor.query("SELECT id, name FROM persons")? {
+///         let (id, name) = (row[0].clone().unwrap(), row[1].clone().unwrap_or_default());
+///         executor.execute(&[&format!(
+///             "UPDATE persons SET name = '{}' WHERE id = {}",
+///             name.to_uppercase(),
+///             id
+///         )])?;
+///     }
+///     Ok(())
+/// }
+/// ```
 #[proc_macro]
 pub fn include_migration_mods(input: TokenStream) -> TokenStream {
-    let location = if input.is_empty() {
-        crate_root().join("src").join("migrations")
-    } else {
-        let location: LitStr = parse_macro_input!(input);
-        crate_root().join(location.value())
+    let Args {
+        namespace,
+        locations,
+    } = match parse_args(input, crate_root().join("src").join("migrations")) {
+        Ok(args) => args,
+        Err(err) => return err.to_compile_error().into(),
     };
 
-    let migration_mod_names = find_migration_files(location, MigrationType::Mod)
-        .expect("error getting migration files")
-        .filter_map(|entry| entry.file_stem().and_then(OsStr::to_str).map(String::from));
+    if let Err(err) = validate_migrations(&locations, MigrationType::Mod) {
+        return err.to_compile_error().into();
+    }
+
+    let mut migration_mod_files = Vec::new();
+    for location in &locations {
+        migration_mod_files.extend(
+            find_migration_files(&location.path, MigrationType::Mod)
+                .expect("error getting migration files")
+                .map(|path| (path, location.span)),
+        );
+    }
 
     let mut migrations_mods = Vec::new();
     let mut _migrations = Vec::new();
+    let mut _code_migrations = Vec::new();
+    let mut errors = Vec::new();
 
-    for migration in migration_mod_names {
+    for (path, span) in migration_mod_files {
+        //safe to call unwrap as find_migration_filenames returns canonical paths
+        let migration = path
+            .file_stem()
+            .and_then(|file| file.to_os_string().into_string().ok())
+            .unwrap();
         let ident = Ident::new(migration.as_str(), Span2::call_site());
-        let mig_mod = quote! {pub mod #ident;};
-        _migrations.push(quote! {(#migration, #ident::migration())});
+        let source = path.display().to_string();
+        // modules are included by path, as they may be found on several locations
+        let mig_mod = quote! {
+            #[path = #source]
+            pub mod #ident;
+        };
+        let kind = match migration_mod_kind(&path, span) {
+            Ok(kind) => kind,
+            Err(err) => {
+                errors.push(err);
+                continue;
+            }
+        };
+        match kind {
+            ModKind::Sql => _migrations.push(quote! {(#migration, #ident::migration())}),
+            ModKind::Sync => _code_migrations.push(quote! {
+                Migration::from_code(#migration, refinery::MigrationFn::Sync(#ident::migrate), include_str!(#source)).unwrap()
+            }),
+            ModKind::Async => _code_migrations.push(quote! {
+                Migration::from_code(#migration, refinery::MigrationFn::Async(|executor| Box::pin(#ident::migrate(executor))), include_str!(#source)).unwrap()
+            }),
+        }
         migrations_mods.push(mig_mod);
     }
 
-    let fnq = migration_fn_quoted(_migrations);
+    if let Err(err) = combine_errors(errors) {
+        return err.to_compile_error().into();
+    }
+
+    let fnq = migration_fn_quoted(_migrations, _code_migrations, Vec::new(), namespace);
     let result = quote! {
         #(#migrations_mods)*
 
@@ -100,38 +419,69 @@ pub fn include_migration_mods(input: TokenStream) -> TokenStream {
 ///
 /// When called without arguments `embed_migrations` searches for migration files on a directory called `migrations` at the root level of your crate.
 /// if you want to specify another directory call `embed_migrations!` with it's location relative to the root level of your crate.
+/// Several comma separated locations can be given, i.e. `embed_migrations!("migrations", "plugins/migrations")`,
+/// their migrations and callbacks are merged into the same [`Runner`].
+///
+/// A function called `migrations` returning the collected migrations is also inserted, so that a library crate can export them
+/// to be run by the crates depending on it along their own, i.e. `Runner::new(&[auth::migrations(), migrations::migrations()].concat())`.
+/// Calling the macro with `namespace = "name"` before the locations, i.e. `embed_migrations!(namespace = "auth", "migrations")`,
+/// sets the namespace on the migrations so their versions don't clash with the ones of other crates, see [`Migration::set_namespace`].
 ///
 /// To be a valid migration module, it has to be named in the format `V{1}__{2}.sql ` where `{1}` represents the migration version and `{2}` the name.
 /// For the name alphanumeric characters plus "_"  are supported.
 /// The migration file must have valid sql instructions for the database you want it to run on.
 ///
+/// Callback files named after the event they run on, i.e. `beforeMigrate.sql` or `afterMigrate__grants.sql`,
+/// are also embedded and added to the [`Runner`] sorted by name, see [`SqlCallback`].
+///
+/// Duplicate versions, also across locations, files named `V{number}...` that aren't valid migration names, and empty files are reported as compile errors.
+///
+/// Cargo only rebuilds the crate when an embedded file changes, to also rebuild it when migration files are added,
+/// call [`rerun_if_migrations_changed`] with the migrations location from the crate's `build.rs`:
+/// ```ignore
+/// // build.rs, with refinery also on [build-dependencies]
+/// fn main() {
+///     refinery::rerun_if_migrations_changed("./migrations").unwrap();
+/// }
+/// ```
+///
 /// [`Runner`]: https://docs.rs/refinery/latest/refinery/struct.Runner.html
+/// [`Migration::set_namespace`]: https://docs.rs/refinery/latest/refinery/struct.Migration.html#method.set_namespace
+/// [`rerun_if_migrations_changed`]: https://docs.rs/refinery/latest/refinery/fn.rerun_if_migrations_changed.html
+/// [`SqlCallback`]: https://docs.rs/refinery/latest/refinery/struct.SqlCallback.html
 #[proc_macro]
 pub fn embed_migrations(input: TokenStream) -> TokenStream {
-    let location = if input.is_empty() {
-        crate_root().join("migrations")
-    } else {
-        let location: LitStr = parse_macro_input!(input);
-        crate_root().join(location.value())
+    let Args {
+        namespace,
+        locations,
+    } = match parse_args(input, crate_root().join("migrations")) {
+        Ok(args) => args,
+        Err(err) => return err.to_compile_error().into(),
     };
 
-    let migration_files =
-        find_migration_files(location, MigrationType::Sql).expect("error getting migration files");
+    if let Err(err) = validate_migrations(&locations, MigrationType::Sql) {
+        return err.to_compile_error().into();
+    }
 
     let mut _migrations = Vec::new();
-    for path in migration_files {
-        //safe to call unwrap as find_migration_filenames returns canonical paths
-        let filename = path
-            .file_stem()
-            .and_then(|file| file.to_os_string().into_string().ok())
-            .unwrap();
-        let path = path.display().to_string();
-        _migrations.push(quote! {
-            (#filename, include_str!(#path).to_string())
-        });
+    let mut callback_files: Vec<PathBuf> = Vec::new();
+    for location in &locations {
+        let migration_files = find_migration_files(&location.path, MigrationType::Sql)
+            .expect("error getting migration files");
+        _migrations.extend(migration_files.map(embedded_file_quoted));
+
+        callback_files.extend(
+            find_migration_files(&location.path, MigrationType::Callback)
+                .expect("error getting callback files"),
+        );
     }
+    callback_files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    let _callbacks = callback_files
+        .into_iter()
+        .map(embedded_file_quoted)
+        .collect();
 
-    let fnq = migration_fn_quoted(_migrations);
+    let fnq = migration_fn_quoted(_migrations, Vec::new(), _callbacks, namespace);
     (quote! {
         pub mod migrations {
             #fnq
@@ -142,21 +492,163 @@ pub fn embed_migrations(input: TokenStream) -> TokenStream {
 
 #[cfg(test)]
 mod tests {
-    use super::{migration_fn_quoted, quote};
+    use super::{
+        migration_fn_quoted, migration_mod_kind, quote, validate_migrations, Location, ModKind,
+    };
+    use proc_macro2::Span;
+    use refinery_core::MigrationType;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn locations_validation_errors(locations: &[&TempDir]) -> Vec<String> {
+        let locations: Vec<Location> = locations
+            .iter()
+            .map(|tmp_dir| Location {
+                path: tmp_dir.path().to_path_buf(),
+                span: Span::call_site(),
+            })
+            .collect();
+        match validate_migrations(&locations, MigrationType::Sql) {
+            Ok(()) => Vec::new(),
+            Err(err) => err.into_iter().map(|err| err.to_string()).collect(),
+        }
+    }
+
+    fn write_files(files: &[(&str, &str)]) -> TempDir {
+        let tmp_dir = TempDir::new().unwrap();
+        for (name, contents) in files {
+            fs::write(tmp_dir.path().join(name), contents).unwrap();
+        }
+        tmp_dir
+    }
+
+    fn validation_errors(files: &[(&str, &str)]) -> Vec<String> {
+        locations_validation_errors(&[&write_files(files)])
+    }
+
+    #[test]
+    fn validates_migration_files() {
+        let errors = validation_errors(&[
+            ("V1__initial.sql", "CREATE TABLE persons (id int);"),
+            ("V2__add_cars_table.sql", "CREATE TABLE cars (id int);"),
+            ("afterMigrate.sql", "SELECT 1;"),
+            ("seed.sql", "INSERT INTO persons VALUES (1);"),
+        ]);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn fails_on_duplicate_versions() {
+        let errors = validation_errors(&[
+            ("V1__initial.sql", "CREATE TABLE persons (id int);"),
+            ("V1__add_cars_table.sql", "CREATE TABLE cars (id int);"),
+        ]);
+        assert_eq!(
+            vec!["migration file V1__initial.sql has the same version 1 as V1__add_cars_table.sql"],
+            errors
+        );
+    }
+
+    #[test]
+    fn fails_on_duplicate_versions_across_locations() {
+        let first = write_files(&[("V1__initial.sql", "CREATE TABLE persons (id int);")]);
+        let second = write_files(&[
+            ("V2__add_cars_table.sql", "CREATE TABLE cars (id int);"),
+            ("V1__add_motos_table.sql", "CREATE TABLE motos (id int);"),
+        ]);
+        let errors = locations_validation_errors(&[&first, &second]);
+        assert_eq!(1, errors.len());
+        assert_eq!(
+            format!(
+                "migration file {} has the same version 1 as {}",
+                second.path().join("V1__add_motos_table.sql").display(),
+                first.path().join("V1__initial.sql").display()
+            ),
+            errors[0]
+        );
+    }
+
+    #[test]
+    fn validates_migrations_across_locations() {
+        let first = write_files(&[("V1__initial.sql", "CREATE TABLE persons (id int);")]);
+        let second = write_files(&[("V2__add_cars_table.sql", "CREATE TABLE cars (id int);")]);
+        assert!(locations_validation_errors(&[&first, &second]).is_empty());
+    }
+
+    #[test]
+    fn fails_on_invalid_names_and_empty_files() {
+        let errors = validation_errors(&[
+            ("V1_initial.sql", "CREATE TABLE persons (id int);"),
+            ("V1.5__add_cars_table.sql", "CREATE TABLE cars (id int);"),
+            ("V2__add_motos_table.sql", "\n"),
+        ]);
+        assert_eq!(3, errors.len());
+        assert!(errors[0].starts_with("invalid migration file V1.5__add_cars_table.sql"));
+        assert!(errors[1].starts_with("invalid migration file name V1_initial.sql"));
+        assert_eq!("migration file V2__add_motos_table.sql is empty", errors[2]);
+    }
+
+    #[test]
+    fn finds_the_kind_of_migration_modules() {
+        let tmp_dir = write_files(&[
+            (
+                "V1__initial.rs",
+                "pub fn migration() -> String { String::new() }",
+            ),
+            (
+                "V2__sync.rs",
+                "pub fn migrate(executor: &mut dyn Executor) {}",
+            ),
+            (
+                "V3__async.rs",
+                "pub async fn migrate(executor: &mut dyn AsyncExecutor) {}",
+            ),
+        ]);
+        let kind = |name| migration_mod_kind(&tmp_dir.path().join(name), Span::call_site());
+        assert!(matches!(kind("V1__initial.rs"), Ok(ModKind::Sql)));
+        assert!(matches!(kind("V2__sync.rs"), Ok(ModKind::Sync)));
+        assert!(matches!(kind("V3__async.rs"), Ok(ModKind::Async)));
+    }
+
+    #[test]
+    fn fails_on_migration_modules_that_dont_parse() {
+        let tmp_dir = write_files(&[("V1__initial.rs", "pub fn migration( -> String {")]);
+        let path = tmp_dir.path().join("V1__initial.rs");
+        let err = match migration_mod_kind(&path, Span::call_site()) {
+            Err(err) => err.to_string(),
+            Ok(_) => panic!("migration module parsed"),
+        };
+        assert!(err.starts_with(&format!(
+            "error parsing migration module {}",
+            path.display()
+        )));
+    }
 
     #[test]
     fn test_quote_fn() {
         let migs = vec![quote!("V1__first", "valid_sql_file")];
+        let callbacks = vec![quote!("afterMigrate", "valid_sql_file")];
         let expected = concat! {
-            "use refinery :: { Migration , Runner } ; ",
-            "pub fn runner ( ) -> Runner { ",
+            "use refinery :: { Migration , Runner , SqlCallback } ; ",
+            "pub fn migrations ( ) -> Vec < Migration > { ",
             "let quoted_migrations : Vec < ( & str , String ) > = vec ! [ \"V1__first\" , \"valid_sql_file\" ] ; ",
-            "let mut migrations : Vec < Migration > = Vec :: new ( ) ; ",
+            "let mut migrations : Vec < Migration > = vec ! [ ] ; ",
             "for module in quoted_migrations . into_iter ( ) { ",
             "migrations . push ( Migration :: from_filename ( module . 0 , & module . 1 ) . unwrap ( ) ) ; ",
             "} ",
-            "Runner :: new ( & migrations ) }"
+            "migrations ",
+            "} ",
+            "pub fn runner ( ) -> Runner { ",
+            "let quoted_callbacks : Vec < ( & str , String ) > = vec ! [ \"afterMigrate\" , \"valid_sql_file\" ] ; ",
+            "let mut runner = Runner :: new ( & migrations ( ) ) ; ",
+            "for callback in quoted_callbacks . into_iter ( ) { ",
+            "runner = runner . add_sql_callback ( SqlCallback :: from_filename ( callback . 0 , & callback . 1 ) . unwrap ( ) ) ; ",
+            "} ",
+            "runner }"
         };
-        assert_eq!(expected, migration_fn_quoted(migs).to_string());
+        assert_eq!(
+            expected,
+            migration_fn_quoted(migs, Vec::new(), callbacks, None).to_string()
+        );
     }
 }